@@ -12,6 +12,6 @@ fn main() {
     let args = Args::parse();
     // Create directory if it does not exist
     std::fs::create_dir_all(&args.output).unwrap();
-    std::fs::write(format!("{}/network.yaml", args.output), serde_yaml::to_string(&Network::crd()).unwrap()).unwrap();
+    std::fs::write(format!("{}/network.yaml", args.output), serde_yaml::to_string(&Network::crd_preserving_unknown_fields()).unwrap()).unwrap();
     std::fs::write(format!("{}/router.yaml", args.output), serde_yaml::to_string(&Router::crd()).unwrap()).unwrap();
 }
\ No newline at end of file
@@ -7,7 +7,7 @@ use kube::{
     },
     Client,
 };
-use operator::controller::Network;
+use operator::controller::{Network, NetworkSpec};
 use std::{convert::Infallible, env, error::Error};
 use tracing::*;
 use warp::{reply, Filter, Reply};
@@ -24,10 +24,18 @@ async fn main() -> anyhow::Result<()> {
     let listen_ip = env::var("NDN_INJECTOR_IP").unwrap_or("0.0.0.0".to_string()).parse::<std::net::IpAddr>()?;
     let cert_path = env::var("NDN_INJECTOR_TLS_CERT_FILE").unwrap_or("tls.crt".to_string());
     let key_path = env::var("NDN_INJECTOR_TLS_KEY_FILE").unwrap_or("tls.key".to_string());
+    // In strict mode Networks with unknown spec fields are rejected instead of only warned about
+    let strict = env::var("NDN_INJECTOR_STRICT").map(|v| v == "true").unwrap_or(false);
 
-    let routes = warp::path::end()
+    let mutate = warp::path::end()
         .and(warp::body::json())
-        .and_then(mutate_handler)
+        .and_then(mutate_handler);
+    let validate_network = warp::path!("validate-network")
+        .and(warp::body::json())
+        .and(warp::any().map(move || strict))
+        .and_then(validate_network_handler);
+    let routes = mutate
+        .or(validate_network)
         .with(warp::trace::request());
 
 
@@ -52,34 +60,115 @@ async fn mutate_handler(body: AdmissionReview<DynamicObject>) -> Result<impl Rep
     };
 
     let mut res = AdmissionResponse::from(&req);
-    if let Some(obj) = req.object {
-        if let Ok(pod) = obj.try_parse::<Pod>() {
-            let name = pod.name_any();
-            if let Operation::Create = req.operation {
-                if let Some(network_name) = pod.annotations().get(ANNOTATION_NAME) {
-                    let network_namespace = match pod.annotations().get(ANNOTATION_NAMESPACE) {
-                        Some(ns) => ns,
-                        None => &pod.namespace().unwrap(),
-                    };
-                    res = match mutate(res.clone(), &pod, network_name, network_namespace).await {
-                        Ok(res) => {
-                            info!("accepted: {:?} on {}", req.operation, name);
-                            res
-                        }
-                        Err(err) => {
-                            warn!("denied: {:?} on {} ({})", req.operation, name, err);
-                            res.deny(err.to_string())
-                        }
-                    };
-                };
+    if let Some(obj) = req.object
+        && let Ok(pod) = obj.try_parse::<Pod>()
+        && let Operation::Create = req.operation
+        && let Some(network_name) = pod.annotations().get(ANNOTATION_NAME)
+    {
+        let name = pod.name_any();
+        let network_namespace = match pod.annotations().get(ANNOTATION_NAMESPACE) {
+            Some(ns) => ns,
+            None => &pod.namespace().unwrap(),
+        };
+        res = match mutate(res.clone(), &pod, network_name, network_namespace).await {
+            Ok(res) => {
+                info!("accepted: {:?} on {}", req.operation, name);
+                res
             }
-        }
+            Err(err) => {
+                warn!("denied: {:?} on {} ({})", req.operation, name, err);
+                res.deny(err.to_string())
+            }
+        };
     }
     // Wrap the AdmissionResponse wrapped in an AdmissionReview
     Ok(reply::json(&res.into_review()))
 }
 
 
+async fn validate_network_handler(body: AdmissionReview<DynamicObject>, strict: bool) -> Result<impl Reply, Infallible> {
+    let req: AdmissionRequest<_> = match body.try_into() {
+        Ok(req) => req,
+        Err(err) => {
+            error!("invalid request: {}", err.to_string());
+            return Ok(reply::json(
+                &AdmissionResponse::invalid(err.to_string()).into_review(),
+            ));
+        }
+    };
+
+    let mut res = AdmissionResponse::from(&req);
+    if let Some(obj) = req.object {
+        let name = obj.name_any();
+        let spec = obj.data.get("spec").cloned().unwrap_or_default();
+        let namespace = req.namespace.clone().unwrap_or_default();
+        let validated = match validate_network(res.clone(), &spec, strict).map_err(|e| e.to_string()) {
+            Ok(res) if res.allowed => check_prefix_overlap(res, &name, &namespace, &spec).await.map_err(|e| e.to_string()),
+            validated => validated,
+        };
+        let validated = match validated {
+            Ok(res) if res.allowed => check_metrics_port(res, &name, &namespace, &spec).await.map_err(|e| e.to_string()),
+            validated => validated,
+        };
+        res = match validated {
+            Ok(res) => res,
+            Err(err) => {
+                warn!("denied: {:?} on {} ({})", req.operation, name, err);
+                res.deny(err)
+            }
+        };
+    }
+    Ok(reply::json(&res.into_review()))
+}
+
+fn validate_network(mut res: AdmissionResponse, spec: &serde_json::Value, strict: bool) -> Result<AdmissionResponse, Box<dyn Error>> {
+    let unknown = NetworkSpec::unknown_fields(spec)?;
+    if !unknown.is_empty() {
+        let message = format!("unknown fields: {}", unknown.join(", "));
+        if strict {
+            return Ok(res.deny(message));
+        }
+        res.warnings = Some(vec![message]);
+    }
+    let spec: NetworkSpec = serde_json::from_value(spec.clone())?;
+    spec.validate()?;
+    Ok(res)
+}
+
+/// Deny Networks whose prefix is an ancestor or descendant of another Network's in the namespace
+async fn check_prefix_overlap(res: AdmissionResponse, name: &str, namespace: &str, spec: &serde_json::Value) -> Result<AdmissionResponse, Box<dyn Error>> {
+    let client = Client::try_default().await?;
+    let api_network = kube::Api::<Network>::namespaced(client, namespace);
+    let networks = api_network.list(&Default::default()).await?.items;
+    let mut network = Network::new(name, serde_json::from_value(spec.clone())?);
+    network.metadata.namespace = Some(namespace.to_string());
+    match network.overlapping(&networks).first() {
+        Some(other) => Ok(res.deny(format!(
+            "prefix {} overlaps prefix {} of Network {}",
+            network.spec.prefix, other.spec.prefix, other.name_any()
+        ))),
+        None => Ok(res),
+    }
+}
+
+/// Deny Networks serving metrics on the host port of another Network in any namespace
+async fn check_metrics_port(res: AdmissionResponse, name: &str, namespace: &str, spec: &serde_json::Value) -> Result<AdmissionResponse, Box<dyn Error>> {
+    let mut network = Network::new(name, serde_json::from_value(spec.clone())?);
+    if network.spec.metrics_port.is_none() {
+        return Ok(res);
+    }
+    network.metadata.namespace = Some(namespace.to_string());
+    let client = Client::try_default().await?;
+    let networks = kube::Api::<Network>::all(client).list(&Default::default()).await?.items;
+    match network.metrics_port_conflicts(&networks).first() {
+        Some(other) => Ok(res.deny(format!(
+            "metrics port {} is also used by Network {}/{}",
+            network.spec.metrics_port.unwrap_or_default(), other.namespace().unwrap_or_default(), other.name_any()
+        ))),
+        None => Ok(res),
+    }
+}
+
 async fn mutate(res: AdmissionResponse, pod: &Pod, network_name: &String, network_namespace: &String) -> Result<AdmissionResponse, Box<dyn Error>> {
 
     let client = Client::try_default().await.expect("Expected a valid KUBECONFIG environment variable");
@@ -174,3 +263,42 @@ fn create_env_var_prefix(network: &Network) -> EnvVar {
         ..EnvVar::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response() -> AdmissionResponse {
+        let review: AdmissionReview<DynamicObject> = serde_json::from_value(json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "uid",
+                "kind": { "group": "named-data.net", "version": "v1alpha1", "kind": "Network" },
+                "resource": { "group": "named-data.net", "version": "v1alpha1", "resource": "networks" },
+                "operation": "CREATE",
+                "userInfo": {},
+                "name": "net",
+                "namespace": "ns",
+            },
+        }))
+        .unwrap();
+        let req: AdmissionRequest<DynamicObject> = review.try_into().unwrap();
+        AdmissionResponse::from(&req)
+    }
+
+    #[test]
+    fn unknown_fields_are_denied_in_strict_mode() {
+        let spec = json!({ "prefix": "/net", "udpUnicastPort": 6363, "nodeSelectr": { "ndn": "true" } });
+        let res = validate_network(response(), &spec, true).unwrap();
+        assert!(!res.allowed);
+        assert!(res.result.message.contains("spec.nodeSelectr"));
+        // Only warned about otherwise
+        let res = validate_network(response(), &spec, false).unwrap();
+        assert!(res.allowed);
+        assert_eq!(res.warnings, Some(vec!["unknown fields: spec.nodeSelectr".to_string()]));
+        let res = validate_network(response(), &json!({ "prefix": "/net", "udpUnicastPort": 6363 }), true).unwrap();
+        assert!(res.allowed && res.warnings.is_none());
+    }
+}
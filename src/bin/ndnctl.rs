@@ -0,0 +1,603 @@
+use clap::{Parser, Subcommand};
+use k8s_openapi::api::{apps::v1::DaemonSet, core::v1::Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{
+    api::{DeleteParams, ListParams, Patch, PatchParams, ResourceExt},
+    Api, Client, CustomResourceExt, Resource,
+};
+use operator::controller::{
+    pending_neighbor_updates, Network, Router, DS_LABEL_KEY, INIT_CONTAINER_NAME, NETWORK_FINALIZER, NETWORK_LABEL_KEY,
+    POD_FINALIZER, POD_LABEL_KEY, ROUTER_FINALIZER, VERSION_ANNOTATION_KEY,
+};
+use std::{collections::BTreeMap, fmt::Debug, time::Duration};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+/// Administrative commands for NDN Networks
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show what the operator would change to reconcile a Network manifest
+    Diff {
+        /// Network manifest
+        #[arg(short, long)]
+        file: String,
+        /// Namespace of the Network, if not set in the manifest
+        #[arg(short, long)]
+        namespace: Option<String>,
+        /// Operator image used for the init and sidecar containers, defaults to the live DaemonSet's
+        #[arg(long)]
+        image: Option<String>,
+    },
+    /// Export a Network as a portable manifest
+    Export {
+        /// Name of the Network
+        name: String,
+        /// Namespace of the Network
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        /// File to write the manifest to, stdout by default
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Recreate a Network from an exported manifest. Its Routers are created with the router pods
+    Import {
+        /// Manifest written by `export`
+        #[arg(short, long)]
+        file: String,
+        /// Namespace to import into
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+    },
+    /// Tear down a stuck Network and its children in order, without the operator: its DaemonSets
+    /// and router pods, then its Routers, then the Network, removing their finalizers
+    ForceDelete {
+        /// Name of the Network
+        #[arg(long)]
+        network: String,
+        /// Namespace of the Network
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        /// Seconds to wait for the router pods to stop and remove their sockets from the nodes
+        #[arg(long, default_value_t = 60)]
+        timeout_seconds: u64,
+    },
+    /// Print the OpenAPI schemas of the Network and Router CRDs as JSON, for client generators
+    Schema {
+        /// Only print the schema of this kind, `Network` or `Router`
+        #[arg(short, long)]
+        kind: Option<String>,
+    },
+    /// Print a PrometheusRule with alerts on the operator metrics
+    #[cfg(feature = "metrics")]
+    Alerts {
+        /// Namespace of the PrometheusRule
+        #[arg(short, long, default_value = "ndn-operator")]
+        namespace: String,
+        /// Label matchers selecting the operator's series, e.g. `job="ndn-operator"`
+        #[arg(long, default_value = "")]
+        selector: String,
+        /// Ratio of failed reconciles over 5 minutes above which to alert
+        #[arg(long, default_value_t = 0.1)]
+        error_ratio: f64,
+        /// Minutes without a successful reconcile of a kind before alerting.
+        /// Objects are only reconciled on changes, so keep it above the quiet periods of the cluster
+        #[arg(long, default_value_t = 60)]
+        stale_minutes: u64,
+        /// Minutes a router condition must last before alerting
+        #[arg(long, default_value_t = 10)]
+        for_minutes: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    match args.command {
+        Command::Diff { file, namespace, image } => diff(&file, namespace, image).await,
+        Command::Export { name, namespace, output } => export(&name, &namespace, output).await,
+        Command::Import { file, namespace } => import(&file, &namespace).await,
+        Command::ForceDelete { network, namespace, timeout_seconds } => {
+            let client = Client::try_default().await?;
+            force_delete(client, &network, &namespace, Duration::from_secs(timeout_seconds)).await
+        }
+        Command::Schema { kind } => {
+            println!("{}", serde_json::to_string_pretty(&crd_schemas(kind.as_deref())?)?);
+            Ok(())
+        }
+        #[cfg(feature = "metrics")]
+        Command::Alerts { namespace, selector, error_ratio, stale_minutes, for_minutes } => {
+            print!("{}", serde_yaml::to_string(&alert_rules(&namespace, &selector, error_ratio, stale_minutes, for_minutes))?);
+            Ok(())
+        }
+    }
+}
+
+/// OpenAPI schemas of the served version of the CRDs, by kind
+fn crd_schemas(kind: Option<&str>) -> anyhow::Result<Value> {
+    let mut schemas = serde_json::Map::new();
+    for crd in [Network::crd(), Router::crd()] {
+        if kind.is_some_and(|kind| !kind.eq_ignore_ascii_case(&crd.spec.names.kind)) {
+            continue;
+        }
+        let schema = crd
+            .spec
+            .versions
+            .iter()
+            .find(|version| version.served)
+            .and_then(|version| version.schema.as_ref())
+            .and_then(|validation| validation.open_api_v3_schema.as_ref());
+        schemas.insert(crd.spec.names.kind.clone(), serde_json::to_value(schema)?);
+    }
+    if schemas.is_empty() {
+        anyhow::bail!("unknown kind `{}`, expected `Network` or `Router`", kind.unwrap_or_default());
+    }
+    Ok(Value::Object(schemas))
+}
+
+/// PrometheusRule alerting on the metrics registered by `operator::metrics`
+#[cfg(feature = "metrics")]
+fn alert_rules(namespace: &str, selector: &str, error_ratio: f64, stale_minutes: u64, for_minutes: u64) -> Value {
+    use operator::metrics::{Metrics, LAST_RECONCILE, RECONCILE_ERRORS, RECONCILIATIONS, ROUTER_EXPECTED_NEIGHBORS, ROUTER_NEIGHBORS};
+    let metrics = Metrics::default();
+    let series = |name: &str| {
+        let name = metrics.series_name(name).expect("Alert rules refer to an unregistered metric");
+        format!("{name}{{{selector}}}")
+    };
+    let rule = |alert: &str, expr: String, for_minutes: u64, severity: &str, summary: &str| {
+        json!({
+            "alert": alert,
+            "expr": expr,
+            "for": format!("{for_minutes}m"),
+            "labels": { "severity": severity },
+            "annotations": { "summary": summary },
+        })
+    };
+    json!({
+        "apiVersion": "monitoring.coreos.com/v1",
+        "kind": "PrometheusRule",
+        "metadata": {
+            "name": "ndn-operator",
+            "namespace": namespace,
+        },
+        "spec": {
+            "groups": [{
+                "name": "ndn-operator",
+                "rules": [
+                    rule(
+                        "NdnOperatorReconcileErrors",
+                        format!(
+                            "sum by (kind) (rate({errors}[5m])) / (sum by (kind) (rate({errors}[5m])) + sum by (kind) (rate({ok}[5m]))) > {error_ratio}",
+                            errors = series(RECONCILE_ERRORS),
+                            ok = series(RECONCILIATIONS),
+                        ),
+                        5,
+                        "warning",
+                        "{{ $value | humanizePercentage }} of the {{ $labels.kind }} reconciles fail",
+                    ),
+                    rule(
+                        "NdnOperatorReconcileStale",
+                        format!("time() - max by (kind) ({}) > {}", series(LAST_RECONCILE), stale_minutes * 60),
+                        0,
+                        "warning",
+                        "No {{ $labels.kind }} was reconciled successfully recently",
+                    ),
+                    rule(
+                        "NdnRouterWithoutNeighbors",
+                        format!("{} == 0 and {} > 0", series(ROUTER_NEIGHBORS), series(ROUTER_EXPECTED_NEIGHBORS)),
+                        for_minutes,
+                        "critical",
+                        "Router {{ $labels.namespace }}/{{ $labels.router }} has no neighbors",
+                    ),
+                    rule(
+                        "NdnRouterFacesDrift",
+                        format!("{} < {}", series(ROUTER_NEIGHBORS), series(ROUTER_EXPECTED_NEIGHBORS)),
+                        for_minutes,
+                        "warning",
+                        "Router {{ $labels.namespace }}/{{ $labels.router }} misses the faces of some online routers",
+                    ),
+                ],
+            }],
+        },
+    })
+}
+
+static FIELD_MANAGER: &str = "ndnctl";
+
+/// Portable part of an object's metadata. The namespace, uid, resource version, owner
+/// references, finalizers and annotations maintained by the operator belong to the source cluster
+fn portable_metadata(metadata: &ObjectMeta) -> ObjectMeta {
+    let strip = |map: &Option<BTreeMap<String, String>>, keys: &[&str]| {
+        map.clone()
+            .map(|map| map.into_iter().filter(|(key, _)| !keys.contains(&key.as_str())).collect::<BTreeMap<_, _>>())
+            .filter(|map| !map.is_empty())
+    };
+    ObjectMeta {
+        name: metadata.name.clone(),
+        labels: strip(&metadata.labels, &[POD_LABEL_KEY]),
+        annotations: strip(
+            &metadata.annotations,
+            &[VERSION_ANNOTATION_KEY, "kubectl.kubernetes.io/last-applied-configuration"],
+        ),
+        ..ObjectMeta::default()
+    }
+}
+
+/// Network as a `v1/List`. Only the desired state is portable: the Routers are bound to the
+/// nodes and pods of the source cluster, and are recreated with the router pods on import
+fn export_manifest(network: &Network) -> anyhow::Result<Value> {
+    let network = Network {
+        metadata: portable_metadata(&network.metadata),
+        spec: network.spec.clone(),
+        status: None,
+    };
+    Ok(json!({
+        "apiVersion": "v1",
+        "kind": "List",
+        "items": [serde_json::to_value(network)?],
+    }))
+}
+
+async fn export(name: &str, namespace: &str, output: Option<String>) -> anyhow::Result<()> {
+    let client = Client::try_default().await?;
+    let network = Api::<Network>::namespaced(client, namespace).get(name).await?;
+    let manifest = serde_yaml::to_string(&export_manifest(&network)?)?;
+    match output {
+        Some(file) => std::fs::write(file, manifest)?,
+        None => print!("{manifest}"),
+    }
+    Ok(())
+}
+
+/// Network of an exported manifest. Routers of manifests exported by older versions are
+/// skipped, the pods of the imported Network create their own
+fn read_manifest(manifest: &str) -> anyhow::Result<Network> {
+    let manifest: Value = serde_yaml::from_str(manifest)?;
+    let mut network = None;
+    for item in manifest["items"].as_array().into_iter().flatten() {
+        match item["kind"].as_str() {
+            Some("Network") => network = Some(serde_json::from_value::<Network>(item.clone())?),
+            Some("Router") => eprintln!("Skipping Router {}, Routers are created with the router pods", item["metadata"]["name"]),
+            kind => anyhow::bail!("Unexpected {kind:?} in the manifest"),
+        }
+    }
+    network.ok_or(anyhow::anyhow!("The manifest has no Network"))
+}
+
+async fn import(file: &str, namespace: &str) -> anyhow::Result<()> {
+    let client = Client::try_default().await?;
+    let network = read_manifest(&std::fs::read_to_string(file)?)?;
+    let api_nw = Api::<Network>::namespaced(client, namespace);
+    let network = api_nw.patch(&network.name_any(), &PatchParams::apply(FIELD_MANAGER), &Patch::Apply(&network)).await?;
+    println!("Network {}/{} imported", namespace, network.name_any());
+    Ok(())
+}
+
+/// Remove a finalizer from an object, so its deletion completes without its controller
+async fn remove_finalizer<K>(api: &Api<K>, obj: &K, finalizer: &str) -> anyhow::Result<()>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    if !obj.finalizers().iter().any(|f| f == finalizer) {
+        return Ok(());
+    }
+    let finalizers: Vec<&String> = obj.finalizers().iter().filter(|f| *f != finalizer).collect();
+    let patch = Patch::Merge(json!({ "metadata": { "finalizers": finalizers } }));
+    api.patch(&obj.name_any(), &PatchParams::default(), &patch).await?;
+    Ok(())
+}
+
+async fn force_delete(client: Client, name: &str, namespace: &str, timeout: Duration) -> anyhow::Result<()> {
+    let api_nw = Api::<Network>::namespaced(client.clone(), namespace);
+    let api_ds = Api::<DaemonSet>::namespaced(client.clone(), namespace);
+    let api_pod = Api::<Pod>::namespaced(client.clone(), namespace);
+    let api_rt = Api::<Router>::namespaced(client, namespace);
+    let network = api_nw.get(name).await?;
+    let dp = DeleteParams::default();
+
+    // Stop the routers first, their sidecars remove the sockets from the nodes as they stop
+    let ds_lp = ListParams::default().labels(&format!("{DS_LABEL_KEY}={name}"));
+    for ds in api_ds.list(&ds_lp).await? {
+        api_ds.delete(&ds.name_any(), &dp).await?;
+        println!("DaemonSet {}/{} deleted", namespace, ds.name_any());
+    }
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let pods = api_pod.list(&ds_lp).await?.items;
+        if pods.is_empty() {
+            break;
+        }
+        for pod in &pods {
+            remove_finalizer(&api_pod, pod, POD_FINALIZER).await?;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            eprintln!("{} router pods still running, their sockets may be left on the nodes", pods.len());
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    // All the Routers go, so there are no neighbor sets left to clean up
+    let rt_lp = ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={name}"));
+    for router in api_rt.list(&rt_lp).await? {
+        api_rt.delete(&router.name_any(), &dp).await?;
+        remove_finalizer(&api_rt, &router, ROUTER_FINALIZER).await?;
+        println!("Router {}/{} deleted", namespace, router.name_any());
+    }
+
+    api_nw.delete(name, &dp).await?;
+    remove_finalizer(&api_nw, &network, NETWORK_FINALIZER).await?;
+    println!("Network {}/{} deleted", namespace, name);
+    Ok(())
+}
+
+fn read_network(file: &str, namespace: Option<String>) -> anyhow::Result<Network> {
+    let mut network: Network = serde_yaml::from_str(&std::fs::read_to_string(file)?)?;
+    if namespace.is_some() {
+        network.metadata.namespace = namespace;
+    }
+    if network.metadata.namespace.is_none() {
+        anyhow::bail!("Network namespace is not set, use --namespace");
+    }
+    Ok(network)
+}
+
+async fn diff(file: &str, namespace: Option<String>, image: Option<String>) -> anyhow::Result<()> {
+    let client = Client::try_default().await?;
+    let desired = read_network(file, namespace)?;
+    let ns = desired.namespace().unwrap();
+    let api_nw = Api::<Network>::namespaced(client.clone(), &ns);
+    let api_ds = Api::<DaemonSet>::namespaced(client.clone(), &ns);
+    let api_rt = Api::<Router>::namespaced(client.clone(), &ns);
+
+    // The desired state is computed on the live object, which carries the uid for owner references
+    let live = api_nw.get_opt(&desired.name_any()).await?;
+    let Some(live) = live else {
+        println!("Network {}/{} doesn't exist, it would be created", ns, desired.name_any());
+        return Ok(());
+    };
+    let mut network = live.clone();
+    network.spec = desired.spec.clone();
+    print_diff("Network", &json!({ "spec": live.spec }), &json!({ "spec": network.spec }))?;
+
+    let live_ds = api_ds.get_opt(&network.name_any()).await?;
+    let image = image.or_else(|| {
+        live_ds.as_ref()
+            .and_then(|ds| ds.spec.as_ref())
+            .and_then(|spec| spec.template.spec.as_ref())
+            .and_then(|spec| spec.init_containers.as_ref())
+            .and_then(|containers| containers.iter().find(|container| container.name == INIT_CONTAINER_NAME))
+            .and_then(|container| container.image.clone())
+    });
+    let desired_ds = serde_json::to_value(network.create_owned_daemonset(image, Some(network.name_any())))?;
+    let live_ds = match live_ds {
+        Some(ds) => prune_to(&serde_json::to_value(ds)?, &desired_ds),
+        None => Value::Null,
+    };
+    print_diff("DaemonSet", &live_ds, &desired_ds)?;
+
+    let lp = ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={}", network.name_any()));
+    let routers = api_rt.list(&lp).await?.items;
+    let roles = network.router_roles(client.clone(), &routers).await?;
+    let links = network.router_links(client, &routers, &roles).await?;
+    for (router, neighbors) in pending_neighbor_updates(&routers, &network.spec.face_policy()?, &roles, links.as_ref()) {
+        let current = router.status.as_ref().map(|status| status.neighbors.clone()).unwrap_or_default();
+        print_diff(
+            &format!("Router {}", router.name_any()),
+            &json!({ "status": { "neighbors": current } }),
+            &json!({ "status": { "neighbors": neighbors } }),
+        )?;
+    }
+    Ok(())
+}
+
+/// Keep only the fields of `live` that are also set in `desired`, dropping
+/// defaults and status filled in by the API server
+fn prune_to(live: &Value, desired: &Value) -> Value {
+    match (live, desired) {
+        (Value::Object(live), Value::Object(desired)) => Value::Object(
+            live.iter()
+                .filter_map(|(key, value)| desired.get(key).map(|d| (key.clone(), prune_to(value, d))))
+                .collect(),
+        ),
+        (Value::Array(live), Value::Array(desired)) => Value::Array(
+            live.iter()
+                .enumerate()
+                .map(|(i, value)| match desired.get(i) {
+                    Some(d) => prune_to(value, d),
+                    None => value.clone(),
+                })
+                .collect(),
+        ),
+        _ => live.clone(),
+    }
+}
+
+fn print_diff<T: Serialize>(name: &str, live: &T, desired: &T) -> anyhow::Result<()> {
+    let live = match serde_json::to_value(live)? {
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(&value)?,
+    };
+    let desired = serde_yaml::to_string(desired)?;
+    let diff = unified_diff(&live, &desired, 3);
+    if !diff.is_empty() {
+        println!("--- live/{name}\n+++ desired/{name}\n{diff}");
+    }
+    Ok(())
+}
+
+/// Line based unified diff of two texts with `context` unchanged lines around each change
+fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // Longest common subsequence table, lcs[i][j] is the LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(('+', new[j]));
+            j += 1;
+        } else {
+            lines.push(('-', old[i]));
+            i += 1;
+        }
+    }
+    // Keep changed lines and their context, separating distant hunks
+    let changed: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    let mut out = String::new();
+    let mut last_printed: Option<usize> = None;
+    for (k, (tag, line)) in lines.iter().enumerate() {
+        let near_change = changed.iter().any(|&c| c.abs_diff(k) <= context);
+        if !near_change {
+            continue;
+        }
+        if last_printed.is_some_and(|last| k > last + 1) {
+            out.push_str("@@\n");
+        }
+        out.push_str(&format!("{tag}{line}\n"));
+        last_printed = Some(k);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use operator::controller::{NetworkSpec, NetworkStatus, RouterSpec};
+
+    #[test]
+    fn manifests_hold_only_the_desired_state_of_the_network() {
+        let mut network = Network::new("net", NetworkSpec { prefix: "/net".to_string(), ..NetworkSpec::default() });
+        network.metadata.uid = Some("uid".to_string());
+        network.metadata.resource_version = Some("42".to_string());
+        network.status = Some(serde_json::from_value::<NetworkStatus>(json!({ "dsCreated": true })).unwrap());
+        let manifest = serde_yaml::to_string(&export_manifest(&network).unwrap()).unwrap();
+        let imported = read_manifest(&manifest).unwrap();
+        assert_eq!(imported.name_any(), "net");
+        assert_eq!(imported.spec.prefix, "/net");
+        assert!(imported.metadata.uid.is_none() && imported.metadata.resource_version.is_none());
+        assert!(imported.status.is_none());
+    }
+
+    #[test]
+    fn diff_shows_only_the_drift_of_the_live_daemonset() {
+        let mut network = Network::new("net", NetworkSpec { prefix: "/net".to_string(), udp_unicast_port: 6363, ..NetworkSpec::default() });
+        network.metadata.namespace = Some("ns".to_string());
+        network.metadata.uid = Some("uid".to_string());
+        let desired = serde_json::to_value(network.create_owned_daemonset(Some("operator:1".to_string()), Some("net".to_string()))).unwrap();
+        let mut live = desired.clone();
+        // Defaults and status filled in by the API server, and a manual edit
+        live["spec"]["revisionHistoryLimit"] = json!(10);
+        live["status"] = json!({ "numberReady": 3 });
+        live["spec"]["template"]["spec"]["hostNetwork"] = json!(false);
+        let yaml = |value: &Value| serde_yaml::to_string(value).unwrap();
+        let diff = unified_diff(&yaml(&prune_to(&live, &desired)), &yaml(&desired), 0);
+        let changes: Vec<String> = diff.lines().filter(|line| line.starts_with(['-', '+'])).map(|line| line.replace(' ', "")).collect();
+        assert_eq!(changes, ["+hostNetwork:true", "-hostNetwork:false"]);
+        assert!(unified_diff(&yaml(&prune_to(&desired, &desired)), &yaml(&desired), 3).is_empty());
+    }
+
+    #[test]
+    fn routers_of_older_manifests_are_skipped() {
+        let manifest = r#"
+apiVersion: v1
+kind: List
+items:
+- apiVersion: named-data.net/v1alpha1
+  kind: Network
+  metadata: { name: net }
+  spec: { prefix: /net, udpUnicastPort: 6363 }
+- apiVersion: named-data.net/v1alpha1
+  kind: Router
+  metadata: { name: router }
+  spec: { prefix: /net, nodeName: node }
+"#;
+        assert_eq!(read_manifest(manifest).unwrap().name_any(), "net");
+    }
+
+    #[tokio::test]
+    async fn force_delete_stops_the_routers_before_deleting_the_network() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let service = tower::service_fn({
+            let requests = requests.clone();
+            move |request: http::Request<kube::client::Body>| {
+                let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+                requests.lock().unwrap().push(format!("{method} {path}"));
+                let metadata = |name: &str, finalizer: &str| ObjectMeta {
+                    name: Some(name.to_string()),
+                    namespace: Some("ns".to_string()),
+                    finalizers: Some(vec![finalizer.to_string()]),
+                    ..ObjectMeta::default()
+                };
+                let daemonset = serde_json::to_value(DaemonSet { metadata: metadata("net", "other"), ..DaemonSet::default() }).unwrap();
+                let mut router = Router::new("router", RouterSpec { prefix: "/net".to_string(), node_name: "node".to_string() });
+                router.metadata = metadata("router", ROUTER_FINALIZER);
+                let router = serde_json::to_value(router).unwrap();
+                let mut network = Network::new("net", NetworkSpec::default());
+                network.metadata = metadata("net", NETWORK_FINALIZER);
+                let network = serde_json::to_value(network).unwrap();
+                let list = |items: Vec<Value>| json!({ "apiVersion": "v1", "kind": "List", "metadata": {}, "items": items });
+                let body = match (method.as_str(), path.as_str()) {
+                    ("GET", "/apis/apps/v1/namespaces/ns/daemonsets") => list(vec![daemonset]),
+                    // The pods are gone by the first check
+                    ("GET", "/api/v1/namespaces/ns/pods") => list(Vec::new()),
+                    ("GET", "/apis/named-data.net/v1alpha1/namespaces/ns/routers") => list(vec![router]),
+                    (_, path) if path.contains("/routers/") => router,
+                    (_, path) if path.contains("/daemonsets/") => daemonset,
+                    _ => network,
+                };
+                let response = http::Response::builder().body(kube::client::Body::from(body.to_string().into_bytes())).unwrap();
+                async move { Ok::<_, std::convert::Infallible>(response) }
+            }
+        });
+        force_delete(Client::new(service, "ns"), "net", "ns", Duration::from_secs(5)).await.unwrap();
+        let requests = requests.lock().unwrap();
+        let position = |request: &str| requests.iter().position(|sent| sent == request).unwrap_or_else(|| panic!("{request} in {requests:?}"));
+        let ds_deleted = position("DELETE /apis/apps/v1/namespaces/ns/daemonsets/net");
+        let pods_checked = position("GET /api/v1/namespaces/ns/pods");
+        let router_deleted = position("DELETE /apis/named-data.net/v1alpha1/namespaces/ns/routers/router");
+        let router_released = position("PATCH /apis/named-data.net/v1alpha1/namespaces/ns/routers/router");
+        let network_deleted = position("DELETE /apis/named-data.net/v1alpha1/namespaces/ns/networks/net");
+        let network_released = position("PATCH /apis/named-data.net/v1alpha1/namespaces/ns/networks/net");
+        assert!(ds_deleted < pods_checked && pods_checked < router_deleted);
+        assert!(router_deleted < router_released && router_released < network_deleted && network_deleted < network_released);
+    }
+
+    #[test]
+    fn schemas_are_printed_for_the_requested_kind() {
+        let all = crd_schemas(None).unwrap();
+        assert!(all["Network"]["properties"]["spec"]["properties"]["udpUnicastPort"].is_object());
+        assert!(all["Router"]["properties"]["spec"].is_object());
+        let router = crd_schemas(Some("router")).unwrap();
+        assert_eq!(router.as_object().unwrap().keys().collect::<Vec<_>>(), ["Router"]);
+        assert!(crd_schemas(Some("Pod")).is_err());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn alert_rules_use_the_registered_series() {
+        let rules = alert_rules("monitoring", r#"job="ndn-operator""#, 0.1, 15, 5).to_string();
+        assert!(rules.contains(r#"operator_reconcile_errors_total{job=\"ndn-operator\"}"#));
+        assert!(rules.contains(r#"operator_reconciliations_total{job=\"ndn-operator\"}"#));
+        assert!(rules.contains(r#"operator_router_expected_neighbors{job=\"ndn-operator\"}"#));
+    }
+}
@@ -6,14 +6,14 @@ use kube::{
 };
 use operator::{
   controller::{
-    is_router_created, Router, RouterFaces, RouterStatus,
+    is_router_created, parse_file_mode, select_address, Cidr, FaceSchemes, RouterIdentity, FaceSpec, Router, RouterFaces, KEEP_ALIVES_BEFORE_DEAD,
   },
-  dv::RouterConfig,
-  fw::{FacesConfig, ForwarderConfig, UdpConfig, UnixConfig},
-  telemetry, Error, NdndConfig,
+  dv::{Neighbor, RouterConfig},
+  fw::{ContentStoreConfig, FacesConfig, ForwarderConfig, TablesConfig, UdpConfig, UnixConfig},
+  telemetry, ConfigFormat, Error, NdndConfig,
 };
 use serde_json::json;
-use std::env;
+use std::{env, fs::{self, Permissions}, net::IpAddr, os::unix::fs::{chown, PermissionsExt}, path::Path};
 use tracing::*;
 
 /// Generate config file for ndnd
@@ -23,14 +23,26 @@ struct Args {
     // Output file
     #[arg(short, long)]
     output: String,
+    // Output format
+    #[arg(short, long, value_enum, default_value_t = ConfigFormat::Yaml)]
+    format: ConfigFormat,
 }
 
-fn gen_config(network_name: String, router_name: String, udp_unicast_port: i32, socket_path: Option<String> ) -> NdndConfig {
+fn gen_config(network_name: String, identity: &RouterIdentity, udp_unicast_port: i32, socket_path: Option<String>, faces: Vec<FaceSpec>) -> NdndConfig {
 
+  let neighbors = faces
+    .into_iter()
+    .map(|face| Neighbor {
+      uri: face.uri,
+      mtu: None,
+      local: face.local,
+    })
+    .collect::<Vec<_>>();
   NdndConfig {
     dv: RouterConfig {
         network: format!("/{network_name}" ),
-        router: format!("/{network_name}/{router_name}"),
+        router: identity.ndnd_router_name(&network_name),
+        neighbors: (!neighbors.is_empty()).then_some(neighbors),
         ..RouterConfig::default()
     },
     fw: ForwarderConfig {
@@ -51,31 +63,79 @@ fn gen_config(network_name: String, router_name: String, udp_unicast_port: i32,
   }
 }
 
+/// Prepare the directory of the ndnd socket before ndnd starts: owned by the socket group,
+/// which the setgid bit passes on to the socket, and with the configured mode
+fn prepare_socket_dir(dir: &Path, mode: Option<u32>, group: Option<u32>) -> anyhow::Result<()> {
+  if let Some(gid) = group {
+    chown(dir, None, Some(gid))?;
+  }
+  if let Some(mode) = mode {
+    let setgid = if group.is_some() { 0o2000 } else { 0 };
+    fs::set_permissions(dir, Permissions::from_mode(mode | setgid))?;
+  }
+  Ok(())
+}
+
+/// Fetch the ndnd config served at the URL
+async fn download_config(url: &str, authorization: Option<String>) -> Result<String, Error> {
+  info!("Downloading the ndnd config from {}", url);
+  let failed = |e: reqwest::Error| Error::OtherError(format!("Failed to download the ndnd config from {url}: {e}"));
+  let mut request = reqwest::Client::new().get(url);
+  if let Some(authorization) = authorization.filter(|authorization| !authorization.is_empty()) {
+    request = request.header(reqwest::header::AUTHORIZATION, authorization);
+  }
+  let response = request.send().await.and_then(|response| response.error_for_status()).map_err(failed)?;
+  response.text().await.map_err(failed)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-  telemetry::init().await;
+  telemetry::init().await?;
   let args = Args::parse();
   let network_name = env::var("NDN_NETWORK_NAME")?;
   let network_namespace = env::var("NDN_NETWORK_NAMESPACE")?;
-  let router_name = env::var("NDN_ROUTER_NAME")?;
+  let identity = RouterIdentity::from_env()?;
+  let router_name = identity.name.clone();
   let udp_unicast_port = env::var("NDN_UDP_UNICAST_PORT")?.parse::<i32>()?;
   let socket_path = env::var("NDN_SOCKET_PATH").ok();
+  let socket_mode = env::var("NDN_SOCKET_MODE").ok().filter(|mode| !mode.is_empty()).map(|mode| parse_file_mode(&mode)).transpose()?;
+  let socket_group = env::var("NDN_SOCKET_GROUP").ok().filter(|gid| !gid.is_empty()).map(|gid| gid.parse::<u32>()).transpose()?;
+  if let Some(dir) = socket_path.as_deref().and_then(|path| Path::new(path).parent()) {
+    prepare_socket_dir(dir, socket_mode, socket_group)?;
+  }
+  let faces = match env::var("NDN_FACES") {
+    Ok(faces) => serde_json::from_str::<Vec<FaceSpec>>(&faces)?,
+    Err(_) => Vec::new(),
+  };
+  let keep_alive_interval = env::var("NDN_KEEP_ALIVE_INTERVAL").ok().filter(|interval| !interval.is_empty()).map(|interval| interval.parse::<u64>()).transpose()?;
+  let cs_capacity = env::var("NDN_CS_CAPACITY").ok().filter(|capacity| !capacity.is_empty()).map(|capacity| capacity.parse::<u16>()).transpose()?;
+  let face_schemes = match env::var("NDN_FACE_SCHEMES") {
+    Ok(schemes) => serde_json::from_str::<FaceSchemes>(&schemes)?,
+    Err(_) => FaceSchemes::default(),
+  };
+
+  let exclude_cidrs = env::var("NDN_EXCLUDE_ADDRESS_CIDRS")
+    .ok()
+    .map(|cidrs| cidrs.split(',').filter(|cidr| !cidr.is_empty()).map(|cidr| cidr.parse::<Cidr>()).collect::<Result<Vec<_>, _>>())
+    .transpose()?
+    .unwrap_or_default();
+  // The address of the default route comes first, then those of the other interfaces
+  let interface_ips: Vec<IpAddr> = local_ip_address::list_afinet_netifas()
+    .map(|interfaces| interfaces.into_iter().map(|(_, ip)| ip).collect())
+    .unwrap_or_default();
+  debug!("interface ips: {:?}", interface_ips);
 
   let local_ip = local_ip_address::local_ip();
   debug!("local ip: {:?}", local_ip);
-  let ip4 = local_ip.ok().map(|ip| ip.to_string());
+  let ip4 = select_address(local_ip.into_iter().chain(interface_ips.iter().copied().filter(IpAddr::is_ipv4)), &exclude_cidrs)
+    .map(|ip| ip.to_string());
 
   let local_ipv6 = local_ip_address::local_ipv6();
   debug!("local ip6: {:?}", local_ipv6);
-  let ip6 = local_ip_address::local_ipv6().ok().map(|ip| ip.to_string());
+  let ip6 = select_address(local_ipv6.into_iter().chain(interface_ips.iter().copied().filter(IpAddr::is_ipv6)), &exclude_cidrs)
+    .map(|ip| ip.to_string());
   info!("local ip4: {:?}", ip4);
   info!("local ip6: {:?}", ip6);
-  // Generate Ndnd config
-  let config = gen_config(network_name.clone(), router_name.clone(), udp_unicast_port, socket_path);
-  let config_str = serde_yaml::to_string(&config)?;
-  std::fs::write(args.output, config_str.clone())?;
-  info!("{}", config_str);
-
   // Wait for the router to be created
   info!("Waiting for the router {}...", router_name);
   let client = Client::try_default().await?;
@@ -87,22 +147,44 @@ async fn main() -> anyhow::Result<()> {
   );
   let _ = tokio::time::timeout(std::time::Duration::from_secs(10), created).await?;
 
+  // Download the Ndnd config if the Network serves it centrally, generate it otherwise
+  let config_str = match env::var("NDN_CONFIG_URL").ok().filter(|url| !url.is_empty()) {
+    Some(url) => download_config(&url, env::var("NDN_CONFIG_AUTHORIZATION").ok()).await?,
+    None => {
+      let mut config = gen_config(network_name.clone(), &identity, udp_unicast_port, socket_path, faces);
+      if let Some(interval) = keep_alive_interval {
+        config.dv.advertise_interval = Some(interval * 1000);
+        config.dv.router_dead_interval = Some(interval * KEEP_ALIVES_BEFORE_DEAD * 1000);
+      }
+      if let Some(capacity) = cs_capacity {
+        config.fw.tables = Some(TablesConfig {
+          content_store: ContentStoreConfig { capacity, ..ContentStoreConfig::default() },
+          ..TablesConfig::default()
+        });
+      }
+      config.render(args.format)?
+    }
+  };
+  std::fs::write(args.output, config_str.clone())?;
+  info!("{}", config_str);
+
   // Patch the status of the existing router
   let faces = RouterFaces {
     udp4: {
-        ip4.map(|ip4| format!("udp://{ip4}:{udp_unicast_port}"))
+        ip4.map(|ip4| format!("{}://{ip4}:{udp_unicast_port}", face_schemes.udp4()))
     },
     tcp4: None,
     udp6: {
-        ip6.map(|ip6| format!("udp://[{ip6}]:{udp_unicast_port}"))
+        ip6.map(|ip6| format!("{}://[{ip6}]:{udp_unicast_port}", face_schemes.udp6()))
     },
     tcp6: None,
   };
+  // Only patch the fields owned by the init container: a restarted pod
+  // must not reset the neighbors already propagated by the operator
   let patch_status = json!({
-    "status": RouterStatus {
-      faces,
-      initialized: true,
-      ..RouterStatus::default()
+    "status": {
+      "faces": faces,
+      "initialized": true,
     }
   });
   debug!("Patch status: {:?}", patch_status);
@@ -114,4 +196,27 @@ async fn main() -> anyhow::Result<()> {
   info!("Patched router status: {:?}", router.status);
 
   Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn faces_are_written_as_neighbors_with_their_local_endpoint() {
+    let identity = RouterIdentity::new("{node}", "net", "node-a", "pod").unwrap();
+    let faces = vec![
+      FaceSpec { uri: "udp://10.0.0.1:6363".to_string(), local: Some("udp://192.168.1.10:6363".to_string()) },
+      FaceSpec { uri: "tcp://10.0.0.2:6363".to_string(), local: None },
+    ];
+    let config = gen_config("net".to_string(), &identity, 6363, None, faces);
+    let neighbors = config.dv.neighbors.as_ref().unwrap();
+    assert_eq!(neighbors[0].uri, "udp://10.0.0.1:6363");
+    assert_eq!(neighbors[0].local.as_deref(), Some("udp://192.168.1.10:6363"));
+    assert_eq!(neighbors[1].local, None);
+    let rendered: serde_json::Value = serde_json::from_str(&config.render(ConfigFormat::Json).unwrap()).unwrap();
+    assert_eq!(rendered["dv"]["neighbors"][0]["local"], "udp://192.168.1.10:6363");
+    assert!(rendered["dv"]["neighbors"][1].get("local").is_none());
+    // Routers without faces keep the neighbors key out of the config
+    assert!(gen_config("net".to_string(), &identity, 6363, None, Vec::new()).dv.neighbors.is_none());
+  }
+}
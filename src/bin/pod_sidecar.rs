@@ -1,41 +1,271 @@
+#[cfg(all(feature = "metrics", feature = "mgmt"))]
+use actix_web::{get, web::Data, App, HttpResponse, HttpServer, Responder};
+#[cfg(all(feature = "metrics", feature = "mgmt"))]
+use operator::{metrics::RoutingMetrics, mgmt::read_forwarder_counters};
+#[cfg(feature = "mgmt")]
+use operator::{controller::health_probe_name, mgmt::{probe, read_face_id, read_forwarder_version, serve_probe}};
 use operator::{
-    controller::{Router, ROUTER_MANAGER_NAME}, telemetry, Error
+    controller::{audit, Router, RouterIdentity, MESH_CONVERGED_CONDITION, NETWORK_LABEL_KEY, ROUTER_MANAGER_NAME}, telemetry, transport::Transport, Error
 };
-use futures::{TryStreamExt, pin_mut};
-use kube::{api::{Patch, PatchParams}, runtime::{watcher, WatchStreamExt}, Api, Client};
+use futures::{stream, StreamExt, TryStreamExt, pin_mut};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::{ListParams, Patch, PatchParams}, runtime::{watcher, WatchStreamExt}, Api, Client, ResourceExt};
+use serde_json::json;
 use json_patch::{jsonptr::PointerBuf, Patch as JsonPatch, PatchOperation, ReplaceOperation};
-use std::{collections::BTreeSet, env};
-use std::process::Command;
+use std::{collections::BTreeSet, env, fs, time::Duration};
+use tokio::{process::Command, signal::unix::{signal, SignalKind}};
 use tracing::*;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    telemetry::init().await;
-    let network_namespace = env::var("NDN_NETWORK_NAMESPACE")?;
-    let my_router_name = env::var("NDN_ROUTER_NAME")?;
-    let client = Client::try_default().await?; 
-    let api_router = Api::<Router>::namespaced(client, &network_namespace);
-    // Set my status.online to true
-    info!("Set my router status to online");
+static LIVENESS_INTERVAL: Duration = Duration::from_secs(10);
+static DEFAULT_LINK_CONCURRENCY: usize = 8;
+static NDND_PATH: &str = "/ndnd";
+
+/// Whether ndnd accepts connections on its management transport. A host that doesn't
+/// resolve counts as ndnd being down until the next check
+async fn forwarder_alive(transport: &Transport) -> bool {
+    transport.connectable().await.unwrap_or_else(|e| {
+        warn!("Failed to reach ndnd: {}", e);
+        false
+    })
+}
+
+/// On pod termination, wait for ndnd to stop, then remove its socket so a deleted Network
+/// doesn't leave a stale socket on the node. ndnd keeps serving its clients until it exits,
+/// e.g. during the withdrawal window of a scale-in
+async fn remove_socket_on_exit(transport: Transport) -> anyhow::Result<()> {
+    signal(SignalKind::terminate())?.recv().await;
+    info!("Terminating, waiting for ndnd to stop");
+    remove_socket_once_stopped(&transport).await;
+    std::process::exit(0);
+}
+
+/// Remove the socket of ndnd once it no longer accepts connections
+async fn remove_socket_once_stopped(transport: &Transport) {
+    while forwarder_alive(transport).await {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    if let Some(socket_path) = transport.socket_path()
+        && let Err(e) = fs::remove_file(socket_path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!("Failed to remove {}: {}", socket_path, e);
+    }
+}
+
+/// Run `/ndnd dv <action> <URL>` for each neighbor, at most `concurrency` at a time so
+/// large neighbor sets are programmed quickly without flooding ndnd. Returns the neighbors
+/// whose command failed, with the reason
+async fn update_links(action: &str, neighbors: BTreeSet<String>, concurrency: usize) -> Vec<(String, String)> {
+    run_per_neighbor(NDND_PATH, neighbors, concurrency, |neighbor| vec!["dv".to_string(), action.to_string(), neighbor.to_string()]).await
+}
+
+/// Register the prefix on the face of each neighbor with `/ndnd fw route-add`, at most
+/// `concurrency` at a time. The face of a neighbor is looked up by its URI, route-add only
+/// takes face IDs. Returns the neighbors whose registration failed, with the reason
+#[cfg(feature = "mgmt")]
+async fn register_prefix(prefix: &str, neighbors: BTreeSet<String>, concurrency: usize) -> Vec<(String, String)> {
+    stream::iter(neighbors)
+        .map(|neighbor| async move {
+            let face_id = match read_face_id(&neighbor).await {
+                Ok(face_id) => face_id,
+                Err(e) => return vec![(neighbor, e.to_string())],
+            };
+            run_per_neighbor(NDND_PATH, BTreeSet::from([neighbor]), 1, |_| {
+                vec!["fw".to_string(), "route-add".to_string(), format!("prefix={prefix}"), format!("face={face_id}")]
+            })
+            .await
+        })
+        .buffer_unordered(concurrency)
+        .flat_map(stream::iter)
+        .collect()
+        .await
+}
+
+/// Run ndnd at `program` with the arguments of each neighbor, at most `concurrency` at a time.
+/// Returns the neighbors whose command failed, with the reason
+async fn run_per_neighbor(
+    program: &str,
+    neighbors: BTreeSet<String>,
+    concurrency: usize,
+    args: impl Fn(&str) -> Vec<String>,
+) -> Vec<(String, String)> {
+    stream::iter(neighbors)
+        .map(|neighbor| {
+            let args = args(&neighbor);
+            async move {
+                info!("Running {program} {}", args.join(" "));
+                let output = Command::new(program).args(&args).output().await;
+                let error = match output {
+                    Ok(output) if output.status.success() => return None,
+                    Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                Some((neighbor, error))
+            }
+        })
+        .buffer_unordered(concurrency)
+        .filter_map(async |failure| failure)
+        .collect()
+        .await
+}
+
+/// Report in the Router status whether ndnd is up
+async fn report_online(api_router: &Api<Router>, router_name: &str, online: bool) -> Result<Router, Error> {
+    info!("Set my router status to {}", if online { "online" } else { "offline" });
     let patches = vec![
         PatchOperation::Replace(
             ReplaceOperation{
                 path: PointerBuf::from_tokens(vec!["status", "online"]),
-                value: serde_json::to_value(true).unwrap(),
+                value: serde_json::to_value(online).unwrap(),
             }
         )
     ];
     let patch = Patch::Json::<()>(JsonPatch(patches));
     debug!("Patch status: {:?}", patch);
     let serverside = PatchParams::apply(ROUTER_MANAGER_NAME);
-    let patched = api_router.patch_status(&my_router_name, &serverside, &patch).await
+    let patched = api_router.patch_status(router_name, &serverside, &patch).await
         .map_err(Error::KubeError)?;
+    audit::<Router>("json-patch", Some("status"), patched.namespace().as_deref(), router_name, Some(ROUTER_MANAGER_NAME));
+    Ok(patched)
+}
+
+/// Record the version of ndnd in the Router status. Failures are only logged,
+/// the version is informational
+#[cfg(feature = "mgmt")]
+async fn report_version(api_router: &Api<Router>, router_name: &str) {
+    let version = match read_forwarder_version().await {
+        Ok(version) => version,
+        Err(e) => {
+            warn!("Failed to read the ndnd version: {}", e);
+            return;
+        }
+    };
+    info!("ndnd runs version {}", version);
+    let patch = Patch::Merge(json!({ "status": { "ndndVersion": version } }));
+    match api_router.patch_status(router_name, &PatchParams::apply(ROUTER_MANAGER_NAME), &patch).await {
+        Ok(patched) => audit::<Router>("merge-patch", Some("status"), patched.namespace().as_deref(), router_name, Some(ROUTER_MANAGER_NAME)),
+        Err(e) => warn!("Failed to report the ndnd version: {}", e),
+    }
+}
+
+/// Whether the router linked all the neighbors of its status, or has no neighbor to link
+/// as the only online router of the Network
+async fn mesh_converged(api_router: &Api<Router>, router: &Router, linked: &BTreeSet<String>) -> Result<bool, Error> {
+    let neighbors = router.status.as_ref().map(|status| status.neighbors.clone()).unwrap_or_default();
+    if neighbors != *linked {
+        return Ok(false);
+    }
+    if !linked.is_empty() {
+        return Ok(true);
+    }
+    let network = router.labels().get(NETWORK_LABEL_KEY).cloned().unwrap_or_default();
+    let lp = ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={network}"));
+    let siblings = api_router.list(&lp).await.map_err(Error::KubeError)?;
+    Ok(!siblings
+        .iter()
+        .any(|sibling| sibling.name_any() != router.name_any() && sibling.status.as_ref().is_some_and(|status| status.online)))
+}
+
+/// Set the mesh readiness gate condition of the router pod
+async fn report_converged(api_pod: &Api<Pod>, pod_name: &str, converged: bool) -> Result<Pod, Error> {
+    info!("Set the mesh of my pod to {}", if converged { "converged" } else { "not converged" });
+    let (status, reason) = match converged {
+        true => ("True", "NeighborsLinked"),
+        false => ("False", "NeighborsPending"),
+    };
+    let patch = Patch::Strategic(json!({
+        "status": {
+            "conditions": [{ "type": MESH_CONVERGED_CONDITION, "status": status, "reason": reason }]
+        }
+    }));
+    let patched = api_pod.patch_status(pod_name, &PatchParams::default(), &patch).await
+        .map_err(Error::KubeError)?;
+    audit::<Pod>("strategic-patch", Some("status"), patched.namespace().as_deref(), pod_name, None);
+    Ok(patched)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    telemetry::init().await?;
+    let network_namespace = env::var("NDN_NETWORK_NAMESPACE")?;
+    let my_router_name = RouterIdentity::from_env()?.name;
+    let my_pod_name = env::var("NDN_POD_NAME")?;
+    let client = Client::try_default().await?; 
+    let api_router = Api::<Router>::namespaced(client.clone(), &network_namespace);
+    let api_pod = Api::<Pod>::namespaced(client, &network_namespace);
+    // The router is online once ndnd accepts connections on its management socket
+    let transport = env::var("NDN_CLIENT_TRANSPORT")?.parse::<Transport>()?;
+    while !forwarder_alive(&transport).await {
+        debug!("Waiting for ndnd to listen on {:?}", transport);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    let exit_transport = transport.clone();
+    tokio::spawn(async move {
+        if let Err(e) = remove_socket_on_exit(exit_transport).await {
+            warn!("Failed to handle termination: {}", e);
+        }
+    });
+    let patched = report_online(&api_router, &my_router_name, true).await?;
     info!("Patched router status: {:?}", patched.status);
+    #[cfg(feature = "mgmt")]
+    report_version(&api_router, &my_router_name).await;
+    // Keep reporting the liveness of ndnd, the operator only propagates the faces of online routers
+    let liveness_api = api_router.clone();
+    let liveness_router_name = my_router_name.clone();
+    tokio::spawn(async move {
+        let mut online = true;
+        let mut interval = tokio::time::interval(LIVENESS_INTERVAL);
+        loop {
+            interval.tick().await;
+            let alive = forwarder_alive(&transport).await;
+            if alive == online {
+                continue;
+            }
+            match report_online(&liveness_api, &liveness_router_name, alive).await {
+                Ok(_) => online = alive,
+                Err(e) => warn!("Failed to report router liveness: {}", e),
+            }
+            // The restarted ndnd may be another version, e.g. after a node image upgrade
+            #[cfg(feature = "mgmt")]
+            if alive {
+                report_version(&liveness_api, &liveness_router_name).await;
+            }
+        }
+    });
+
+    #[cfg(all(feature = "metrics", feature = "mgmt"))]
+    if let Ok(port) = env::var("NDN_METRICS_PORT")
+        && !port.is_empty()
+        && let Err(e) = serve_routing_metrics(my_router_name.clone(), port.parse()?)
+    {
+        warn!("Failed to serve the routing metrics on port {}: {}", port, e);
+    }
+
+    let link_concurrency = match env::var("NDN_LINK_CONCURRENCY") {
+        Ok(concurrency) if !concurrency.is_empty() => concurrency.parse::<usize>()?,
+        _ => DEFAULT_LINK_CONCURRENCY,
+    };
+
+    // The probes are bounded like the link updates
+    #[cfg(feature = "mgmt")]
+    if let Ok(seconds) = env::var("NDN_HEALTH_CHECK_INTERVAL")
+        && !seconds.is_empty()
+    {
+        spawn_health_checks(api_router.clone(), my_router_name.clone(), Duration::from_secs(seconds.parse()?), link_concurrency)?;
+    }
+    // The Network prefix is registered toward the neighbors if the routing doesn't announce it
+    #[cfg(feature = "mgmt")]
+    let registered_prefix = match env::var("NDN_AUTO_REGISTER_PREFIX") {
+        Ok(enabled) if enabled == "true" => Some(env::var("NDN_NETWORK_PREFIX")?),
+        _ => None,
+    };
     // Watch the neighbors in my_router's status and run `/ndnd dv link-create <URL>` or `/ndnd dv link-destroy <URL>` when it changes
     let wc = watcher::Config::default()
         .fields(format!("metadata.name={}", my_router_name).as_str());
     let mut neighbors = BTreeSet::<String>::new();
-    let watcher = watcher(api_router, wc).applied_objects();
+    let readiness_gate = env::var("NDN_MESH_READINESS_GATE").is_ok_and(|enabled| enabled == "true");
+    let mut converged = None;
+    let watcher = watcher(api_router.clone(), wc).applied_objects();
     pin_mut!(watcher);
     while let Some(router) = watcher.try_next().await? {
         let new_neighbors = match router.status {
@@ -44,26 +274,214 @@ async fn main() -> anyhow::Result<()> {
         };
         let added_neighbors: BTreeSet<String> = new_neighbors.difference(&neighbors).cloned().collect();
         let removed_neighbors: BTreeSet<String> = neighbors.difference(&new_neighbors).cloned().collect();
-        for neighbor in added_neighbors {
-            info!("Creating link to {}", neighbor);
-            Command::new("/ndnd")
-                .arg("dv")
-                .arg("link-create")
-                .arg(neighbor)
-                .output()
-                .expect("Failed to create link");
+        // Failed links are left out of the known neighbors, so they are retried on the next change
+        let mut known_neighbors = new_neighbors.clone();
+        for (neighbor, error) in update_links("link-create", added_neighbors, link_concurrency).await {
+            warn!("Failed to create link to {}: {}", neighbor, error);
+            known_neighbors.remove(&neighbor);
         }
-        for neighbor in removed_neighbors {
-            info!("Destroying link to {}", neighbor);
-            Command::new("/ndnd")
-                .arg("dv")
-                .arg("link-destroy")
-                .arg(neighbor)
-                .output()
-                .expect("Failed to destroy link");
+        for (neighbor, error) in update_links("link-destroy", removed_neighbors, link_concurrency).await {
+            warn!("Failed to destroy link to {}: {}", neighbor, error);
+            known_neighbors.insert(neighbor);
         }
-        neighbors = new_neighbors;
+        // Links just created are the known neighbors that weren't known before
+        #[cfg(feature = "mgmt")]
+        if let Some(prefix) = &registered_prefix {
+            let created_neighbors = known_neighbors.difference(&neighbors).cloned().collect();
+            for (neighbor, error) in register_prefix(prefix, created_neighbors, link_concurrency).await {
+                warn!("Failed to register {} toward {}: {}", prefix, neighbor, error);
+            }
+        }
+        neighbors = known_neighbors;
         info!("Updated neighbors: {:?}", neighbors);
+        if readiness_gate {
+            let now_converged = mesh_converged(&api_router, &router, &neighbors).await?;
+            if converged != Some(now_converged) {
+                report_converged(&api_pod, &my_pod_name, now_converged).await?;
+                converged = Some(now_converged);
+            }
+        }
         };
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(all(feature = "metrics", feature = "mgmt"))]
+static FORWARDER_COUNTERS_INTERVAL: Duration = Duration::from_secs(15);
+
+#[cfg(all(feature = "metrics", feature = "mgmt"))]
+#[get("/metrics")]
+async fn metrics(m: Data<RoutingMetrics>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+        .body(m.encode())
+}
+
+/// Serve the forwarding counters of ndnd on the host port of the Network
+#[cfg(all(feature = "metrics", feature = "mgmt"))]
+fn serve_routing_metrics(router_name: String, metrics_port: u16) -> anyhow::Result<()> {
+    let network_name = env::var("NDN_NETWORK_NAME")?;
+    let routing_metrics = RoutingMetrics::default();
+    let server = HttpServer::new({
+        let routing_metrics = routing_metrics.clone();
+        move || {
+            App::new()
+                .app_data(Data::new(routing_metrics.clone()))
+                .service(metrics)
+        }
+    })
+    .bind(("0.0.0.0", metrics_port))?
+    .workers(1)
+    .shutdown_timeout(5)
+    .run();
+    tokio::spawn(server);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FORWARDER_COUNTERS_INTERVAL);
+        loop {
+            interval.tick().await;
+            match read_forwarder_counters().await {
+                Ok(counters) => routing_metrics.record(&network_name, &router_name, &counters),
+                Err(e) => debug!("Failed to read forwarder counters: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer the health probes of the other routers, and probe the other online routers
+/// of the Network every interval, reporting `DataPlaneHealthy` in my Router status
+#[cfg(feature = "mgmt")]
+fn spawn_health_checks(api_router: Api<Router>, router_name: String, interval: Duration, concurrency: usize) -> anyhow::Result<()> {
+    let network_name = env::var("NDN_NETWORK_NAME")?;
+    let prefix = env::var("NDN_NETWORK_PREFIX")?;
+    let probe_server = serve_probe(&health_probe_name(&prefix, &router_name))?;
+    tokio::spawn(async move {
+        // Keep the probe server with the task, it answers as long as the sidecar runs
+        let _probe_server = probe_server;
+        let lp = ListParams::default().labels(&format!("{}={}", NETWORK_LABEL_KEY, network_name));
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let routers = match api_router.list(&lp).await {
+                Ok(routers) => routers.items,
+                Err(e) => {
+                    warn!("Failed to list the routers to probe: {}", e);
+                    continue;
+                }
+            };
+            let Some(me) = routers.iter().find(|router| router.name_any() == router_name) else {
+                continue;
+            };
+            let peers: Vec<String> = routers
+                .iter()
+                .filter(|router| router.name_any() != router_name)
+                .filter(|router| router.status.as_ref().is_some_and(|status| status.online))
+                .map(|router| router.name_any())
+                .collect();
+            let mut unreachable: Vec<String> = stream::iter(peers.clone())
+                .map(|peer| {
+                    let name = health_probe_name(&prefix, &peer);
+                    async move {
+                        match probe(&name).await {
+                            Ok(answered) => (!answered).then_some(peer),
+                            Err(e) => {
+                                warn!("Failed to probe {}: {}", peer, e);
+                                Some(peer)
+                            }
+                        }
+                    }
+                })
+                .buffer_unordered(concurrency)
+                .filter_map(futures::future::ready)
+                .collect()
+                .await;
+            // The probes complete in any order, the message must not change with it
+            unreachable.sort();
+            let condition = me.data_plane_condition(peers.len(), &unreachable);
+            let current = me.current_condition(&condition.type_);
+            if current.is_some_and(|c| c.status == condition.status && c.message == condition.message) {
+                continue;
+            }
+            info!("Data plane health changed: {}", condition.message);
+            if let Err(e) = me.set_conditions(&api_router, Some(ROUTER_MANAGER_NAME), vec![condition]).await {
+                warn!("Failed to report data plane health: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn liveness_is_reported_in_the_router_status() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let service = tower::service_fn({
+            let requests = requests.clone();
+            move |request: http::Request<kube::client::Body>| {
+                let requests = requests.clone();
+                async move {
+                    let (method, uri) = (request.method().clone(), request.uri().clone());
+                    let body = request.into_body().collect_bytes().await.unwrap();
+                    requests.lock().unwrap().push((method, uri, serde_json::from_slice::<serde_json::Value>(&body).unwrap()));
+                    let router = json!({
+                        "apiVersion": "named-data.net/v1alpha1",
+                        "kind": "Router",
+                        "metadata": { "name": "r", "namespace": "ns" },
+                        "spec": { "prefix": "/net/r", "nodeName": "node" },
+                        "status": { "online": false },
+                    });
+                    Ok::<_, std::convert::Infallible>(http::Response::builder().status(200).body(kube::client::Body::from(router.to_string().into_bytes())).unwrap())
+                }
+            }
+        });
+        let api_router = Api::<Router>::namespaced(Client::new(service, "ns"), "ns");
+        let patched = report_online(&api_router, "r", false).await.unwrap();
+        assert!(!patched.status.unwrap().online);
+        let requests = requests.lock().unwrap();
+        let (method, uri, body) = &requests[0];
+        assert_eq!(*method, http::Method::PATCH);
+        assert_eq!(uri.path(), "/apis/named-data.net/v1alpha1/namespaces/ns/routers/r/status");
+        assert!(uri.query().unwrap().contains(&format!("fieldManager={ROUTER_MANAGER_NAME}")));
+        assert_eq!(*body, json!([{ "op": "replace", "path": "/status/online", "value": false }]));
+    }
+
+    #[tokio::test]
+    async fn socket_is_removed_once_ndnd_stopped() {
+        let path = env::temp_dir().join(format!("ndnd-{}.sock", std::process::id()));
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let transport = Transport::Unix(path.to_string_lossy().into_owned());
+        let remove = tokio::spawn(async move { remove_socket_once_stopped(&transport).await });
+        // Kept while ndnd serves its clients
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(Path::new(&path).exists());
+        drop(listener);
+        tokio::time::timeout(Duration::from_secs(5), remove).await.unwrap().unwrap();
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn links_are_run_in_bounded_parallel_and_report_their_failures() {
+        let dir = env::temp_dir().join(format!("ndnd-links-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let neighbors: BTreeSet<String> = ["a", "b", "c", "d", "fail"].map(String::from).into();
+        // Each command records how many commands run alongside it
+        let failures = run_per_neighbor("sh", neighbors, 2, |neighbor| {
+            let script = format!(
+                "touch {dir}/{neighbor}.running; ls {dir} | grep -c running >> {dir}/{neighbor}.parallel; sleep 0.2; rm {dir}/{neighbor}.running; \
+                 if [ {neighbor} = fail ]; then echo unreachable >&2; exit 1; fi",
+                dir = dir.display()
+            );
+            vec!["-c".to_string(), script]
+        })
+        .await;
+        assert_eq!(failures, vec![("fail".to_string(), "unreachable".to_string())]);
+        for neighbor in ["a", "b", "c", "d", "fail"] {
+            let parallel: usize = fs::read_to_string(dir.join(format!("{neighbor}.parallel"))).unwrap().trim().parse().unwrap();
+            assert!((1..=2).contains(&parallel), "{neighbor}: {parallel}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,32 @@
 mod main;
+mod config;
 mod network;
 mod router;
 mod pod_sync;
 mod helper;
+mod validation;
+mod cidr;
+mod mesh;
+mod notifier;
+mod quota;
+mod ca;
+mod audit;
+mod rbac;
+mod maintenance;
+mod leader;
 pub use main::*;
+pub use config::*;
 pub use network::*;
 pub use router::*;
 pub use pod_sync::*;
-pub use helper::*;
\ No newline at end of file
+pub use helper::*;
+pub use validation::*;
+pub use cidr::*;
+pub use mesh::*;
+pub use notifier::*;
+pub use quota::*;
+pub use ca::*;
+pub use audit::*;
+pub use rbac::*;
+pub use maintenance::*;
+pub use leader::*;
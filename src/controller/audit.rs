@@ -0,0 +1,39 @@
+use crate::telemetry::AUDIT_TARGET;
+use kube::{Resource, ResourceExt};
+use tracing::*;
+
+/// Record a mutating API call the operator made, as a structured line under the `audit`
+/// target. `operation` is `apply`, `create`, `merge-patch`, `json-patch`, `strategic-patch` or
+/// `delete`, `subresource` e.g. `status`. See `telemetry::init` for where the records go
+pub fn audit<K: Resource<DynamicType = ()>>(
+    operation: &str,
+    subresource: Option<&str>,
+    namespace: Option<&str>,
+    name: &str,
+    field_manager: Option<&str>,
+) {
+    info!(
+        target: AUDIT_TARGET,
+        kind = %K::kind(&()),
+        subresource = subresource.unwrap_or_default(),
+        namespace = namespace.unwrap_or_default(),
+        name,
+        operation,
+        field_manager = field_manager.unwrap_or_default(),
+        "{} {} {}",
+        operation,
+        K::kind(&()),
+        name
+    );
+}
+
+/// Record the finalizer patch `kube::runtime::finalizer` made reconciling the object, given the
+/// object before the reconcile: the finalizer is added to objects without it, and removed from
+/// deleted objects once they are cleaned up
+pub fn audit_finalizer<K: Resource<DynamicType = ()>>(object: &K, finalizer: &str) {
+    let finalized = object.finalizers().iter().any(|f| f == finalizer);
+    let deleted = object.meta().deletion_timestamp.is_some();
+    if finalized == deleted {
+        audit::<K>("json-patch", None, object.namespace().as_deref(), &object.name_any(), None);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{Error, Result};
+use reqwest::Certificate;
+
+/// Certificates of a PEM bundle. A bundle without any certificate is rejected,
+/// it is most likely not PEM, e.g. a DER certificate or the wrong Secret key
+pub fn parse_ca_bundle(pem: &[u8]) -> Result<Vec<Certificate>> {
+    let certificates =
+        Certificate::from_pem_bundle(pem).map_err(|e| Error::OtherError(format!("CA bundle is not valid PEM: {e}")))?;
+    if certificates.is_empty() {
+        return Err(Error::OtherError("CA bundle holds no PEM certificate".to_string()));
+    }
+    Ok(certificates)
+}
+
+/// Certificates of the PEM bundle at the path, e.g. mounted from a Secret
+pub fn read_ca_bundle(path: &str) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).map_err(Error::IoError)?;
+    parse_ca_bundle(&pem).map_err(|e| Error::OtherError(format!("{path}: {e}")))
+}
+
+/// HTTP client trusting the CA bundle on top of the public roots
+pub fn http_client(ca_bundle: &[Certificate]) -> Result<reqwest::Client> {
+    ca_bundle
+        .iter()
+        .fold(reqwest::Client::builder(), |builder, certificate| builder.add_root_certificate(certificate.clone()))
+        .build()
+        .map_err(|e| Error::OtherError(format!("Failed to build the HTTP client: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBezCCASGgAwIBAgIUDgt2EiaMfD+sX4SLPRU8ZYLv7CcwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHdGVzdC1jYTAgFw0yNjEwMTUxMTQ0MDVaGA8yMTI2MDkyMTEx
+NDQwNVowEjEQMA4GA1UEAwwHdGVzdC1jYTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABMOfmKOc2AmdDeF7PErGftWL4uYDasyNBOifsdJVcrt7lzDBLQma4AJlAGqO
+dtD/P0uywtQxKKmv0U3d1EK2/wWjUzBRMB0GA1UdDgQWBBTC6o51tDShhBC2QudB
+xcy1cLqSaTAfBgNVHSMEGDAWgBTC6o51tDShhBC2QudBxcy1cLqSaTAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQCcl5efKuEqcIAVNJbycjwYNhe2
+Vy2rhFZZDmI+kxdYHQIgXdcGTH0ObQHYsUjMQ1BrMHfOw7koYZnqjQUvPvMSIPU=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn ca_bundles_must_hold_pem_certificates() {
+        let path = std::env::temp_dir().join(format!("ca-{}.pem", std::process::id()));
+        std::fs::write(&path, format!("{CA}{CA}")).unwrap();
+        let bundle = read_ca_bundle(path.to_str().unwrap()).unwrap();
+        assert_eq!(bundle.len(), 2);
+        assert!(http_client(&bundle).is_ok());
+        // Like a DER certificate read from the wrong Secret key
+        std::fs::write(&path, b"\x30\x82\x01\x7b").unwrap();
+        let error = read_ca_bundle(path.to_str().unwrap()).unwrap_err().to_string();
+        assert!(error.contains(path.to_str().unwrap()) && error.contains("no PEM certificate"), "{error}");
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_ca_bundle(path.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Error, Result};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// IPv4 or IPv6 network in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidSpec(format!("invalid CIDR `{s}`: {reason}"));
+        let (addr, prefix_len) = s.split_once('/').ok_or_else(|| invalid("missing prefix length"))?;
+        let addr = addr.parse::<IpAddr>().map_err(|e| invalid(&e.to_string()))?;
+        let prefix_len = prefix_len.parse::<u8>().map_err(|e| invalid(&e.to_string()))?;
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(invalid(&format!("prefix length must be at most {max_len}")));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Whether a router can advertise a face on the address. Loopback and link-local
+/// addresses are unreachable from other nodes, and excluded networks unwanted
+pub fn is_usable_address(ip: &IpAddr, exclude: &[Cidr]) -> bool {
+    let link_local = match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_unicast_link_local(),
+    };
+    !ip.is_loopback() && !ip.is_unspecified() && !link_local && !exclude.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// First usable address among the candidates, ordered by preference
+pub fn select_address(candidates: impl IntoIterator<Item = IpAddr>, exclude: &[Cidr]) -> Option<IpAddr> {
+    candidates.into_iter().find(|ip| is_usable_address(ip, exclude))
+}
+
+pub fn parse_cidrs(cidrs: &Option<Vec<String>>) -> Result<Vec<Cidr>> {
+    cidrs.iter().flatten().map(|cidr| cidr.parse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(ips: &[&str]) -> Vec<IpAddr> {
+        ips.iter().map(|ip| ip.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn unreachable_addresses_are_skipped() {
+        let candidates = ips(&["127.0.0.1", "169.254.0.1", "0.0.0.0", "fe80::1", "::1", "10.0.0.1"]);
+        assert_eq!(select_address(candidates, &[]), "10.0.0.1".parse().ok());
+    }
+
+    #[test]
+    fn excluded_networks_are_skipped() {
+        let exclude = parse_cidrs(&Some(vec!["10.0.0.0/8".to_string(), "fd00::/8".to_string()])).unwrap();
+        assert_eq!(select_address(ips(&["10.1.2.3", "fd00::1", "192.168.0.1"]), &exclude), "192.168.0.1".parse().ok());
+        assert_eq!(select_address(ips(&["10.1.2.3", "fd00::1"]), &exclude), None);
+        // Candidates are taken in order of preference
+        assert_eq!(select_address(ips(&["2001:db8::1", "192.168.0.1"]), &exclude), "2001:db8::1".parse().ok());
+    }
+
+    #[test]
+    fn invalid_cidrs_are_rejected() {
+        assert!("10.0.0.0".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("fd00::/129".parse::<Cidr>().is_err());
+        assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains(&"8.8.8.8".parse().unwrap()));
+    }
+}
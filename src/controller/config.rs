@@ -0,0 +1,188 @@
+use clap::{CommandFactory, Parser};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Environment variables exposed by the config endpoint besides those of the flags
+static EXPOSED_ENV_VARS: &[&str] = &["RUST_LOG", "KUBECONFIG", "NDN_OPERATOR_AUDIT_LOG"];
+static REDACTED: &str = "<redacted>";
+
+/// Operator configuration, read from command line flags or environment variables.
+/// Secret flags are marked with `hide_env_values` and `#[serde(skip_serializing)]`,
+/// so the config endpoint exposes neither their value nor their variable
+#[derive(Parser, Clone, Debug, Serialize)]
+#[command(version, about, long_about = None)]
+pub struct Config {
+    /// Maximum number of objects each controller reconciles in parallel, 0 means unbounded.
+    /// The same object is never reconciled concurrently
+    #[arg(long, env = "NDN_OPERATOR_CONCURRENCY", default_value_t = 0)]
+    pub concurrency: u16,
+
+    /// Seconds to wait for the events of a Network to settle before reconciling it,
+    /// so node scale events reconcile each Network once
+    #[arg(long, env = "NDN_OPERATOR_DEBOUNCE_SECONDS", default_value_t = 1)]
+    pub debounce_seconds: u64,
+
+    /// Seconds each controller may take to list its objects on startup before the operator exits,
+    /// 0 means no limit. `/readyz` fails until all lists complete, see the readiness probe
+    #[arg(long, env = "NDN_OPERATOR_CACHE_SYNC_TIMEOUT_SECONDS", default_value_t = 600)]
+    pub cache_sync_timeout_seconds: u64,
+
+    /// Objects per page of the lists the controllers start from and relist with. Smaller pages
+    /// put less load on the API server in large clusters, at the cost of more requests
+    #[arg(long, env = "NDN_OPERATOR_LIST_PAGE_SIZE", default_value_t = 500)]
+    pub list_page_size: u32,
+
+    /// Consecutive reconciles failed with a permanent error, like an invalid spec, after which a Network
+    /// or Router is marked `Stalled` and only retried once its spec changes, 0 retries forever.
+    /// Failed reconciles are retried every 5 minutes, transient errors never stall
+    #[arg(long, env = "NDN_OPERATOR_MAX_RECONCILE_RETRIES", default_value_t = 0)]
+    pub max_reconcile_retries: u32,
+
+    /// Reconcile Networks when a matching Node joins the cluster or its labels change,
+    /// and Routers when the problems of their Node, like memory pressure, change
+    #[arg(long, env = "NDN_OPERATOR_WATCH_NODES", default_value_t = false)]
+    pub watch_nodes: bool,
+
+    /// Reconcile Networks when a Secret they reference changes, rolling the router pods
+    /// if their config depends on it. Needs to list and watch Secrets cluster-wide
+    #[arg(long, env = "NDN_OPERATOR_WATCH_SECRETS", default_value_t = false)]
+    pub watch_secrets: bool,
+
+    /// Reconcile Routers when the external topology ConfigMap of their Network changes.
+    /// Needs to list and watch ConfigMaps cluster-wide
+    #[arg(long, env = "NDN_OPERATOR_WATCH_CONFIG_MAPS", default_value_t = false)]
+    pub watch_config_maps: bool,
+
+    /// Serve `/path`, the fewest-hop path between two Routers of a managed Network. The
+    /// endpoint is unauthenticated, keep it off where the port is reachable by others
+    #[arg(long, env = "NDN_OPERATOR_SERVE_PATH", default_value_t = false)]
+    pub serve_path: bool,
+
+    /// Label the nodes running routers with `named-data.net/network-<namespace>.<name>: "true"`
+    /// for each Network scheduled on them. Needs to patch Nodes
+    #[arg(long, env = "NDN_OPERATOR_LABEL_NODES", default_value_t = false)]
+    pub label_nodes: bool,
+
+    /// Only reconcile the Networks matching this label selector, e.g. `team=platform`, and their
+    /// Routers and pods, to split the Networks of a cluster between operator instances.
+    /// Equality-based requirements only: `key=value`, `key!=value`, `key` and `!key`
+    #[arg(long, env = "NDN_OPERATOR_NETWORK_SELECTOR")]
+    pub network_selector: Option<String>,
+
+    /// Elect a leader among the operator replicas with a Lease in the operator namespace, only the
+    /// leader runs the controllers. `POST /step-down` hands the leadership over to another replica
+    #[arg(long, env = "NDN_OPERATOR_LEADER_ELECTION", default_value_t = false)]
+    pub leader_election: bool,
+
+    /// Name of the Lease of the leader election
+    #[arg(long, env = "NDN_OPERATOR_LEADER_ELECTION_LEASE", default_value = "ndn-operator")]
+    pub leader_election_lease: String,
+
+    /// Seconds the leader Lease is valid without renewal, before another replica takes over
+    #[arg(long, env = "NDN_OPERATOR_LEADER_ELECTION_LEASE_SECONDS", default_value_t = 15)]
+    pub leader_election_lease_seconds: u64,
+
+    /// Log events instead of publishing them to the API server
+    #[arg(long, env = "NDN_OPERATOR_DISABLE_EVENTS", default_value_t = false)]
+    pub disable_events: bool,
+
+    /// Name of the cluster, set as the reporting instance of the published events
+    /// to tell apart the events of several clusters shipped to a central store
+    #[arg(long, env = "NDN_OPERATOR_CLUSTER_NAME")]
+    pub cluster_name: Option<String>,
+
+    /// Webhook the significant events are posted to as JSON, e.g. a Slack incoming webhook.
+    /// Not exposed by the config endpoint, webhook URLs usually embed a token
+    #[arg(long, env = "NDN_OPERATOR_NOTIFY_WEBHOOK_URL", hide_env_values = true)]
+    #[serde(skip_serializing)]
+    pub notify_webhook_url: Option<String>,
+
+    /// Reasons of the events posted to the webhook. `ReconcileFailed` notifies failed reconciles
+    #[arg(long, env = "NDN_OPERATOR_NOTIFY_REASONS", value_delimiter = ',', default_value = "MeshPartitioned,IsolatedRouter,ReconcileFailed")]
+    pub notify_reasons: Vec<String>,
+
+    /// PEM bundle of private CAs the operator trusts on top of the public roots
+    /// when posting to the notification webhook, e.g. mounted from a Secret
+    #[arg(long, env = "NDN_OPERATOR_CA_BUNDLE_FILE")]
+    pub ca_bundle_file: Option<String>,
+
+    /// Minimum seconds between two notifications of the same reason for the same object
+    #[arg(long, env = "NDN_OPERATOR_NOTIFY_INTERVAL_SECONDS", default_value_t = 300)]
+    pub notify_interval_seconds: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::parse_from(std::iter::empty::<String>())
+    }
+}
+
+/// Effective operator configuration exposed by the web server
+#[derive(Clone, Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub version: String,
+    pub config: Config,
+    pub env: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn effective(&self) -> EffectiveConfig {
+        EffectiveConfig {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: self.clone(),
+            env: exposed_env(std::env::vars()),
+        }
+    }
+}
+
+fn exposed_env(vars: impl Iterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    // Variables of the flags, and whether they are secret
+    let flags = Config::command()
+        .get_arguments()
+        .filter_map(|arg| Some((arg.get_env()?.to_str()?.to_string(), arg.is_hide_env_values_set())))
+        .collect::<BTreeMap<_, _>>();
+    vars.filter_map(|(name, value)| {
+            let secret = match flags.get(&name) {
+                Some(secret) => *secret,
+                None if EXPOSED_ENV_VARS.contains(&name.as_str()) => false,
+                None => return None,
+            };
+            match secret {
+                true => Some((name, REDACTED.to_string())),
+                false => Some((name, value)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(names: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        names.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn secret_flags_are_redacted() {
+        let env = exposed_env(vars(&[
+            ("NDN_OPERATOR_NOTIFY_WEBHOOK_URL", "https://hooks.example.com/T0/B0/token"),
+            ("NDN_OPERATOR_CONCURRENCY", "4"),
+        ]));
+        assert_eq!(env["NDN_OPERATOR_NOTIFY_WEBHOOK_URL"], REDACTED);
+        assert_eq!(env["NDN_OPERATOR_CONCURRENCY"], "4");
+    }
+
+    #[test]
+    fn unknown_variables_are_not_exposed() {
+        let env = exposed_env(vars(&[("NDN_OPERATOR_API_KEY", "secret"), ("HOME", "/root"), ("RUST_LOG", "info")]));
+        assert_eq!(env.keys().collect::<Vec<_>>(), vec!["RUST_LOG"]);
+    }
+
+    #[test]
+    fn secret_flags_are_not_serialized() {
+        let config = Config { notify_webhook_url: Some("https://hooks.example.com/token".to_string()), ..Config::default() };
+        let json = serde_json::to_value(&config).unwrap();
+        assert!(json.get("notify_webhook_url").is_none());
+    }
+}
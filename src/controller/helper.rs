@@ -1,6 +1,13 @@
 use k8s_openapi::api::core::v1::Pod;
-use kube::{Api, Client};
+use kube::{api::{Patch, PatchParams}, Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::fmt::Debug;
 use crate::{Result, Error};
+use super::audit;
+
+pub static VERSION_ANNOTATION_KEY: &str = "named-data.net/reconciled-by-version";
+pub static OPERATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub fn get_my_namespace() -> Result<String> {
     std::fs::read_to_string("/var/run/secrets/kubernetes.io/serviceaccount/namespace")
@@ -18,4 +25,71 @@ pub async fn get_my_pod(client: Client) -> Result<Pod> {
     let pod_name_raw = get_my_pod_name()?;
     let pod_name = pod_name_raw.trim_end_matches('\n');
     api_pods.get(pod_name).await.map_err(Error::KubeError)
-}
\ No newline at end of file
+}
+
+/// Stamp the object with the operator version that reconciled it.
+/// The object is only patched when the version changes, to avoid extra watch events
+pub async fn annotate_version<K>(api: &Api<K>, obj: &K) -> Result<()>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug,
+{
+    if obj.annotations().get(VERSION_ANNOTATION_KEY).map(String::as_str) == Some(OPERATOR_VERSION) {
+        return Ok(());
+    }
+    let patch = json!({
+        "metadata": {
+            "annotations": {
+                VERSION_ANNOTATION_KEY: OPERATOR_VERSION,
+            }
+        }
+    });
+    api.patch(&obj.name_any(), &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .map_err(Error::KubeError)?;
+    audit::<K>("merge-patch", None, obj.namespace().as_deref(), &obj.name_any(), None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{template_hash, Network, NetworkSpec};
+    use std::sync::{Arc, Mutex};
+
+    /// Client of a fake API server keeping the patches, and answering with the patched object
+    fn patching_client(patches: Arc<Mutex<Vec<serde_json::Value>>>, object: serde_json::Value) -> Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let (patches, mut object) = (patches.clone(), object.clone());
+            async move {
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                object["metadata"]["annotations"] = patch["metadata"]["annotations"].clone();
+                patches.lock().unwrap().push(patch);
+                let response = http::Response::builder()
+                    .status(200)
+                    .body(kube::client::Body::from(object.to_string().into_bytes()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        Client::new(service, "ns")
+    }
+
+    #[tokio::test]
+    async fn objects_are_stamped_with_the_operator_version_once() {
+        let mut object = Network::new("net", NetworkSpec::default());
+        object.metadata.namespace = Some("ns".to_string());
+        object.metadata.uid = Some("uid".to_string());
+        let hash = template_hash(&object.create_owned_daemonset(None, None));
+        let patches = Arc::new(Mutex::new(Vec::new()));
+        let api = Api::<Network>::namespaced(patching_client(patches.clone(), serde_json::to_value(&object).unwrap()), "ns");
+        annotate_version(&api, &object).await.unwrap();
+        assert_eq!(*patches.lock().unwrap(), vec![json!({ "metadata": { "annotations": { VERSION_ANNOTATION_KEY: OPERATOR_VERSION } } })]);
+        // Reconciled again by the same version
+        object.annotations_mut().insert(VERSION_ANNOTATION_KEY.to_string(), OPERATOR_VERSION.to_string());
+        annotate_version(&api, &object).await.unwrap();
+        assert_eq!(patches.lock().unwrap().len(), 1);
+        // The annotation doesn't roll the router pods
+        assert_eq!(template_hash(&object.create_owned_daemonset(None, None)), hash);
+    }
+}
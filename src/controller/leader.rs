@@ -0,0 +1,258 @@
+use super::State;
+use crate::{Error, Result};
+use chrono::{TimeDelta, Utc};
+use k8s_openapi::{api::coordination::v1::{Lease, LeaseSpec}, apimachinery::pkg::apis::meta::v1::MicroTime};
+use kube::{
+    api::{Api, ObjectMeta, PostParams},
+    client::Client,
+};
+use std::future::Future;
+use tokio::time::Duration;
+use tracing::*;
+
+/// Lease the operator replicas compete for, only its holder runs the controllers.
+/// The holder renews it every third of its duration, the others take it over once
+/// it expired or was released
+#[derive(Clone)]
+pub struct LeaderLease {
+    api: Api<Lease>,
+    name: String,
+    identity: String,
+    duration: Duration,
+}
+
+impl LeaderLease {
+    pub fn new(client: Client, name: &str, identity: &str, duration: Duration) -> Self {
+        Self {
+            api: Api::default_namespaced(client),
+            name: name.to_string(),
+            identity: identity.to_string(),
+            duration,
+        }
+    }
+
+    fn retry_period(&self) -> Duration {
+        self.duration / 3
+    }
+
+    /// Acquire the Lease, or renew it if already held. False if another replica holds it.
+    /// Writes are guarded by the resource version, so two replicas never both acquire it
+    pub async fn try_acquire(&self) -> Result<bool> {
+        let now = MicroTime(Utc::now());
+        let Some(mut lease) = self.api.get_opt(&self.name).await.map_err(Error::KubeError)? else {
+            let lease = Lease {
+                metadata: ObjectMeta { name: Some(self.name.clone()), ..ObjectMeta::default() },
+                spec: Some(LeaseSpec {
+                    holder_identity: Some(self.identity.clone()),
+                    lease_duration_seconds: Some(self.duration.as_secs() as i32),
+                    acquire_time: Some(now.clone()),
+                    renew_time: Some(now),
+                    lease_transitions: Some(0),
+                    ..LeaseSpec::default()
+                }),
+            };
+            return match self.api.create(&PostParams::default(), &lease).await {
+                Ok(_) => Ok(true),
+                Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+                Err(e) => Err(Error::KubeError(e)),
+            };
+        };
+        let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+        let holder = spec.holder_identity.as_deref().filter(|holder| !holder.is_empty());
+        let held = holder == Some(self.identity.as_str());
+        if !held && holder.is_some() {
+            let duration = TimeDelta::seconds(spec.lease_duration_seconds.unwrap_or_default().into());
+            let expired = spec.renew_time.as_ref().is_none_or(|renewed| renewed.0 + duration < now.0);
+            if !expired {
+                return Ok(false);
+            }
+        }
+        if !held {
+            spec.holder_identity = Some(self.identity.clone());
+            spec.acquire_time = Some(now.clone());
+            spec.lease_transitions = Some(spec.lease_transitions.unwrap_or_default() + 1);
+        }
+        spec.lease_duration_seconds = Some(self.duration.as_secs() as i32);
+        spec.renew_time = Some(now);
+        match self.api.replace(&self.name, &PostParams::default(), &lease).await {
+            Ok(_) => Ok(true),
+            Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+            Err(e) => Err(Error::KubeError(e)),
+        }
+    }
+
+    /// Release the Lease if held, so another replica acquires it right away
+    pub async fn release(&self) -> Result<()> {
+        let Some(mut lease) = self.api.get_opt(&self.name).await.map_err(Error::KubeError)? else {
+            return Ok(());
+        };
+        let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+        if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+            return Ok(());
+        }
+        spec.holder_identity = None;
+        spec.renew_time = None;
+        self.api
+            .replace(&self.name, &PostParams::default(), &lease)
+            .await
+            .map_err(Error::KubeError)?;
+        info!("Released the leader lease {}", self.name);
+        Ok(())
+    }
+
+    /// Wait for the Lease, then run the controllers until they stop and release it. The Lease
+    /// is renewed meanwhile, and the operator steps down if it can't be renewed in time, as
+    /// another replica may have taken over. Returns without running them if the operator
+    /// steps down first, e.g. on shutdown.
+    ///
+    /// Leads once: the Lease isn't competed for again after it is released, as the stopped
+    /// controllers can't be restarted. The process must exit once this returns, the replica
+    /// then competes again as a standby when its pod restarts
+    pub async fn lead(&self, state: &State, controllers: impl Future<Output = ()>) {
+        let acquire = async {
+            loop {
+                match self.try_acquire().await {
+                    Ok(true) => break,
+                    Ok(false) => debug!("Leader lease {} is held by another replica", self.name),
+                    Err(e) => warn!("Failed to acquire the leader lease {}: {e}", self.name),
+                }
+                tokio::time::sleep(self.retry_period()).await;
+            }
+        };
+        tokio::select! {
+            _ = acquire => {}
+            _ = state.stop_signal() => return,
+        }
+        info!("Acquired the leader lease {} as {}", self.name, self.identity);
+        state.set_leading(true);
+        let renew = async {
+            let mut renewed = tokio::time::Instant::now();
+            loop {
+                tokio::time::sleep(self.retry_period()).await;
+                match self.try_acquire().await {
+                    Ok(true) => renewed = tokio::time::Instant::now(),
+                    Ok(false) => {
+                        warn!("Leader lease {} was taken over, stepping down", self.name);
+                        break;
+                    }
+                    Err(e) if renewed.elapsed() >= self.duration => {
+                        warn!("Failed to renew the leader lease {} in time, stepping down: {e}", self.name);
+                        break;
+                    }
+                    Err(e) => warn!("Failed to renew the leader lease {}: {e}", self.name),
+                }
+            }
+            state.step_down();
+        };
+        tokio::select! {
+            _ = controllers => {}
+            _ = renew => {}
+        }
+        // The controllers stopped on step-down, the renewal already did
+        state.set_leading(false);
+        if let Err(e) = self.release().await {
+            warn!("Failed to release the leader lease {}: {e}", self.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// API server keeping a single Lease, rejecting writes of stale resource versions
+    fn lease_client(lease: Arc<Mutex<Option<serde_json::Value>>>) -> Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let lease = lease.clone();
+            async move {
+                let method = request.method().clone();
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let mut lease = lease.lock().unwrap();
+                let version = |lease: &serde_json::Value| lease["metadata"]["resourceVersion"].as_str().unwrap_or_default().to_string();
+                let (status, body) = match (method, lease.as_ref()) {
+                    (http::Method::GET, Some(current)) => (200, current.clone()),
+                    (http::Method::GET, None) => (404, json!({ "kind": "Status", "apiVersion": "v1", "status": "Failure", "reason": "NotFound", "code": 404 })),
+                    (http::Method::POST, None) | (http::Method::PUT, Some(_)) => {
+                        let mut new: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        if lease.as_ref().is_some_and(|current| version(current) != version(&new)) {
+                            (409, json!({ "kind": "Status", "apiVersion": "v1", "status": "Failure", "reason": "Conflict", "code": 409 }))
+                        } else {
+                            let next = lease.as_ref().map(|current| version(current).parse::<u32>().unwrap() + 1).unwrap_or(1);
+                            new["metadata"]["resourceVersion"] = json!(next.to_string());
+                            *lease = Some(new.clone());
+                            (200, new)
+                        }
+                    }
+                    _ => (409, json!({ "kind": "Status", "apiVersion": "v1", "status": "Failure", "reason": "AlreadyExists", "code": 409 })),
+                };
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(kube::client::Body::from(body.to_string().into_bytes()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        Client::new(service, "ns")
+    }
+
+    fn holder(lease: &Mutex<Option<serde_json::Value>>) -> Option<String> {
+        lease.lock().unwrap().as_ref()?["spec"]["holderIdentity"].as_str().map(String::from)
+    }
+
+    #[tokio::test]
+    async fn lease_is_held_by_one_replica_until_released() {
+        let lease = Arc::new(Mutex::new(None));
+        let a = LeaderLease::new(lease_client(lease.clone()), "leader", "a", Duration::from_secs(15));
+        let b = LeaderLease::new(lease_client(lease.clone()), "leader", "b", Duration::from_secs(15));
+        assert!(a.try_acquire().await.unwrap());
+        assert!(!b.try_acquire().await.unwrap());
+        // Renewing keeps the Lease
+        assert!(a.try_acquire().await.unwrap());
+        assert_eq!(holder(&lease).as_deref(), Some("a"));
+        // Only the holder releases it
+        b.release().await.unwrap();
+        assert_eq!(holder(&lease).as_deref(), Some("a"));
+        a.release().await.unwrap();
+        assert_eq!(holder(&lease), None);
+        assert!(b.try_acquire().await.unwrap());
+        assert_eq!(holder(&lease).as_deref(), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn expired_leases_are_taken_over() {
+        let renewed = MicroTime(Utc::now() - TimeDelta::seconds(20));
+        let lease = Arc::new(Mutex::new(Some(json!({
+            "apiVersion": "coordination.k8s.io/v1",
+            "kind": "Lease",
+            "metadata": { "name": "leader", "resourceVersion": "1" },
+            "spec": { "holderIdentity": "b", "leaseDurationSeconds": 15, "renewTime": renewed, "leaseTransitions": 0 },
+        }))));
+        let a = LeaderLease::new(lease_client(lease.clone()), "leader", "a", Duration::from_secs(15));
+        assert!(a.try_acquire().await.unwrap());
+        assert_eq!(holder(&lease).as_deref(), Some("a"));
+        assert_eq!(lease.lock().unwrap().as_ref().unwrap()["spec"]["leaseTransitions"], json!(1));
+    }
+
+    #[tokio::test]
+    async fn step_down_releases_the_lease_and_stops_the_controllers() {
+        let lease = Arc::new(Mutex::new(None));
+        let leader = LeaderLease::new(lease_client(lease.clone()), "leader", "a", Duration::from_secs(15));
+        let state = State::default();
+        // Like the controllers, which shut down gracefully on the stop signal
+        let controllers = state.stop_signal();
+        let lead = tokio::spawn({
+            let state = state.clone();
+            async move { leader.lead(&state, controllers).await }
+        });
+        while !state.is_leading() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(holder(&lease).as_deref(), Some("a"));
+        state.step_down();
+        tokio::time::timeout(Duration::from_secs(5), lead).await.unwrap().unwrap();
+        assert!(!state.is_leading());
+        assert_eq!(holder(&lease), None);
+    }
+}
@@ -1,25 +1,46 @@
 use chrono::{DateTime, Utc};
 use futures::StreamExt;
-use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::api::core::v1::{ConfigMap, Node, ObjectReference, Pod, Secret};
 use kube::{
-    api::{Api, ListParams, ResourceExt},
+    api::{Api, ListParams, Resource, ResourceExt},
     client::Client,
-    core::Expression,
+    core::{Expression, Selector, SelectorExt},
     runtime::{
-        controller::{Action, Controller},
-        events::{Recorder, Reporter},
-        finalizer::{finalizer, Event as Finalizer},
-        watcher,
+        controller::{Action, Config as ControllerConfig, Controller},
+        events::{Event, EventType, Recorder, Reporter},
+        finalizer::{self, finalizer, Event as Finalizer},
+        metadata_watcher,
+        reflector::{self, ObjectRef, Store},
+        watcher, WatchStreamExt,
     },
+    core::PartialObjectMeta,
 };
 use serde::Serialize;
-use std::sync::Arc;
-use tokio::{sync::RwLock, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    future::Future,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+};
+use tokio::{sync::{watch, RwLock}, time::Duration};
 use tracing::*;
 
-use super::{pod_apply, pod_cleanup, Network, Router, DS_LABEL_KEY, NETWORK_FINALIZER, ROUTER_FINALIZER};
+use super::{
+    audit, audit_finalizer, http_client, node_problems, parse_label_selector, pod_apply, pod_cleanup, read_ca_bundle, Config, EffectiveConfig, EventSeverity, Network, Notification, Notifier, Router, DS_LABEL_KEY, NETWORK_FINALIZER,
+    NETWORK_LABEL_KEY, RECONCILE_FAILED_REASON, ROUTER_FINALIZER,
+};
 use crate::{controller::POD_FINALIZER, Error, Result};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+
+
+/// Generation an object failed to reconcile at, and how many times in a row
+type FailureCount = (Option<i64>, u32);
 
+/// Period failed reconciles of Networks and Routers are retried with
+static RETRY_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// Object and reason of published events
+type EventKey = (String, String);
 
 // Context for our reconciler
 #[derive(Clone)]
@@ -30,13 +51,288 @@ pub struct Context {
     pub recorder: Recorder,
     /// Diagnostics read by the web server
     pub diagnostics: Arc<RwLock<Diagnostics>>,
+    /// Prometheus metrics
+    #[cfg(feature = "metrics")]
+    pub metrics: Metrics,
+    /// Only log events instead of publishing them
+    pub disable_events: bool,
+    /// Label the nodes with the Networks running on them
+    pub label_nodes: bool,
+    /// Cluster the events are reported from
+    pub cluster_name: Option<String>,
+    /// Webhook notifier of significant events
+    pub notifier: Option<Arc<Notifier>>,
+    /// Labels of the Networks this operator instance manages, all if unset
+    pub network_selector: Option<Selector>,
+    /// Networks the selector is checked against, for the objects belonging to a Network
+    pub networks: Option<Store<Network>>,
+    /// Consecutive failed reconciles after which an object is stalled, 0 retries forever
+    pub max_retries: u32,
+    /// Consecutive failed reconciles by object, with the generation they failed at
+    failures: Arc<Mutex<HashMap<String, FailureCount>>>,
+    /// Notes of the events last published by object and reason, see `publish_changed_events`
+    published: Arc<Mutex<HashMap<EventKey, BTreeSet<String>>>>,
+}
+
+impl Context {
+    /// Whether this operator instance manages the Network, by its labels. The objects of a
+    /// missing Network are only managed without a Network selector, or for their cleanup
+    pub fn manages_network(&self, namespace: &str, name: &str, deleting: bool) -> bool {
+        let Some(selector) = &self.network_selector else {
+            return true;
+        };
+        let network = self.networks.as_ref().and_then(|networks| networks.get(&ObjectRef::new(name).within(namespace)));
+        match network {
+            Some(network) => selector.matches(network.labels()),
+            None => deleting,
+        }
+    }
+
+    /// Publish an event. `event_types` maps event reasons to the type they are published
+    /// with, overriding the type set by the reconciler
+    pub async fn publish_event(
+        &self,
+        mut event: Event,
+        reference: &ObjectReference,
+        event_types: Option<&BTreeMap<String, EventSeverity>>,
+    ) -> Result<()> {
+        if let Some(severity) = event_types.and_then(|types| types.get(&event.reason)) {
+            event.type_ = match severity {
+                EventSeverity::Normal => EventType::Normal,
+                EventSeverity::Warning => EventType::Warning,
+            };
+        }
+        self.notify(&event, reference);
+        if self.disable_events {
+            info!(
+                "Event {:?} {} on {}/{}{}: {}",
+                event.type_,
+                event.reason,
+                reference.kind.as_deref().unwrap_or_default(),
+                reference.name.as_deref().unwrap_or_default(),
+                self.cluster_name.as_ref().map(|cluster| format!(" in cluster {cluster}")).unwrap_or_default(),
+                event.note.as_deref().unwrap_or_default()
+            );
+            return Ok(());
+        }
+        self.recorder
+            .publish(&event, reference)
+            .await
+            .map_err(Error::KubeError)?;
+        // Events are named by the recorder, they are recorded under the object they are about
+        audit::<k8s_openapi::api::events::v1::Event>(
+            "create",
+            None,
+            reference.namespace.as_deref(),
+            reference.name.as_deref().unwrap_or_default(),
+            None,
+        );
+        Ok(())
+    }
+
+    /// Publish the events of a check run on every reconcile only when they change: events whose
+    /// note was published last time for the object and reason are skipped. No events reset the
+    /// reason, so a problem coming back is reported again
+    pub async fn publish_changed_events(
+        &self,
+        reference: &ObjectReference,
+        reason: &str,
+        events: Vec<Event>,
+        event_types: Option<&BTreeMap<String, EventSeverity>>,
+    ) -> Result<()> {
+        let key = (reference_key(reference), reason.to_string());
+        let previous = self.published.lock().unwrap().get(&key).cloned().unwrap_or_default();
+        let mut notes = BTreeSet::new();
+        for event in events {
+            let note = event.note.clone().unwrap_or_default();
+            if !previous.contains(&note) {
+                self.publish_event(event, reference, event_types).await?;
+            }
+            notes.insert(note);
+        }
+        let mut published = self.published.lock().unwrap();
+        match notes.is_empty() {
+            true => published.remove(&key),
+            false => published.insert(key, notes),
+        };
+        Ok(())
+    }
+
+    /// Forget the events published for a deleted object
+    pub fn forget_events(&self, reference: &ObjectReference) {
+        let object = reference_key(reference);
+        self.published.lock().unwrap().retain(|(key, _), _| *key != object);
+    }
+
+    /// Post the event to the notification webhook in the background, if it is configured
+    fn notify(&self, event: &Event, reference: &ObjectReference) {
+        if let Some(notifier) = self.notifier.clone() {
+            let notification = Notification::new(event, reference, self.cluster_name.clone());
+            tokio::spawn(async move { notifier.notify(notification).await });
+        }
+    }
+
+    /// Notify a failed reconcile of the object
+    fn notify_failed(&self, reference: &ObjectReference, error: &Error) {
+        let event = Event {
+            type_: EventType::Warning,
+            reason: RECONCILE_FAILED_REASON.into(),
+            note: Some(error.to_string()),
+            action: "Reconciling".into(),
+            secondary: None,
+        };
+        self.notify(&event, reference);
+    }
+
+    /// Count a failed reconcile of the object, restarting the count when its spec changed
+    fn record_failure<K: Resource<DynamicType = ()>>(&self, obj: &K) -> u32 {
+        let mut failures = self.failures.lock().unwrap();
+        let entry = failures.entry(failure_key(obj)).or_insert((obj.meta().generation, 0));
+        if entry.0 != obj.meta().generation {
+            *entry = (obj.meta().generation, 0);
+        }
+        entry.1 += 1;
+        entry.1
+    }
+
+    fn record_success<K: Resource<DynamicType = ()>>(&self, obj: &K) {
+        self.failures.lock().unwrap().remove(&failure_key(obj));
+    }
+
+    /// Whether the object failed `max_retries` times in a row since its spec last changed.
+    /// Stalled objects are skipped until their spec changes or they are deleted
+    fn is_stalled<K: Resource<DynamicType = ()>>(&self, obj: &K) -> bool {
+        if self.max_retries == 0 || obj.meta().deletion_timestamp.is_some() {
+            return false;
+        }
+        self.failures
+            .lock()
+            .unwrap()
+            .get(&failure_key(obj))
+            .is_some_and(|(generation, count)| *generation == obj.meta().generation && *count >= self.max_retries)
+    }
+
+    /// Forget the failures of the objects no longer in the store, like objects whose
+    /// finalizer was removed by hand, which are deleted without a cleanup
+    fn prune_failures<K>(&self, store: &Store<K>)
+    where
+        K: Resource<DynamicType = ()> + Clone,
+    {
+        let present: BTreeSet<String> = store.state().iter().map(|obj| failure_key(obj.as_ref())).collect();
+        self.failures.lock().unwrap().retain(|key, _| present.contains(key));
+    }
+
+    /// Prune the failures against the store of the controller every retry period
+    fn spawn_failure_pruning<K>(self: Arc<Self>, store: Store<K>)
+    where
+        K: Resource<DynamicType = ()> + Clone + Send + Sync + 'static,
+    {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RETRY_PERIOD).await;
+                self.prune_failures(&store);
+            }
+        });
+    }
+
+    /// Requeue of a failed reconcile. Once the object failed `max_retries` times in a row with
+    /// a permanent error, it is reported as stalled in the background and only reconciled again
+    /// on a change. Transient errors, like an API server outage, are retried forever
+    fn retry_or_stall<K, F>(self: Arc<Self>, obj: &K, error: &Error, requeue: Duration, stall: impl FnOnce(Arc<Self>, u32, String) -> F) -> Action
+    where
+        K: Resource<DynamicType = ()>,
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        if !is_permanent(error) {
+            return Action::requeue(requeue);
+        }
+        let failures = self.record_failure(obj);
+        if self.max_retries == 0 || failures < self.max_retries {
+            return Action::requeue(requeue);
+        }
+        if failures == self.max_retries {
+            warn!("Reconcile of {} failed {} times, stalling it", failure_key(obj), failures);
+            let stalled = stall(self.clone(), failures, error.to_string());
+            tokio::spawn(async move {
+                if let Err(e) = stalled.await {
+                    warn!("Failed to report a stalled reconcile: {}", e);
+                }
+            });
+        }
+        Action::await_change()
+    }
+
+    /// Record a successful reconcile of an object of the given kind, if metrics are enabled
+    fn reconciled(&self, _kind: &str) {
+        #[cfg(feature = "metrics")]
+        self.metrics.reconciled(_kind);
+    }
+
+    /// Record a failed reconcile of an object of the given kind, if metrics are enabled
+    fn reconcile_failed(&self, _kind: &str) {
+        #[cfg(feature = "metrics")]
+        self.metrics.reconcile_failed(_kind);
+    }
+
+    /// Record the actual and expected neighbor counts of a router, if metrics are enabled
+    pub fn router_neighbors(&self, _router: &Router, _neighbors: usize, _expected: usize) {
+        #[cfg(feature = "metrics")]
+        self.metrics.router_neighbors(&_router.namespace().unwrap_or_default(), &_router.name_any(), _neighbors, _expected);
+    }
+
+    /// Record whether a router is isolated, if metrics are enabled
+    pub fn router_isolated(&self, _router: &Router, _isolated: bool) {
+        #[cfg(feature = "metrics")]
+        self.metrics.router_isolated(&_router.namespace().unwrap_or_default(), &_router.name_any(), _isolated);
+    }
+
+    /// Stop exporting the metrics of a deleted router
+    pub fn router_deleted(&self, _router: &Router) {
+        #[cfg(feature = "metrics")]
+        self.metrics.router_deleted(&_router.namespace().unwrap_or_default(), &_router.name_any());
+    }
+}
+
+/// Whether retrying a failed reconcile can't succeed until the object changes: invalid specs,
+/// objects missing their labels, and changes refused by the API server's validation
+fn is_permanent(error: &Error) -> bool {
+    match error {
+        Error::InvalidSpec(_) | Error::MissingLabel(_) | Error::MissingAnnotation(_) | Error::SerializationError(_) => true,
+        Error::KubeError(kube::Error::Api(response)) => matches!(response.code, 400 | 422),
+        Error::FinalizerError(error) => match error.as_ref() {
+            finalizer::Error::ApplyFailed(error) | finalizer::Error::CleanupFailed(error) => is_permanent(error),
+            finalizer::Error::AddFinalizer(_) | finalizer::Error::RemoveFinalizer(_) => false,
+            finalizer::Error::UnnamedObject | finalizer::Error::InvalidFinalizer => true,
+        },
+        _ => false,
+    }
+}
+
+/// Key of an object in the failed reconcile counts
+fn failure_key<K: Resource<DynamicType = ()>>(obj: &K) -> String {
+    format!("{}/{}/{}", K::kind(&()), obj.meta().namespace.as_deref().unwrap_or_default(), obj.meta().name.as_deref().unwrap_or_default())
+}
+
+/// Key of an object in the published event notes
+fn reference_key(reference: &ObjectReference) -> String {
+    format!(
+        "{}/{}/{}",
+        reference.kind.as_deref().unwrap_or_default(),
+        reference.namespace.as_deref().unwrap_or_default(),
+        reference.name.as_deref().unwrap_or_default()
+    )
 }
 
 async fn reconcile_network(network: Arc<Network>, ctx: Arc<Context>) -> Result<Action> {
     let ns = network.namespace().unwrap();
     let api_nw: Api<Network> = Api::namespaced(ctx.client.clone(), &ns);
+    if ctx.is_stalled(network.as_ref()) {
+        debug!("Network \"{}\" in {} is stalled until its spec changes", network.name_any(), ns);
+        return Ok(Action::await_change());
+    }
 
     info!("Reconciling Network \"{}\" in {}", network.name_any(), ns);
+    let network_ref = network.clone();
     finalizer(&api_nw, NETWORK_FINALIZER, network, async |event| {
         match event {
             Finalizer::Apply(network) => network.reconcile(ctx.clone()).await,
@@ -45,27 +341,61 @@ async fn reconcile_network(network: Arc<Network>, ctx: Arc<Context>) -> Result<A
     })
     .await
     .map_err(|e| Error::FinalizerError(Box::new(e)))
+    .inspect(|_| audit_finalizer(network_ref.as_ref(), NETWORK_FINALIZER))
+    .inspect(|_| ctx.reconciled("Network"))
+    .inspect(|_| ctx.record_success(network_ref.as_ref()))
+}
+
+async fn reconcile_network_status(network: Arc<Network>, ctx: Arc<Context>) -> Result<Action> {
+    // Deleted Networks are left to their cleanup, and Networks not reconciled yet have no pods
+    if network.meta().deletion_timestamp.is_some() || !network.finalizers().iter().any(|f| f == NETWORK_FINALIZER) {
+        return Ok(Action::await_change());
+    }
+    debug!("Updating the status of Network \"{}\" in {}", network.name_any(), network.namespace().unwrap());
+    network.reconcile_status(ctx).await
 }
 
 async fn reconcile_router(router: Arc<Router>, ctx: Arc<Context>) -> Result<Action> {
     let ns = router.namespace().unwrap();
     let api_router: Api<Router> = Api::namespaced(ctx.client.clone(), &ns);
 
+    // Routers of other operator instances' Networks are left alone
+    let network = router.labels().get(NETWORK_LABEL_KEY).cloned().unwrap_or_default();
+    if !ctx.manages_network(&ns, &network, router.meta().deletion_timestamp.is_some()) {
+        debug!("Router \"{}\" in {} belongs to an unmanaged Network", router.name_any(), ns);
+        return Ok(Action::await_change());
+    }
+    if ctx.is_stalled(router.as_ref()) {
+        debug!("Router \"{}\" in {} is stalled until its spec changes", router.name_any(), ns);
+        return Ok(Action::await_change());
+    }
     info!("Reconciling Router \"{}\" in {}", router.name_any(), ns);
-    finalizer(&api_router, ROUTER_FINALIZER, router, async |event| {
+    let router_ref = router.clone();
+    let action = finalizer(&api_router, ROUTER_FINALIZER, router, async |event| {
         match event {
             Finalizer::Apply(router) => router.reconcile(ctx.clone()).await,
             Finalizer::Cleanup(router) => router.cleanup(ctx.clone()).await,
         }
     })
     .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    .map_err(|e| Error::FinalizerError(Box::new(e)))?;
+    audit_finalizer(router_ref.as_ref(), ROUTER_FINALIZER);
+    ctx.reconciled("Router");
+    ctx.record_success(router_ref.as_ref());
+    router_ref.clear_stalled(&api_router).await?;
+    Ok(action)
 }
 
 async fn reconcile_pod(pod: Arc<Pod>, ctx: Arc<Context>) -> Result<Action> {
     let ns = pod.namespace().unwrap();
     let api_pod: Api<Pod> = Api::namespaced(ctx.client.clone(), &ns);
+    let network = pod.labels().get(DS_LABEL_KEY).cloned().unwrap_or_default();
+    if !ctx.manages_network(&ns, &network, pod.meta().deletion_timestamp.is_some()) {
+        debug!("Pod \"{}\" in {} belongs to an unmanaged Network", pod.name_any(), ns);
+        return Ok(Action::await_change());
+    }
     info!("Reconciling Pod \"{}\" in {}", pod.name_any(), ns);
+    let pod_ref = pod.clone();
     finalizer(&api_pod, POD_FINALIZER, pod, async |event| {
         match event {
             Finalizer::Apply(pod) => pod_apply(pod, (*ctx).clone()).await,
@@ -74,6 +404,8 @@ async fn reconcile_pod(pod: Arc<Pod>, ctx: Arc<Context>) -> Result<Action> {
     })
     .await
     .map_err(|e| Error::FinalizerError(Box::new(e)))
+    .inspect(|_| audit_finalizer(pod_ref.as_ref(), POD_FINALIZER))
+    .inspect(|_| ctx.reconciled("Pod"))
 }
 
 /// Diagnostics to be exposed by the web server
@@ -93,8 +425,13 @@ impl Default for Diagnostics {
     }
 }
 impl Diagnostics {
-    fn recorder(&self, client: Client) -> Recorder {
-        Recorder::new(client, self.reporter.clone())
+    /// Event recorder, reporting from the cluster if it is named
+    fn recorder(&self, client: Client, cluster_name: Option<String>) -> Recorder {
+        let reporter = Reporter {
+            instance: cluster_name.or(self.reporter.instance.clone()),
+            ..self.reporter.clone()
+        };
+        Recorder::new(client, reporter)
     }
 }
 
@@ -103,39 +440,372 @@ impl Diagnostics {
 pub struct State {
     /// Diagnostics populated by the reconciler
     diagnostics: Arc<RwLock<Diagnostics>>,
+    /// Operator configuration
+    config: Config,
+    /// Metrics exposed by the web server
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
+    /// Webhook notifier shared by the controllers, throttling across them
+    notifier: Option<Arc<Notifier>>,
+    /// Labels of the managed Networks
+    network_selector: Option<Selector>,
+    /// Kinds whose controller listed its objects since the start, see `CONTROLLER_CACHES`
+    synced_caches: Arc<RwLock<BTreeSet<&'static str>>>,
+    /// Set once the operator steps down, stopping the controllers
+    stopped: watch::Sender<bool>,
+    /// Whether this replica holds the leader Lease, with leader election
+    leading: Arc<AtomicBool>,
 }
 
+/// Kinds of the objects the controllers list on startup
+static CONTROLLER_CACHES: &[&str] = &["Network", "Router", "Pod"];
+
 impl State {
+    /// Fails if the configured CA bundle can't be read or isn't PEM,
+    /// or if the Network selector is invalid
+    pub fn new(config: Config) -> Result<Self> {
+        let network_selector = config
+            .network_selector
+            .as_deref()
+            .map(parse_label_selector)
+            .transpose()?
+            .filter(|selector| !selector.selects_all());
+        let ca_bundle = match &config.ca_bundle_file {
+            Some(path) => read_ca_bundle(path)?,
+            None => Vec::new(),
+        };
+        let notifier = match config.notify_webhook_url.clone() {
+            Some(url) => Some(Arc::new(Notifier::new(
+                url,
+                config.notify_reasons.clone(),
+                Duration::from_secs(config.notify_interval_seconds),
+                http_client(&ca_bundle)?,
+            ))),
+            None => None,
+        };
+        Ok(Self {
+            config,
+            notifier,
+            network_selector,
+            ..Self::default()
+        })
+    }
+
+    fn controller_config(&self) -> ControllerConfig {
+        ControllerConfig::default().concurrency(self.config.concurrency)
+    }
+
     /// State getter
     pub async fn diagnostics(&self) -> Diagnostics {
         self.diagnostics.read().await.clone()
     }
 
+    /// Kinds whose controller hasn't listed its objects yet, the operator is ready without any
+    pub async fn pending_caches(&self) -> Vec<&'static str> {
+        let synced = self.synced_caches.read().await;
+        CONTROLLER_CACHES.iter().filter(|kind| !synced.contains(*kind)).copied().collect()
+    }
+
+    /// Watcher config of the lists and watches of the controllers
+    fn watcher_config(&self) -> watcher::Config {
+        watcher::Config::default().page_size(self.config.list_page_size)
+    }
+
+    /// Record the cache of a controller as synced once it listed its objects. Exits the operator
+    /// if that takes longer than the cache sync timeout, rather than staying unready forever
+    fn track_cache_sync<K>(&self, kind: &'static str, store: Store<K>)
+    where
+        K: Resource<DynamicType = ()> + Clone + Send + Sync + 'static,
+    {
+        let synced_caches = self.synced_caches.clone();
+        let timeout = self.config.cache_sync_timeout_seconds;
+        tokio::spawn(async move {
+            let synced = match timeout {
+                0 => Ok(store.wait_until_ready().await),
+                seconds => tokio::time::timeout(Duration::from_secs(seconds), store.wait_until_ready()).await,
+            };
+            match synced {
+                Ok(Ok(())) => {
+                    info!("{kind} cache synced");
+                    synced_caches.write().await.insert(kind);
+                }
+                // The controller stopped before its first list, e.g. on shutdown
+                Ok(Err(_)) => {}
+                Err(_) => {
+                    error!("{kind} cache not synced within {timeout}s, consider raising the cache sync timeout");
+                    std::process::exit(1);
+                }
+            }
+        });
+    }
+
+    /// Stop the controllers gracefully, and with leader election release the Lease once they stopped
+    pub fn step_down(&self) {
+        self.stopped.send_replace(true);
+    }
+
+    /// Resolves once the operator steps down
+    pub fn stop_signal(&self) -> impl Future<Output = ()> + Send + Sync + 'static {
+        let mut stopped = self.stopped.subscribe();
+        async move {
+            // The sender lives as long as the state, and stopping also ends the wait
+            let _ = stopped.wait_for(|stopped| *stopped).await;
+        }
+    }
+
+    /// Whether this replica holds the leader Lease
+    pub fn is_leading(&self) -> bool {
+        self.leading.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_leading(&self, leading: bool) {
+        self.leading.store(leading, Ordering::SeqCst);
+    }
+
+    /// Whether this operator instance manages the Network, by its labels
+    pub fn manages(&self, network: &Network) -> bool {
+        self.network_selector.as_ref().is_none_or(|selector| selector.matches(network.labels()))
+    }
+
+    /// Effective configuration getter
+    pub fn config(&self) -> EffectiveConfig {
+        self.config.effective()
+    }
+
+    /// Metrics getter
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> String {
+        self.metrics.encode()
+    }
+
     // Create a Controller Context that can update State
     pub async fn to_context(&self, client: Client) -> Arc<Context> {
         Arc::new(Context {
             client: client.clone(),
-            recorder: self.diagnostics.read().await.recorder(client),
+            recorder: self.diagnostics.read().await.recorder(client, self.config.cluster_name.clone()),
             diagnostics: self.diagnostics.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics.clone(),
+            disable_events: self.config.disable_events,
+            label_nodes: self.config.label_nodes,
+            cluster_name: self.config.cluster_name.clone(),
+            notifier: self.notifier.clone(),
+            network_selector: self.network_selector.clone(),
+            networks: None,
+            max_retries: self.config.max_reconcile_retries,
+            failures: Arc::default(),
+            published: Arc::default(),
         })
     }
+
+    /// Context of the controllers of the objects belonging to a Network. With a Network selector,
+    /// the Networks are reflected to check it, and listed before the controller starts, as every
+    /// object would look unmanaged until then
+    async fn to_network_scoped_context(&self, client: Client) -> Arc<Context> {
+        let mut ctx = (*self.to_context(client.clone()).await).clone();
+        if self.network_selector.is_some() {
+            let networks = networks(client, self.watcher_config());
+            if networks.wait_until_ready().await.is_err() {
+                warn!("Network cache stopped before listing the Networks");
+            }
+            ctx.networks = Some(networks);
+        }
+        Arc::new(ctx)
+    }
 }
 
-fn network_error_policy(_: Arc<Network>, error: &Error, _: Arc<Context>) -> Action {
+fn network_error_policy(network: Arc<Network>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
-    Action::requeue(Duration::from_secs(5 * 60))
+    ctx.reconcile_failed("Network");
+    ctx.notify_failed(&network.object_ref(&()), error);
+    let stalled = network.clone();
+    ctx.retry_or_stall(network.as_ref(), error, RETRY_PERIOD, move |ctx, retries, error| async move {
+        stalled.stall(ctx, retries, error).await
+    })
+}
+
+fn network_status_error_policy(_: Arc<Network>, error: &Error, _: Arc<Context>) -> Action {
+    warn!("status update failed: {:?}", error);
+    Action::requeue(Duration::from_secs(60))
 }
 
-fn router_error_policy(_: Arc<Router>, error: &Error, _: Arc<Context>) -> Action {
+fn router_error_policy(router: Arc<Router>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
-    Action::requeue(Duration::from_secs(5 * 60))
+    ctx.reconcile_failed("Router");
+    ctx.notify_failed(&router.object_ref(&()), error);
+    let stalled = router.clone();
+    ctx.retry_or_stall(router.as_ref(), error, RETRY_PERIOD, move |ctx, retries, error| async move {
+        stalled.stall(ctx, retries, error).await
+    })
 }
 
-fn pod_error_policy(_: Arc<Pod>, error: &Error, _: Arc<Context>) -> Action {
+fn pod_error_policy(_: Arc<Pod>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {:?}", error);
+    ctx.reconcile_failed("Pod");
     Action::requeue(Duration::from_secs(60))
 }
 
+/// Names of the nodes of the cluster, reflected from their metadata only
+fn node_names(client: Client, config: watcher::Config) -> Store<PartialObjectMeta<Node>> {
+    let (reader, writer) = reflector::store();
+    let stream = reflector::reflector(writer, metadata_watcher(Api::<Node>::all(client), config))
+        .default_backoff()
+        .touched_objects()
+        .for_each(|_| futures::future::ready(()));
+    tokio::spawn(stream);
+    reader
+}
+
+/// Networks of the cluster, reflected for the mappers of other controllers
+fn networks(client: Client, config: watcher::Config) -> Store<Network> {
+    let (reader, writer) = reflector::store();
+    let stream = reflector::reflector(writer, watcher(Api::<Network>::all(client), config))
+        .default_backoff()
+        .touched_objects()
+        .for_each(|_| futures::future::ready(()));
+    tokio::spawn(stream);
+    reader
+}
+
+/// Last value seen of each node, so the Node mappers only forward changes. The nodes
+/// removed from the cluster are forgotten once more nodes are remembered than exist
+struct NodeMemory<T> {
+    seen: Mutex<HashMap<String, T>>,
+    nodes: Store<PartialObjectMeta<Node>>,
+}
+
+impl<T> NodeMemory<T> {
+    fn new(nodes: Store<PartialObjectMeta<Node>>) -> Self {
+        Self { seen: Mutex::new(HashMap::new()), nodes }
+    }
+
+    /// Remember the value of the node, returning the previous one
+    fn remember(&self, node: &str, value: T) -> Option<T> {
+        let mut seen = self.seen.lock().unwrap();
+        let previous = seen.insert(node.to_string(), value);
+        let existing = self.nodes.state().len();
+        if existing > 0 && seen.len() > existing {
+            seen.retain(|name, _| name == node || self.nodes.get(&ObjectRef::new(name)).is_some());
+        }
+        previous
+    }
+}
+
+/// Maps Node events to the Networks selecting the node, or selecting it before its labels
+/// changed. Only new nodes and label changes are forwarded, status heartbeats are ignored.
+/// Removed nodes need no mapping: their router pods are deleted, which cleans up the Routers
+fn networks_for_node(networks: Store<Network>, nodes: Store<PartialObjectMeta<Node>>) -> impl Fn(Node) -> Vec<ObjectRef<Network>> {
+    let known_labels = NodeMemory::new(nodes);
+    move |node| {
+        let labels = node.labels().clone();
+        let previous = known_labels.remember(&node.name_any(), labels.clone());
+        if previous.as_ref() == Some(&labels) {
+            return Vec::new();
+        }
+        networks
+            .state()
+            .iter()
+            .filter(|network| network.selects_node(&node) || previous.as_ref().is_some_and(|labels| network.selects_labels(labels)))
+            .map(|network| ObjectRef::from_obj(network.as_ref()))
+            .collect()
+    }
+}
+
+/// Maps Node events to the Routers on the node when its problems change, see `node_problems`
+fn routers_for_node(routers: Store<Router>, nodes: Store<PartialObjectMeta<Node>>) -> impl Fn(Node) -> Vec<ObjectRef<Router>> {
+    let known_problems = NodeMemory::new(nodes);
+    move |node| {
+        let problems = node_problems(&node);
+        let previous = known_problems.remember(&node.name_any(), problems.clone());
+        if previous.is_none_or(|previous| previous == problems) {
+            return Vec::new();
+        }
+        routers
+            .state()
+            .iter()
+            .filter(|router| router.spec.node_name == node.name_any())
+            .map(|router| ObjectRef::from_obj(router.as_ref()))
+            .collect()
+    }
+}
+
+/// Maps Network events to its Routers when the spec changes or the Network becomes reconciled,
+/// so Routers waiting for their Network resume and follow its spec. Status updates are ignored
+fn routers_for_network(routers: Store<Router>) -> impl Fn(Network) -> Vec<ObjectRef<Router>> {
+    let seen = Mutex::new(HashMap::<ObjectRef<Network>, (Option<i64>, bool)>::new());
+    let of_network = |router: &Router, network: &ObjectRef<Network>| {
+        router.namespace() == network.namespace && router.labels().get(NETWORK_LABEL_KEY) == Some(&network.name)
+    };
+    move |network| {
+        let key = ObjectRef::from_obj(&network);
+        let state = (network.metadata.generation, network.is_reconciled());
+        let mut seen = seen.lock().unwrap();
+        if seen.insert(key.clone(), state) == Some(state) {
+            return Vec::new();
+        }
+        let routers = routers.state();
+        // Networks without Routers, e.g. deleted ones, are forgotten
+        seen.retain(|network, _| routers.iter().any(|router| of_network(router, network)));
+        routers
+            .iter()
+            .filter(|router| of_network(router, &key))
+            .map(|router| ObjectRef::from_obj(router.as_ref()))
+            .collect()
+    }
+}
+
+/// Maps ConfigMap events to the Routers of the Networks of the namespace whose external
+/// topology, or the one of their template, is read from the ConfigMap
+fn routers_for_config_map(routers: Store<Router>, networks: Store<Network>) -> impl Fn(PartialObjectMeta<ConfigMap>) -> Vec<ObjectRef<Router>> {
+    move |config_map| {
+        let networks: Vec<Arc<Network>> = networks.state().into_iter().filter(|network| network.namespace() == config_map.namespace()).collect();
+        let topology_config_map = |network: &Network| {
+            let template = network.template_name().and_then(|name| networks.iter().find(|template| template.name_any() == name));
+            let resolved = template.and_then(|template| network.with_template(template).ok());
+            resolved.as_ref().unwrap_or(network).spec.external_topology.as_ref().map(|topology| topology.config_map.clone())
+        };
+        let names: BTreeSet<String> = networks
+            .iter()
+            .filter(|network| topology_config_map(network) == Some(config_map.name_any()))
+            .map(|network| network.name_any())
+            .collect();
+        routers
+            .state()
+            .iter()
+            .filter(|router| router.namespace() == config_map.namespace())
+            .filter(|router| router.labels().get(NETWORK_LABEL_KEY).is_some_and(|network| names.contains(network)))
+            .map(|router| ObjectRef::from_obj(router.as_ref()))
+            .collect()
+    }
+}
+
+/// Maps Network events to the Networks using the Network as their template
+fn networks_for_template(networks: Store<Network>) -> impl Fn(Network) -> Vec<ObjectRef<Network>> {
+    move |template| {
+        networks
+            .state()
+            .iter()
+            .filter(|network| network.namespace() == template.namespace())
+            .filter(|network| network.template_name() == Some(template.name_any().as_str()))
+            .map(|network| ObjectRef::from_obj(network.as_ref()))
+            .collect()
+    }
+}
+
+/// Maps Secret events to the Networks of the namespace referencing the Secret. Only the
+/// metadata of the Secrets is watched, their data is never read
+fn networks_for_secret(networks: Store<Network>) -> impl Fn(PartialObjectMeta<Secret>) -> Vec<ObjectRef<Network>> {
+    move |secret| {
+        networks
+            .state()
+            .iter()
+            .filter(|network| network.namespace() == secret.namespace())
+            .filter(|network| network.referenced_secrets().contains(&secret.name_any()))
+            .map(|network| ObjectRef::from_obj(network.as_ref()))
+            .collect()
+    }
+}
+
 pub async fn run_nw(state: State) {
     let client = Client::try_default().await.expect("Expected a valid KUBECONFIG environment variable");
     let api_nw = Api::<Network>::all(client.clone());
@@ -144,13 +814,70 @@ pub async fn run_nw(state: State) {
         info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
         std::process::exit(1);
     }
-    Controller::new(api_nw, watcher::Config::default().any_semantic())
+    // The Routers and router pods only change the status, updated by `run_nw_status`
+    let wc = match &state.network_selector {
+        Some(selector) => state.watcher_config().labels_from(selector),
+        None => state.watcher_config(),
+    };
+    // Networks wait for their events to settle, e.g. during node scale events
+    let controller = Controller::new(api_nw, wc.any_semantic())
+        .with_config(state.controller_config().debounce(Duration::from_secs(state.config.debounce_seconds)));
+    state.track_cache_sync("Network", controller.store());
+    // Networks derived from a template follow the changes of the template
+    let templates = controller.store();
+    let controller = controller.watches(Api::<Network>::all(client.clone()), state.watcher_config(), networks_for_template(templates));
+    let controller = match state.config.watch_nodes {
+        true => {
+            let networks = controller.store();
+            let nodes = node_names(client.clone(), state.watcher_config());
+            controller.watches(Api::<Node>::all(client.clone()), state.watcher_config(), networks_for_node(networks, nodes))
+        }
+        false => controller,
+    };
+    let controller = match state.config.watch_secrets {
+        true => {
+            let networks = controller.store();
+            let secrets = metadata_watcher(Api::<Secret>::all(client.clone()), state.watcher_config()).default_backoff().touched_objects();
+            controller.watches_stream(secrets, networks_for_secret(networks))
+        }
+        false => controller,
+    };
+    let ctx = state.to_context(client.clone()).await;
+    ctx.clone().spawn_failure_pruning(controller.store());
+    controller
+        .graceful_shutdown_on(state.stop_signal())
         .shutdown_on_signal()
-        .run(reconcile_network, network_error_policy, state.to_context(client.clone()).await)
+        .run(reconcile_network, network_error_policy, ctx)
         .filter_map(async |x| { std::result::Result::ok(x) })
         .for_each(async |_| () ).await;
 }
 
+/// Keep the status of the Networks derived from their Routers and router pods up to date: router
+/// pods are watched to surface their restarts, and Routers to surface partitions of the mesh
+pub async fn run_nw_status(state: State) {
+    let client = Client::try_default().await.expect("Expected a valid KUBECONFIG environment variable");
+    let wc = match &state.network_selector {
+        Some(selector) => state.watcher_config().labels_from(selector),
+        None => state.watcher_config(),
+    };
+    Controller::new(Api::<Network>::all(client.clone()), wc.any_semantic())
+        .with_config(state.controller_config().debounce(Duration::from_secs(state.config.debounce_seconds)))
+        .owns(Api::<Router>::all(client.clone()), state.watcher_config())
+        .watches(
+            Api::<Pod>::all(client.clone()),
+            state.watcher_config().labels_from(&Expression::Exists(DS_LABEL_KEY.into()).into()),
+            |pod| {
+                let network = pod.labels().get(DS_LABEL_KEY)?;
+                Some(ObjectRef::new(network).within(&pod.namespace()?))
+            },
+        )
+        .graceful_shutdown_on(state.stop_signal())
+        .shutdown_on_signal()
+        .run(reconcile_network_status, network_status_error_policy, state.to_context(client.clone()).await)
+        .filter_map(async |x| { std::result::Result::ok(x) })
+        .for_each(async |_| ()).await;
+}
+
 pub async fn run_router(state: State) {
     let client = Client::try_default().await.expect("Expected a valid KUBECONFIG environment variable");
     let api_router = Api::<Router>::all(client.clone());
@@ -159,9 +886,37 @@ pub async fn run_router(state: State) {
         info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
         std::process::exit(1);
     }
-    Controller::new(api_router, watcher::Config::default().any_semantic())
+    let controller = Controller::new(api_router, state.watcher_config().any_semantic())
+        .with_config(state.controller_config());
+    state.track_cache_sync("Router", controller.store());
+    // Routers wait for their Network to be reconciled, and follow its spec
+    let routers = controller.store();
+    let controller = controller.watches(Api::<Network>::all(client.clone()), state.watcher_config(), routers_for_network(routers));
+    // Routers report the problems of their node as soon as they appear
+    let controller = match state.config.watch_nodes {
+        true => {
+            let routers = controller.store();
+            let nodes = node_names(client.clone(), state.watcher_config());
+            controller.watches(Api::<Node>::all(client.clone()), state.watcher_config(), routers_for_node(routers, nodes))
+        }
+        false => controller,
+    };
+    // Routers follow the external topology of their Network
+    let controller = match state.config.watch_config_maps {
+        true => {
+            let routers = controller.store();
+            let networks = networks(client.clone(), state.watcher_config());
+            let config_maps = metadata_watcher(Api::<ConfigMap>::all(client.clone()), state.watcher_config()).default_backoff().touched_objects();
+            controller.watches_stream(config_maps, routers_for_config_map(routers, networks))
+        }
+        false => controller,
+    };
+    let ctx = state.to_network_scoped_context(client.clone()).await;
+    ctx.clone().spawn_failure_pruning(controller.store());
+    controller
+        .graceful_shutdown_on(state.stop_signal())
         .shutdown_on_signal()
-        .run(reconcile_router, router_error_policy, state.to_context(client.clone()).await)
+        .run(reconcile_router, router_error_policy, ctx)
         .filter_map(async |x| { std::result::Result::ok(x) })
         .for_each(async |_| ()).await;
 }
@@ -169,9 +924,352 @@ pub async fn run_router(state: State) {
 pub async fn run_pod_sync(state: State) {
     let client = Client::try_default().await.expect("Expected a valid KUBECONFIG environment variable");
     let api_pod = Api::<Pod>::all(client.clone());
-    Controller::new(api_pod, watcher::Config::default().labels_from(&Expression::Exists(DS_LABEL_KEY.into()).into()))
+    let controller = Controller::new(api_pod, state.watcher_config().labels_from(&Expression::Exists(DS_LABEL_KEY.into()).into()))
+        .with_config(state.controller_config());
+    state.track_cache_sync("Pod", controller.store());
+    controller
+        .graceful_shutdown_on(state.stop_signal())
         .shutdown_on_signal()
-        .run(reconcile_pod, pod_error_policy, state.to_context(client.clone()).await)
+        .run(reconcile_pod, pod_error_policy, state.to_network_scoped_context(client.clone()).await)
         .filter_map(async |x| { std::result::Result::ok(x) })
         .for_each(async |_| ()).await;
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{ExternalTopology, NetworkSpec, TEMPLATE_ANNOTATION_KEY};
+    use kube::api::ObjectMeta;
+
+    fn node(name: &str, labels: &[(&str, &str)]) -> Node {
+        Node {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some(labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+                ..ObjectMeta::default()
+            },
+            ..Node::default()
+        }
+    }
+
+    fn network(name: &str, selector: &[(&str, &str)]) -> Network {
+        let mut network = Network::new(name, NetworkSpec {
+            node_selector: Some(selector.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+            ..NetworkSpec::default()
+        });
+        network.metadata.namespace = Some("default".to_string());
+        network
+    }
+
+    fn store<K>(objects: Vec<K>) -> Store<K>
+    where
+        K: Resource + Clone + 'static,
+        K::DynamicType: Default + Eq + std::hash::Hash + Clone,
+    {
+        let (reader, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Init);
+        for object in objects {
+            writer.apply_watcher_event(&watcher::Event::InitApply(object));
+        }
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        reader
+    }
+
+    fn node_names(names: &[&str]) -> Store<PartialObjectMeta<Node>> {
+        store(names.iter().map(|name| PartialObjectMeta::<Node> {
+            metadata: ObjectMeta { name: Some(name.to_string()), ..ObjectMeta::default() },
+            ..PartialObjectMeta::default()
+        }).collect())
+    }
+
+    #[test]
+    fn matching_node_reconciles_network() {
+        let networks = store(vec![network("edge", &[("pool", "edge")]), network("core", &[("pool", "core")])]);
+        let mapper = networks_for_node(networks, node_names(&["node-1"]));
+        let refs = mapper(node("node-1", &[("pool", "edge")]));
+        assert_eq!(refs, vec![ObjectRef::new("edge").within("default")]);
+        // Heartbeats without label changes are ignored
+        assert!(mapper(node("node-1", &[("pool", "edge")])).is_empty());
+    }
+
+    #[test]
+    fn relabeled_node_reconciles_previous_network() {
+        let networks = store(vec![network("edge", &[("pool", "edge")]), network("core", &[("pool", "core")])]);
+        let mapper = networks_for_node(networks, node_names(&["node-1"]));
+        mapper(node("node-1", &[("pool", "edge")]));
+        let mut refs = mapper(node("node-1", &[("pool", "core")]));
+        refs.sort_by_key(|object| object.name.clone());
+        assert_eq!(refs, vec![ObjectRef::new("core").within("default"), ObjectRef::new("edge").within("default")]);
+    }
+
+    fn router(name: &str, network: &str) -> Router {
+        let mut router = Router::new(name, Default::default());
+        router.metadata.namespace = Some("default".to_string());
+        router.metadata.labels = Some(BTreeMap::from([(NETWORK_LABEL_KEY.to_string(), network.to_string())]));
+        router
+    }
+
+    #[test]
+    fn network_changes_reconcile_its_routers() {
+        let routers = store(vec![router("a", "edge"), router("b", "edge"), router("c", "core")]);
+        let mapper = routers_for_network(routers);
+        let mut edge = network("edge", &[]);
+        edge.metadata.generation = Some(1);
+        let mut refs = mapper(edge.clone());
+        refs.sort_by_key(|object| object.name.clone());
+        assert_eq!(refs, vec![ObjectRef::new("a").within("default"), ObjectRef::new("b").within("default")]);
+        // Status updates of the Network are ignored
+        assert!(mapper(edge.clone()).is_empty());
+        edge.metadata.generation = Some(2);
+        assert_eq!(mapper(edge).len(), 2);
+    }
+
+    #[test]
+    fn topology_config_map_reconciles_routers() {
+        let mut edge = network("edge", &[]);
+        edge.spec.external_topology = Some(ExternalTopology { config_map: "edges".to_string(), key: None });
+        // Derived from the edge Network, with its topology
+        let mut derived = network("derived", &[]);
+        derived.metadata.annotations = Some(BTreeMap::from([(TEMPLATE_ANNOTATION_KEY.to_string(), "edge".to_string())]));
+        let routers = store(vec![router("a", "edge"), router("b", "derived"), router("c", "core")]);
+        let mapper = routers_for_config_map(routers, store(vec![edge, derived, network("core", &[])]));
+        let config_map = PartialObjectMeta::<ConfigMap> {
+            metadata: ObjectMeta { name: Some("edges".to_string()), namespace: Some("default".to_string()), ..ObjectMeta::default() },
+            ..PartialObjectMeta::default()
+        };
+        let mut refs = mapper(config_map);
+        refs.sort_by_key(|object| object.name.clone());
+        assert_eq!(refs, vec![ObjectRef::new("a").within("default"), ObjectRef::new("b").within("default")]);
+    }
+
+    #[test]
+    fn removed_nodes_are_forgotten() {
+        let memory = NodeMemory::new(node_names(&["node-1"]));
+        memory.remember("node-2", ());
+        memory.remember("node-1", ());
+        assert_eq!(memory.seen.lock().unwrap().keys().collect::<Vec<_>>(), vec!["node-1"]);
+    }
+
+    /// Client of a fake API server counting the requests, and answering with the posted object
+    fn counting_client(requests: Arc<std::sync::atomic::AtomicUsize>) -> Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let requests = requests.clone();
+            async move {
+                requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let response = http::Response::builder()
+                    .status(201)
+                    .body(kube::client::Body::from(body.to_vec()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        Client::new(service, "default")
+    }
+
+    /// Client of a fake API server keeping the posted objects, and answering with them
+    fn recording_client(posted: Arc<std::sync::Mutex<Vec<serde_json::Value>>>) -> Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let posted = posted.clone();
+            async move {
+                let body = request.into_body().collect_bytes().await.unwrap();
+                posted.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                let response = http::Response::builder()
+                    .status(201)
+                    .body(kube::client::Body::from(body.to_vec()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        Client::new(service, "default")
+    }
+
+    fn event(note: &str) -> Event {
+        Event {
+            type_: EventType::Warning,
+            reason: "PrefixOverlap".into(),
+            note: Some(note.to_string()),
+            action: "Validating".into(),
+            secondary: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn changed_events_are_published_once() {
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ctx = State::default().to_context(counting_client(requests.clone())).await;
+        let reference = network("edge", &[]).object_ref(&());
+        let published = || requests.load(std::sync::atomic::Ordering::SeqCst);
+        ctx.publish_changed_events(&reference, "PrefixOverlap", vec![event("a")], None).await.unwrap();
+        assert_eq!(published(), 1);
+        // Found again on the next reconcile
+        ctx.publish_changed_events(&reference, "PrefixOverlap", vec![event("a")], None).await.unwrap();
+        assert_eq!(published(), 1);
+        ctx.publish_changed_events(&reference, "PrefixOverlap", vec![event("a"), event("b")], None).await.unwrap();
+        assert_eq!(published(), 2);
+        // Resolved, then back
+        ctx.publish_changed_events(&reference, "PrefixOverlap", vec![], None).await.unwrap();
+        ctx.publish_changed_events(&reference, "PrefixOverlap", vec![event("a")], None).await.unwrap();
+        assert_eq!(published(), 3);
+        ctx.forget_events(&reference);
+        assert!(ctx.published.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn events_are_reported_from_the_cluster() {
+        let posted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let state = State { config: Config { cluster_name: Some("east".to_string()), ..Config::default() }, ..State::default() };
+        let ctx = state.to_context(recording_client(posted.clone())).await;
+        ctx.publish_event(event("a"), &network("edge", &[]).object_ref(&()), None).await.unwrap();
+        let posted = posted.lock().unwrap();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0]["reportingInstance"], "east");
+    }
+
+    #[tokio::test]
+    async fn configured_event_types_override_the_default() {
+        let posted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ctx = State::default().to_context(recording_client(posted.clone())).await;
+        let reference = network("edge", &[]).object_ref(&());
+        let event_types = BTreeMap::from([("PrefixOverlap".to_string(), EventSeverity::Normal)]);
+        ctx.publish_event(event("a"), &reference, Some(&event_types)).await.unwrap();
+        // Other reasons keep the type set by the reconciler
+        let other = BTreeMap::from([("NoMatchingNodes".to_string(), EventSeverity::Normal)]);
+        ctx.publish_event(event("b"), &reference, Some(&other)).await.unwrap();
+        let types: Vec<serde_json::Value> = posted.lock().unwrap().iter().map(|event| event["type"].clone()).collect();
+        assert_eq!(types, ["Normal", "Warning"]);
+    }
+
+    #[tokio::test]
+    async fn disabled_events_are_only_logged() {
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let state = State { config: Config { disable_events: true, ..Config::default() }, ..State::default() };
+        let ctx = state.to_context(counting_client(requests.clone())).await;
+        ctx.publish_event(event("a"), &network("edge", &[]).object_ref(&()), None).await.unwrap();
+        ctx.publish_changed_events(&network("edge", &[]).object_ref(&()), "PrefixOverlap", vec![event("b")], None).await.unwrap();
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn objects_are_reconciled_concurrently() {
+        // The API server answers the events only once both are posted: publishing for one
+        // Network must not wait on the other, no lock of the context is held meanwhile
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let barrier = barrier.clone();
+            async move {
+                let body = request.into_body().collect_bytes().await.unwrap();
+                barrier.wait().await;
+                Ok::<_, std::convert::Infallible>(http::Response::builder().status(201).body(kube::client::Body::from(body.to_vec())).unwrap())
+            }
+        });
+        let ctx = State::default().to_context(Client::new(service, "default")).await;
+        let (a, b) = (network("a", &[]).object_ref(&()), network("b", &[]).object_ref(&()));
+        let publish = futures::future::try_join(
+            ctx.publish_changed_events(&a, "PrefixOverlap", vec![event("a")], None),
+            ctx.publish_changed_events(&b, "PrefixOverlap", vec![event("b")], None),
+        );
+        tokio::time::timeout(Duration::from_secs(5), publish).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn network_selector_partitions_the_objects_of_networks() {
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let state = State { network_selector: Some(parse_label_selector("team=a").unwrap()), ..State::default() };
+        let mut ctx = (*state.to_context(counting_client(requests)).await).clone();
+        let mut managed = network("mine", &[]);
+        managed.metadata.labels = Some(BTreeMap::from([("team".to_string(), "a".to_string())]));
+        ctx.networks = Some(store(vec![managed, network("theirs", &[])]));
+        assert!(ctx.manages_network("default", "mine", false));
+        // Other instances' objects are skipped, even being deleted
+        assert!(!ctx.manages_network("default", "theirs", false));
+        assert!(!ctx.manages_network("default", "theirs", true));
+        // Objects of a deleted Network are only cleaned up
+        assert!(!ctx.manages_network("default", "gone", false));
+        assert!(ctx.manages_network("default", "gone", true));
+    }
+
+    fn api_error(code: u16) -> Error {
+        Error::KubeError(kube::Error::Api(kube::core::ErrorResponse {
+            status: "Failure".to_string(),
+            message: "failed".to_string(),
+            reason: "Failed".to_string(),
+            code,
+        }))
+    }
+
+    #[tokio::test]
+    async fn objects_failing_with_permanent_errors_end_up_stalled() {
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut ctx = (*State::default().to_context(counting_client(requests)).await).clone();
+        ctx.max_retries = 3;
+        let ctx = Arc::new(ctx);
+        let mut network = network("net", &[]);
+        network.metadata.generation = Some(1);
+        let stalls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let fail = |error: Error, network: &Network| {
+            let stalls = stalls.clone();
+            ctx.clone().retry_or_stall(network, &error, RETRY_PERIOD, move |_, _, _| {
+                stalls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async { Ok(()) }
+            })
+        };
+        // An API server outage is retried forever
+        for _ in 0..10 {
+            assert_eq!(fail(api_error(503), &network), Action::requeue(RETRY_PERIOD));
+        }
+        assert!(!ctx.is_stalled(&network));
+        // A spec that keeps failing is stalled once, on the last retry
+        assert_eq!(fail(Error::InvalidSpec("bad".to_string()), &network), Action::requeue(RETRY_PERIOD));
+        assert_eq!(fail(api_error(422), &network), Action::requeue(RETRY_PERIOD));
+        let finalizer_error = Error::FinalizerError(Box::new(finalizer::Error::ApplyFailed(Error::InvalidSpec("bad".to_string()))));
+        assert_eq!(fail(finalizer_error, &network), Action::await_change());
+        assert_eq!(fail(Error::InvalidSpec("bad".to_string()), &network), Action::await_change());
+        assert_eq!(stalls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(ctx.is_stalled(&network));
+        // Until its spec changes
+        network.metadata.generation = Some(2);
+        assert!(!ctx.is_stalled(&network));
+    }
+
+    #[tokio::test]
+    async fn failures_of_deleted_objects_are_pruned() {
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ctx = State::default().to_context(counting_client(requests)).await;
+        let (kept, deleted) = (network("kept", &[]), network("deleted", &[]));
+        ctx.record_failure(&kept);
+        ctx.record_failure(&deleted);
+        ctx.prune_failures(&store(vec![kept.clone()]));
+        let failures = ctx.failures.lock().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(failures, vec![failure_key(&kept)]);
+    }
+
+    #[tokio::test]
+    async fn readiness_waits_for_every_cache() {
+        let state = State::default();
+        assert_eq!(state.pending_caches().await, CONTROLLER_CACHES);
+        let (reader, mut writer) = reflector::store::<Router>();
+        state.track_cache_sync("Router", reader);
+        // Listed by the time the initial list completes, not when it starts
+        writer.apply_watcher_event(&watcher::Event::Init);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(state.pending_caches().await.contains(&"Router"));
+        writer.apply_watcher_event(&watcher::Event::InitDone);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state.pending_caches().await.contains(&"Router") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(state.pending_caches().await, ["Network", "Pod"]);
+        state.track_cache_sync("Network", store::<Network>(Vec::new()));
+        state.track_cache_sync("Pod", store::<Pod>(Vec::new()));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !state.pending_caches().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{Error, Result};
+use chrono::{DateTime, Datelike, Days, NaiveTime, TimeDelta, Utc, Weekday};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+/// Longest maintenance window, a week
+static MAX_WINDOW_MINUTES: u32 = 7 * 24 * 60;
+
+/// Recurring time range in which the operator may roll the router pods, in UTC.
+/// The window opens at `start` on each of `days` and lasts `durationMinutes`,
+/// possibly past midnight
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceWindow {
+    /// Days the window opens, like `Sat` or `Sunday`, every day if unset
+    pub days: Option<Vec<String>>,
+    /// Time of day the window opens, `HH:MM` in UTC
+    pub start: String,
+    /// Length of the window in minutes, at most a week
+    pub duration_minutes: u32,
+}
+
+impl MaintenanceWindow {
+    pub fn validate(&self) -> Result<()> {
+        self.start_time()?;
+        self.weekdays()?;
+        if self.duration_minutes == 0 || self.duration_minutes > MAX_WINDOW_MINUTES {
+            return Err(Error::InvalidSpec(format!(
+                "maintenance window duration must be between 1 and {MAX_WINDOW_MINUTES} minutes"
+            )));
+        }
+        Ok(())
+    }
+
+    fn start_time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.start, "%H:%M")
+            .map_err(|_| Error::InvalidSpec(format!("maintenance window start `{}` must be HH:MM", self.start)))
+    }
+
+    /// Days the window opens, all days if unset
+    fn weekdays(&self) -> Result<Vec<Weekday>> {
+        match &self.days {
+            None => Ok(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun]),
+            Some(days) => days
+                .iter()
+                .map(|day| day.parse::<Weekday>().map_err(|_| Error::InvalidSpec(format!("`{day}` is not a day of the week"))))
+                .collect(),
+        }
+    }
+
+    /// Openings of the window from the day `from` is in, `days` days on
+    fn openings(&self, from: DateTime<Utc>, days: u64) -> Result<Vec<DateTime<Utc>>> {
+        let start = self.start_time()?;
+        let weekdays = self.weekdays()?;
+        Ok((0..=days)
+            .filter_map(|day| from.date_naive().checked_add_days(Days::new(day)))
+            .filter(|date| weekdays.contains(&date.weekday()))
+            .map(|date| date.and_time(start).and_utc())
+            .collect())
+    }
+
+    /// Whether the window is open at the time
+    pub fn is_open(&self, now: DateTime<Utc>) -> Result<bool> {
+        let duration = TimeDelta::minutes(self.duration_minutes as i64);
+        // A window opened up to a week ago may still be open
+        let week_ago = now - TimeDelta::days(7);
+        Ok(self.openings(week_ago, 8)?.into_iter().any(|opening| opening <= now && now < opening + duration))
+    }
+
+    /// Next time the window opens after the time
+    pub fn next_start(&self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.openings(now, 8)?.into_iter().find(|opening| *opening > now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(days: Option<&[&str]>, start: &str, duration_minutes: u32) -> MaintenanceWindow {
+        MaintenanceWindow {
+            days: days.map(|days| days.iter().map(|day| day.to_string()).collect()),
+            start: start.to_string(),
+            duration_minutes,
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn window_is_open_from_its_start_for_its_duration() {
+        // 2025-01-04 is a Saturday
+        let window = window(Some(&["Sat"]), "02:00", 60);
+        assert!(!window.is_open(at("2025-01-04T01:59:00Z")).unwrap());
+        assert!(window.is_open(at("2025-01-04T02:00:00Z")).unwrap());
+        assert!(window.is_open(at("2025-01-04T02:59:00Z")).unwrap());
+        assert!(!window.is_open(at("2025-01-04T03:00:00Z")).unwrap());
+        assert!(!window.is_open(at("2025-01-05T02:30:00Z")).unwrap());
+    }
+
+    #[test]
+    fn window_stays_open_past_midnight() {
+        let window = window(Some(&["Saturday"]), "23:00", 180);
+        assert!(window.is_open(at("2025-01-05T01:30:00Z")).unwrap());
+        assert!(!window.is_open(at("2025-01-05T02:00:00Z")).unwrap());
+    }
+
+    #[test]
+    fn next_start_is_the_next_opening() {
+        let weekly = window(Some(&["Sat"]), "02:00", 60);
+        assert_eq!(weekly.next_start(at("2025-01-04T02:30:00Z")).unwrap(), Some(at("2025-01-11T02:00:00Z")));
+        assert_eq!(weekly.next_start(at("2025-01-01T00:00:00Z")).unwrap(), Some(at("2025-01-04T02:00:00Z")));
+        let daily = window(None, "02:00", 60);
+        assert_eq!(daily.next_start(at("2025-01-04T02:30:00Z")).unwrap(), Some(at("2025-01-05T02:00:00Z")));
+    }
+
+    #[test]
+    fn invalid_windows_are_rejected() {
+        assert!(window(None, "02:00", 60).validate().is_ok());
+        assert!(window(None, "2am", 60).validate().is_err());
+        assert!(window(Some(&["Caturday"]), "02:00", 60).validate().is_err());
+        assert!(window(None, "02:00", 0).validate().is_err());
+        assert!(window(None, "02:00", MAX_WINDOW_MINUTES + 1).validate().is_err());
+    }
+}
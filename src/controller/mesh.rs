@@ -0,0 +1,409 @@
+use super::{fnv1a, fnv1a_extend, is_dns_label, Router, RouterRole, RouterRoles};
+use crate::{Error, Result};
+use kube::ResourceExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Routers listed in the topology of a Network status, bounding the size of the Network
+pub static MAX_TOPOLOGY_ROUTERS: usize = 256;
+/// Neighbor entries listed in the topology of a Network status, over all its routers
+pub static MAX_TOPOLOGY_LINKS: usize = 4096;
+
+/// Mesh of the routers of a namespace, linking two routers when one has a face of the other
+/// among its neighbors. Links are undirected as ndnd faces carry traffic both ways
+#[derive(Clone, Debug, Default)]
+pub struct MeshGraph {
+    adjacency: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl MeshGraph {
+    pub fn new(routers: &[Router]) -> Self {
+        let owners: BTreeMap<String, String> = routers
+            .iter()
+            .flat_map(|router| {
+                let faces = router.status.as_ref().map(|status| status.faces.to_btree_set()).unwrap_or_default();
+                faces.into_iter().map(move |face| (face, router.name_any()))
+            })
+            .collect();
+        let mut adjacency: BTreeMap<String, BTreeSet<String>> =
+            routers.iter().map(|router| (router.name_any(), BTreeSet::new())).collect();
+        for router in routers {
+            let neighbors = router.status.iter().flat_map(|status| status.neighbors.iter());
+            for neighbor in neighbors.filter_map(|face| owners.get(face)) {
+                if *neighbor == router.name_any() {
+                    continue;
+                }
+                adjacency.entry(router.name_any()).or_default().insert(neighbor.clone());
+                adjacency.entry(neighbor.clone()).or_default().insert(router.name_any());
+            }
+        }
+        Self { adjacency }
+    }
+
+    /// Routers on a path with the fewest hops between two routers, both included,
+    /// or `None` if they are in disconnected parts of the mesh
+    pub fn shortest_path(&self, from: &str, to: &str) -> Result<Option<Vec<String>>> {
+        for router in [from, to] {
+            if !self.adjacency.contains_key(router) {
+                return Err(Error::OtherError(format!("router `{router}` is not in the mesh")));
+            }
+        }
+        let mut previous = BTreeMap::<&str, &str>::new();
+        let mut queue = VecDeque::from([from]);
+        let mut visited = BTreeSet::from([from]);
+        while let Some(router) = queue.pop_front() {
+            if router == to {
+                let mut path = vec![to.to_string()];
+                let mut hop = to;
+                while let Some(&prev) = previous.get(hop) {
+                    path.push(prev.to_string());
+                    hop = prev;
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            for neighbor in self.adjacency[router].iter() {
+                if visited.insert(neighbor) {
+                    previous.insert(neighbor, router);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sets of routers connected to each other, largest first
+    pub fn components(&self) -> Vec<BTreeSet<String>> {
+        let mut components = Vec::<BTreeSet<String>>::new();
+        for router in self.adjacency.keys() {
+            if components.iter().any(|component| component.contains(router)) {
+                continue;
+            }
+            let mut component = BTreeSet::from([router.clone()]);
+            let mut queue = VecDeque::from([router]);
+            while let Some(router) = queue.pop_front() {
+                for neighbor in self.adjacency[router].iter() {
+                    if component.insert(neighbor.clone()) {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+        components
+    }
+}
+
+/// Adjacency list of the mesh of a Network, published in its status for other controllers
+/// and `kubectl`. Routers are listed by name up to `MAX_TOPOLOGY_ROUTERS`, and their
+/// neighbors up to `MAX_TOPOLOGY_LINKS` in total
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MeshTopology {
+    pub routers: Vec<TopologyRouter>,
+    /// Whether routers or neighbors were left out to bound the size
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TopologyRouter {
+    pub name: String,
+    pub online: bool,
+    /// Routers linked to this one, by name
+    pub neighbors: Vec<String>,
+}
+
+impl MeshTopology {
+    pub fn new(routers: &[Router]) -> Self {
+        let graph = MeshGraph::new(routers);
+        let online: BTreeMap<String, bool> = routers
+            .iter()
+            .map(|router| (router.name_any(), router.status.as_ref().is_some_and(|status| status.online)))
+            .collect();
+        let listed: BTreeSet<&String> = online.keys().take(MAX_TOPOLOGY_ROUTERS).collect();
+        let mut truncated = listed.len() < online.len();
+        let mut links = 0;
+        let routers = listed
+            .iter()
+            .map(|name| {
+                let mut neighbors: Vec<String> =
+                    graph.adjacency[*name].iter().filter(|neighbor| listed.contains(neighbor)).cloned().collect();
+                if links + neighbors.len() > MAX_TOPOLOGY_LINKS {
+                    neighbors.truncate(MAX_TOPOLOGY_LINKS - links);
+                    truncated = true;
+                }
+                links += neighbors.len();
+                TopologyRouter {
+                    name: name.to_string(),
+                    online: online[*name],
+                    neighbors,
+                }
+            })
+            .collect();
+        Self { routers, truncated }
+    }
+}
+
+/// Bound on the neighbors of each router, so large meshes form a sparse graph rather than
+/// a full mesh. The links are chosen deterministically from the router names
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FanOut {
+    /// Routers each router links to at most, at least 2 so the routers can form a connected graph
+    pub max_neighbors: u32,
+    /// Order the links are picked in once the backbone is formed, `SeededByName` by default
+    pub tie_break: Option<TieBreak>,
+}
+
+/// Order routers and links are picked in by a `FanOut`
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum TieBreak {
+    /// Pseudo-random order seeded by the router names, spreading the links over the mesh
+    #[default]
+    SeededByName,
+    /// Alphabetical order of the router names
+    LowestNameFirst,
+}
+
+impl FanOut {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_neighbors < 2 {
+            return Err(Error::InvalidSpec("fan-out max neighbors must be at least 2".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn tie_break(&self) -> TieBreak {
+        self.tie_break.unwrap_or_default()
+    }
+
+    /// Routers each online router links to, by name. A backbone first attaches every router,
+    /// in tie-break order, to the first router before it with a free slot, which keeps the graph
+    /// connected as long as the roles allow it. The remaining slots are then filled pair by pair.
+    /// Routers on degraded nodes come last, so they hang off the backbone instead of carrying it.
+    /// Names are hashed once per router, the routers are then handled by index
+    pub fn links(&self, routers: &[Router], roles: &RouterRoles) -> BTreeMap<String, BTreeSet<String>> {
+        let max = self.max_neighbors as usize;
+        let tie_break = self.tie_break();
+        let mut online: Vec<(bool, u32, String, RouterRole)> = routers
+            .iter()
+            .filter(|router| router.status.as_ref().is_some_and(|status| status.online))
+            .map(|router| {
+                let key = match tie_break {
+                    TieBreak::SeededByName => fnv1a(&router.name_any()),
+                    TieBreak::LowestNameFirst => 0,
+                };
+                (router.is_node_degraded(), key, router.name_any(), roles.of(router))
+            })
+            .collect();
+        online.sort_by(|x, y| (x.0, x.1, &x.2).cmp(&(y.0, y.1, &y.2)));
+        let mut adjacency: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); online.len()];
+        let link = |adjacency: &mut Vec<BTreeSet<usize>>, a: usize, b: usize| {
+            if adjacency[a].len() >= max || adjacency[b].len() >= max || adjacency[a].contains(&b) {
+                return;
+            }
+            adjacency[a].insert(b);
+            adjacency[b].insert(a);
+        };
+        for i in 0..online.len() {
+            let parent = (0..i).find(|&parent| online[parent].3.links_to(online[i].3) && adjacency[parent].len() < max);
+            if let Some(parent) = parent {
+                link(&mut adjacency, parent, i);
+            }
+        }
+        // Pairs are ordered by the hash of `a/b`, continued from the hash of `a/`
+        let prefixes: Vec<u32> = online.iter().map(|(_, _, name, _)| fnv1a_extend(fnv1a(name), "/")).collect();
+        let mut pairs: Vec<(u32, usize, usize)> = (0..online.len())
+            .flat_map(|a| (a + 1..online.len()).map(move |b| (a, b)))
+            .filter(|&(a, b)| online[a].3.links_to(online[b].3))
+            .map(|(a, b)| match tie_break {
+                TieBreak::SeededByName => (fnv1a_extend(prefixes[a], &online[b].2), a, b),
+                TieBreak::LowestNameFirst => (0, a, b),
+            })
+            .collect();
+        pairs.sort_by(|x, y| (x.0, &online[x.1].2, &online[x.2].2).cmp(&(y.0, &online[y.1].2, &online[y.2].2)));
+        for (_, a, b) in pairs {
+            link(&mut adjacency, a, b);
+        }
+        adjacency
+            .into_iter()
+            .enumerate()
+            .map(|(i, linked)| (online[i].2.clone(), linked.into_iter().map(|j| online[j].2.clone()).collect()))
+            .collect()
+    }
+}
+
+/// Links of an externally planned mesh, from one `router-a router-b` edge per line.
+/// Blank lines and `#` comments are skipped. Like the links of `MeshGraph`, edges are undirected
+pub fn parse_edges(text: &str) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut links = BTreeMap::<String, BTreeSet<String>>::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| Error::InvalidSpec(format!("topology edge on line {} `{line}` {reason}", number + 1));
+        let [a, b] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(invalid("must name two routers"));
+        };
+        if a == b {
+            return Err(invalid("links a router to itself"));
+        }
+        if !is_dns_label(a) || !is_dns_label(b) {
+            return Err(invalid("names an invalid router"));
+        }
+        links.entry(a.to_string()).or_default().insert(b.to_string());
+        links.entry(b.to_string()).or_default().insert(a.to_string());
+    }
+    Ok(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{RouterFaces, RouterSpec, RouterStatus};
+
+    fn face(name: &str) -> String {
+        format!("udp://{name}.test:6363")
+    }
+
+    fn router(name: &str, online: bool, neighbors: &[&str]) -> Router {
+        let mut router = Router::new(name, RouterSpec::default());
+        router.status = Some(RouterStatus {
+            online,
+            faces: RouterFaces { udp4: Some(face(name)), ..RouterFaces::default() },
+            neighbors: neighbors.iter().map(|name| face(name)).collect(),
+            ..RouterStatus::default()
+        });
+        router
+    }
+
+    #[test]
+    fn topology_lists_undirected_links_by_name() {
+        // `b` links `a` only one way, `c` has an unknown neighbor
+        let routers = vec![router("a", true, &[]), router("b", true, &["a"]), router("c", false, &["b", "gone"])];
+        let topology = MeshTopology::new(&routers);
+        assert!(!topology.truncated);
+        let neighbors: Vec<(&str, bool, Vec<&str>)> = topology
+            .routers
+            .iter()
+            .map(|router| (router.name.as_str(), router.online, router.neighbors.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(neighbors, vec![("a", true, vec!["b"]), ("b", true, vec!["a", "c"]), ("c", false, vec!["b"])]);
+    }
+
+    #[test]
+    fn shortest_paths_follow_the_fewest_hops() {
+        // a - b - c - d, with a shortcut b - d, and e on its own
+        let routers = vec![
+            router("a", true, &["b"]),
+            router("b", true, &["c", "d"]),
+            router("c", true, &["d"]),
+            router("d", true, &[]),
+            router("e", true, &[]),
+        ];
+        let mesh = MeshGraph::new(&routers);
+        assert_eq!(mesh.shortest_path("a", "d").unwrap(), Some(vec!["a".to_string(), "b".to_string(), "d".to_string()]));
+        // Links are undirected
+        assert_eq!(mesh.shortest_path("d", "a").unwrap(), Some(vec!["d".to_string(), "b".to_string(), "a".to_string()]));
+        assert_eq!(mesh.shortest_path("a", "a").unwrap(), Some(vec!["a".to_string()]));
+        assert_eq!(mesh.shortest_path("a", "e").unwrap(), None);
+        assert!(mesh.shortest_path("a", "unknown").is_err());
+    }
+
+    #[test]
+    fn topology_is_truncated_to_the_listed_routers() {
+        let names: Vec<String> = (0..MAX_TOPOLOGY_ROUTERS + 1).map(|i| format!("r{i:03}")).collect();
+        // Every router links the last one, which is left out
+        let last = names.last().unwrap().as_str();
+        let routers: Vec<Router> = names.iter().map(|name| router(name, true, &[last])).collect();
+        let topology = MeshTopology::new(&routers);
+        assert!(topology.truncated);
+        assert_eq!(topology.routers.len(), MAX_TOPOLOGY_ROUTERS);
+        assert!(topology.routers.iter().all(|router| router.neighbors.is_empty()));
+    }
+
+    #[test]
+    fn topology_bounds_the_neighbor_entries() {
+        let names: Vec<String> = (0..70).map(|i| format!("r{i:02}")).collect();
+        let all: Vec<&str> = names.iter().map(String::as_str).collect();
+        let routers: Vec<Router> = names.iter().map(|name| router(name, true, &all)).collect();
+        let topology = MeshTopology::new(&routers);
+        assert!(topology.truncated);
+        assert_eq!(topology.routers.len(), 70);
+        assert_eq!(topology.routers.iter().map(|router| router.neighbors.len()).sum::<usize>(), MAX_TOPOLOGY_LINKS);
+    }
+
+    fn connected(links: &BTreeMap<String, BTreeSet<String>>) -> bool {
+        let Some(first) = links.keys().next() else {
+            return true;
+        };
+        let mut reached = BTreeSet::from([first]);
+        let mut queue = VecDeque::from([first]);
+        while let Some(router) = queue.pop_front() {
+            for neighbor in &links[router] {
+                if reached.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        reached.len() == links.len()
+    }
+
+    #[test]
+    fn fan_out_bounds_the_degree_and_stays_connected() {
+        let names: Vec<String> = (0..200).map(|i| format!("r{i:03}")).collect();
+        let routers: Vec<Router> = names.iter().map(|name| router(name, true, &[])).collect();
+        for tie_break in [TieBreak::SeededByName, TieBreak::LowestNameFirst] {
+            for max_neighbors in [2, 3, 8] {
+                let links = FanOut { max_neighbors, tie_break: Some(tie_break) }.links(&routers, &RouterRoles::default());
+                assert_eq!(links.len(), 200);
+                assert!(links.values().all(|linked| linked.len() <= max_neighbors as usize));
+                assert!(links.iter().all(|(router, linked)| linked.iter().all(|other| links[other].contains(router))));
+                assert!(connected(&links), "{tie_break:?} {max_neighbors}");
+            }
+        }
+    }
+
+    #[test]
+    fn fan_out_links_online_routers_allowed_by_their_roles() {
+        let routers = vec![router("hub", true, &[]), router("leaf-1", true, &[]), router("leaf-2", true, &[]), router("off", false, &[])];
+        let roles: RouterRoles = [("hub", RouterRole::Hub), ("leaf-1", RouterRole::Leaf), ("leaf-2", RouterRole::Leaf)]
+            .into_iter()
+            .map(|(name, role)| (name.to_string(), role))
+            .collect();
+        let links = FanOut { max_neighbors: 4, tie_break: None }.links(&routers, &roles);
+        assert_eq!(links, BTreeMap::from([
+            ("hub".to_string(), BTreeSet::from(["leaf-1".to_string(), "leaf-2".to_string()])),
+            ("leaf-1".to_string(), BTreeSet::from(["hub".to_string()])),
+            ("leaf-2".to_string(), BTreeSet::from(["hub".to_string()])),
+        ]));
+    }
+
+    #[test]
+    fn fan_out_needs_two_neighbors() {
+        assert!(FanOut { max_neighbors: 1, tie_break: None }.validate().is_err());
+        assert!(FanOut { max_neighbors: 2, tie_break: None }.validate().is_ok());
+    }
+
+    #[test]
+    fn edges_are_undirected_and_skip_comments() {
+        let links = parse_edges("# planned mesh\na b\n\n  b   c # backbone\nc a\n").unwrap();
+        let links: Vec<(&str, Vec<&str>)> = links
+            .iter()
+            .map(|(name, links)| (name.as_str(), links.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(links, vec![("a", vec!["b", "c"]), ("b", vec!["a", "c"]), ("c", vec!["a", "b"])]);
+    }
+
+    #[test]
+    fn invalid_edges_are_rejected_with_their_line() {
+        for text in ["a", "a b c", "a a", "a B_1"] {
+            let error = parse_edges(&format!("x y\n{text}")).unwrap_err().to_string();
+            assert!(error.contains("line 2"), "{text}: {error}");
+        }
+    }
+}
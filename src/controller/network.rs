@@ -1,38 +1,71 @@
-use super::{get_my_pod, Context};
-use crate::{Error, Result};
+use super::{annotate_version, audit, MaintenanceWindow, face_ip, get_my_pod, parse_cidrs, validate_router_name_template, Cidr, DEFAULT_ROUTER_NAME_TEMPLATE, unknown_fields, validate_face_scheme, validate_face_uri, validate_local_endpoint, validate_qualified_name, validate_label_value, validate_http_url, validate_dns_subdomain, is_dns_label, parse_file_mode, merge_json, prefixes_overlap, port_change_updates, parse_quantity, pod_usage, quota_risks, Context, FanOut, MeshGraph, MeshTopology, NodeRoles, Router, RouterRole, RouterRoles, parse_edges};
+use crate::{ConfigFormat, Error, Result};
+use chrono::{DateTime, Utc};
 use k8s_openapi::{
     api::{
         apps::v1::{DaemonSet, DaemonSetSpec},
         core::v1::{
-            Container, ContainerPort, EnvVar, EnvVarSource, HostPathVolumeSource, ObjectFieldSelector, PodSpec, PodTemplateSpec, SecurityContext, ServiceAccount, Volume, VolumeMount
+            Affinity, Container, ConfigMap, ContainerPort, EnvVar, EnvVarSource, HostPathVolumeSource, Lifecycle, LifecycleHandler, LocalObjectReference, Node, NodeAffinity, NodeSelector, NodeSelectorRequirement, NodeSelectorTerm, ObjectFieldSelector, Pod, PodReadinessGate, PodSchedulingGate, PodSecurityContext, PodSpec, PodTemplateSpec, ResourceFieldSelector, ResourceQuota, ResourceRequirements, Secret, SecretKeySelector, SecurityContext, ServiceAccount, SleepAction, Volume, VolumeMount
         }, rbac::v1::{PolicyRule, Role, RoleBinding, RoleRef, Subject},
     },
-    apimachinery::pkg::apis::meta::v1::{LabelSelector, ObjectMeta},
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::{Condition, LabelSelector, ObjectMeta, Time}},
 };
 use kube::{
-    api::{Api, Patch, PatchParams, ResourceExt},
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, ResourceExt},
+    core::Expression,
     runtime::{
         controller::Action,
         events::{Event, EventType},
     },
-    CustomResource, Resource,
+    Client, CustomResource, CustomResourceExt, Resource,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_with::skip_serializing_none;
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::{BTreeMap, BTreeSet}, hash::{DefaultHasher, Hash, Hasher}, sync::Arc, time::Duration};
+use tracing::*;
 
 pub static NETWORK_FINALIZER: &str = "network.named-data.net/finalizer";
 pub static NETWORK_MANAGER_NAME: &str = "network-controller";
 pub static NETWORK_LABEL_KEY: &str = "network.named-data.net/name";
 pub static DS_LABEL_KEY : &str = "network.named-data.net/managed-by";
+/// Name of the init container generating the ndnd config, running the operator image
+pub static INIT_CONTAINER_NAME: &str = "init";
 pub static CONTAINER_CONFIG_DIR: &str = "/etc/ndnd";
 pub static CONTAINER_SOCKET_DIR: &str = "/run/ndnd";
+static DEFAULT_EXTERNAL_TOPOLOGY_KEY: &str = "edges";
+/// Variables of the `extra_env` values
+static ENV_TEMPLATE_VARIABLES: &[&str] = &["network.name", "network.namespace", "network.prefix", "network.udpUnicastPort"];
 // The host directories where the configuration and socket files will be stored
 // Subdirectories are created for each namespace
 pub static HOST_CONFIG_ROOT_DIR: &str = "/etc/ndnd";
 pub static HOST_SOCKET_ROOT_DIR: &str = "/run/ndnd";
+pub static DEFAULT_MAX_POD_RESTARTS: i32 = 5;
+/// Containers whose last termination is older than this no longer count as restarting
+pub static RESTART_WINDOW: Duration = Duration::from_secs(10 * 60);
+pub static DEGRADED_CONDITION: &str = "Degraded";
+pub static MESH_PARTITIONED_CONDITION: &str = "MeshPartitioned";
+/// Condition of the Networks and Routers whose reconcile failed too many times, see `Config::max_reconcile_retries`
+pub static STALLED_CONDITION: &str = "Stalled";
+/// Pod condition of the mesh readiness gate, set by the router sidecar
+pub static MESH_CONVERGED_CONDITION: &str = "named-data.net/mesh-converged";
+pub static NODE_NETWORK_LABEL_PREFIX: &str = "named-data.net/network-";
+pub static ARCH_LABEL_KEY: &str = "network.named-data.net/arch";
+static SAFE_TO_EVICT_ANNOTATION_KEY: &str = "cluster-autoscaler.kubernetes.io/safe-to-evict";
+// Default termination grace period of pods, left to the router after its withdrawal
+static TERMINATION_GRACE_SECONDS: i64 = 30;
+/// Longest keep-alive interval of a Network, in seconds
+static MAX_KEEP_ALIVE_INTERVAL_SECONDS: u64 = 60;
+/// Keep-alive intervals a neighbor may miss before ndnd considers it dead
+pub static KEEP_ALIVES_BEFORE_DEAD: u64 = 6;
+static PUBLISHED_DAEMONSETS_SUFFIX: &str = "daemonsets";
+pub static PUBLISHED_DAEMONSETS_KEY: &str = "daemonsets.yaml";
+pub static TEMPLATE_ANNOTATION_KEY: &str = "network.named-data.net/template";
+pub static TEMPLATE_HASH_ANNOTATION_KEY: &str = "network.named-data.net/template-hash";
+pub static CONFIG_VERSION_ANNOTATION_KEY: &str = "network.named-data.net/config-version";
+static NODE_ARCH_LABEL_KEY: &str = "kubernetes.io/arch";
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -43,6 +76,347 @@ pub struct NetworkSpec {
     pub udp_unicast_port: i32,
     pub node_selector: Option<BTreeMap<String, String>>,
     pub ndnd: Option<Ndnd>,
+    /// Format of the generated ndnd config file, `yaml` (default) or `json`
+    pub config_format: Option<ConfigFormat>,
+    /// Secrets in the Network namespace used to pull the ndnd and operator images
+    pub image_pull_secrets: Option<Vec<LocalObjectReference>>,
+    /// Static faces every router of the Network establishes, in addition to its neighbors
+    pub faces: Option<Vec<FaceSpec>>,
+    /// URI schemes of the faces each router advertises to its neighbors
+    pub face_schemes: Option<FaceSchemes>,
+    /// Scheduling gates of the router pods, holding them until removed by an external controller
+    pub scheduling_gates: Option<Vec<PodSchedulingGate>>,
+    /// Type of the events published for the Network, by event reason,
+    /// e.g. `MissingPullSecret: Normal` to stop alerting on it
+    pub event_types: Option<BTreeMap<String, EventSeverity>>,
+    /// If set, routers only form faces to addresses in these CIDRs
+    pub face_allow_cidrs: Option<Vec<String>>,
+    /// Routers never form faces to addresses in these CIDRs
+    pub face_deny_cidrs: Option<Vec<String>>,
+    /// Node addresses in these CIDRs are never advertised as faces of the routers.
+    /// Loopback and link-local addresses are always skipped
+    pub exclude_address_cidrs: Option<Vec<String>>,
+    /// Template of the names of the Routers created for the Network's pods. Variables:
+    /// `{network}`, `{node}` and `{pod}`, `{pod}` by default. It must use `{node}` or `{pod}`
+    /// to tell the Routers apart. See `router_name`
+    pub router_name_template: Option<String>,
+    /// Host port the router sidecars serve forwarding metrics on, no metrics if unset.
+    /// Since the routers use the host network, no two Networks may set the same port
+    pub metrics_port: Option<i32>,
+    /// Container restarts of a router pod after which the Network is marked `Degraded`, 5 by default.
+    /// Only containers whose last restart is recent count, so the Network recovers once they settle
+    pub max_pod_restarts: Option<i32>,
+    /// Octal permission mode of the host directory of the ndnd socket, like `0750`. ndnd makes
+    /// its socket world-writable, so the directory decides which local clients can connect.
+    /// Set by the init container before ndnd starts
+    pub socket_mode: Option<String>,
+    /// Group owning the directory of the ndnd socket, e.g. the group of the local clients.
+    /// Set as the `fsGroup` of the router pods, which the sidecar then runs with
+    pub socket_group: Option<i64>,
+    /// If set, every router sidecar probes the data plane this often, by expressing an interest
+    /// for the health probe name of each other online router, and sets `DataPlaneHealthy`
+    /// in its Router status. See `health_probe_name`
+    pub health_check_interval_seconds: Option<u64>,
+    /// Number of OS threads ndnd forwards with, set as `GOMAXPROCS` on the network container.
+    /// Defaults to the container's CPU limit, rounded up, or to the node's CPUs without a limit
+    pub worker_threads: Option<u32>,
+    /// Download the ndnd config from a URL in the init container instead of generating it,
+    /// for deployments that generate configs centrally. The routers still report their faces
+    pub remote_config: Option<RemoteConfig>,
+    /// Termination of the router pods when the cluster autoscaler removes their node
+    pub scale_in: Option<ScaleIn>,
+    /// Links each router sidecar creates or destroys in parallel when its neighbors change, 8 by default
+    pub link_concurrency: Option<u16>,
+    /// Whether the operator applies the router DaemonSets, `Apply` by default, or only publishes
+    /// them for an external applier like a GitOps tool, `Publish`. See `ApplyMode`
+    pub apply_mode: Option<ApplyMode>,
+    /// Compute resources of the ndnd container. With them set, the operator warns with `QuotaRisk`
+    /// when the router pods it is about to schedule would exceed a ResourceQuota of the namespace
+    pub resources: Option<ResourceRequirements>,
+    /// Whether each router sidecar registers the Network prefix on the faces of its neighbors
+    /// once they are created. Off by default: distance-vector routing, the only routing of ndnd,
+    /// already announces the prefixes of the routers
+    pub auto_register_prefix: Option<bool>,
+    /// Time range in which the operator rolls the router pods. Changes to the pod template of an
+    /// existing DaemonSet, e.g. a new image, ndnd setting or referenced Secret, and the removal
+    /// of DaemonSets are deferred until the window opens. New DaemonSets and status updates
+    /// are applied anytime. Ignored with the `Publish` apply mode
+    pub maintenance_window: Option<MaintenanceWindow>,
+    /// Packets the ndnd content store holds, 1024 by default. ndnd only evicts the least
+    /// recently used packets
+    pub content_store_capacity: Option<u16>,
+    /// Seconds between the advertisements the routers send their neighbors, 1 to 60, ndnd's
+    /// default of 5 if unset. They keep NAT mappings open, and a neighbor silent for 6 intervals,
+    /// `KEEP_ALIVES_BEFORE_DEAD`, is considered dead
+    pub keep_alive_interval_seconds: Option<u64>,
+    /// Environment variables added to the ndnd container. Values may reference the Network with
+    /// `${network.name}`, `${network.namespace}`, `${network.prefix}` and `${network.udpUnicastPort}`,
+    /// expanded when the DaemonSet is built. See `expand_env_template`
+    pub extra_env: Option<Vec<EnvVar>>,
+    /// Bound on the neighbors of each router, for meshes too large to be full. See `FanOut`
+    pub fan_out: Option<FanOut>,
+    /// Roles of the routers by a label of their node, for a hub-and-spoke mesh. All routers
+    /// are `Full` if unset. See `NodeRoles`
+    pub node_roles: Option<NodeRoles>,
+    /// Keys of node labels copied onto the Routers of the Network's pods, e.g.
+    /// `topology.kubernetes.io/zone`, for placement and meshing aware of where routers run
+    pub topology_labels: Option<Vec<String>>,
+    /// ConfigMap holding the links between the routers, planned by an external tool.
+    /// Replaces the full mesh, the roles and the fan-out. See `ExternalTopology`
+    pub external_topology: Option<ExternalTopology>,
+    /// Run the router pods in the host PID namespace, off by default. The privileged ndnd
+    /// and sidecar containers can then see and signal every process of the node
+    pub host_pid: Option<bool>,
+    /// Run the router pods in the host IPC namespace, off by default. The containers can then
+    /// reach the shared memory and message queues of every process of the node
+    pub host_ipc: Option<bool>,
+    /// Hold the readiness of the router pods until their sidecar linked all the neighbors the
+    /// operator gave the router, with the `named-data.net/mesh-converged` readiness gate, so
+    /// rolling updates wait for the mesh to converge. Off by default
+    pub mesh_readiness_gate: Option<bool>,
+}
+
+/// How the desired router DaemonSets reach the cluster
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum ApplyMode {
+    /// The operator applies the DaemonSets
+    #[default]
+    Apply,
+    /// The operator writes the DaemonSets as a `v1/List` manifest under the `daemonsets.yaml` key
+    /// of the `<network>-daemonsets` ConfigMap, owned by the Network, and applies nothing.
+    /// The external applier must keep the DaemonSets' names and labels
+    Publish,
+}
+
+/// ConfigMap in the Network namespace listing the links between the routers of the Network,
+/// one `router-a router-b` edge per line, see `parse_edges`. Routers only neighbor the routers
+/// they are linked to. Edges naming routers that don't exist are reported and have no effect
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalTopology {
+    /// Name of the ConfigMap
+    pub config_map: String,
+    /// Key of the edges in the ConfigMap, `edges` by default
+    pub key: Option<String>,
+}
+
+impl ExternalTopology {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(DEFAULT_EXTERNAL_TOPOLOGY_KEY)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaleIn {
+    /// Value of the `cluster-autoscaler.kubernetes.io/safe-to-evict` annotation of the router pods,
+    /// `false` keeps the autoscaler from removing nodes running a router
+    pub safe_to_evict: Option<bool>,
+    /// Seconds ndnd keeps forwarding once its pod is terminating. The Router is deleted as soon
+    /// as the pod terminates, withdrawing it from its neighbors, which reroute before ndnd stops
+    pub withdraw_seconds: Option<i64>,
+}
+
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteConfig {
+    /// `http` or `https` URL of the config, in the Network's `configFormat`
+    pub url: String,
+    /// Key of a Secret in the Network namespace holding the `Authorization` header of the download
+    pub authorization_secret: Option<SecretKeySelector>,
+}
+
+/// Peering policy restricting the addresses routers form faces to
+#[derive(Clone, Debug, Default)]
+pub struct FacePolicy {
+    pub allow: Vec<Cidr>,
+    pub deny: Vec<Cidr>,
+}
+
+impl FacePolicy {
+    /// Whether a face to the URI is permitted. With an allow list, faces whose host
+    /// is a name rather than an address are rejected since they can't be checked
+    pub fn allows(&self, uri: &str) -> bool {
+        match face_ip(uri) {
+            Some(ip) => {
+                (self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(&ip)))
+                    && !self.deny.iter().any(|cidr| cidr.contains(&ip))
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum EventSeverity {
+    Normal,
+    Warning,
+}
+
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FaceSpec {
+    /// Remote face URI, e.g. `udp://10.0.0.1:6363`
+    pub uri: String,
+    /// Local endpoint to bind the face to on multi-homed nodes, e.g. `udp://192.168.1.10:6363`
+    pub local: Option<String>,
+}
+
+/// Schemes of the faces the routers advertise. Routers only listen on UDP
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FaceSchemes {
+    /// Scheme of IPv4 UDP faces, `udp` by default
+    pub udp4: Option<String>,
+    /// Scheme of IPv6 UDP faces, `udp` by default
+    pub udp6: Option<String>,
+    /// Accept schemes ndnd doesn't support out of the box, for custom transports
+    pub allow_custom: Option<bool>,
+}
+
+impl FaceSchemes {
+    pub fn udp4(&self) -> &str {
+        self.udp4.as_deref().unwrap_or("udp")
+    }
+
+    pub fn udp6(&self) -> &str {
+        self.udp6.as_deref().unwrap_or("udp")
+    }
+
+    pub fn allow_custom(&self) -> bool {
+        self.allow_custom.unwrap_or(false)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for scheme in [self.udp4(), self.udp6()] {
+            validate_face_scheme(scheme, self.allow_custom())?;
+        }
+        Ok(())
+    }
+}
+
+impl NetworkSpec {
+    pub fn validate(&self) -> Result<()> {
+        let face_schemes = self.face_schemes.clone().unwrap_or_default();
+        face_schemes.validate()?;
+        for face in self.faces.iter().flatten() {
+            validate_face_uri(&face.uri, face_schemes.allow_custom())?;
+            if let Some(local) = &face.local {
+                validate_local_endpoint(local, &face.uri)?;
+            }
+        }
+        for gate in self.scheduling_gates.iter().flatten() {
+            validate_qualified_name(&gate.name)?;
+        }
+        for (key, value) in self.node_selector.iter().flatten() {
+            validate_qualified_name(key)
+                .map_err(|e| Error::InvalidSpec(format!("node selector key is invalid: {e}")))?;
+            validate_label_value(value)
+                .map_err(|e| Error::InvalidSpec(format!("node selector value of `{key}` is invalid: {e}")))?;
+        }
+        for key in self.topology_labels.iter().flatten() {
+            validate_qualified_name(key)
+                .map_err(|e| Error::InvalidSpec(format!("topology label key is invalid: {e}")))?;
+        }
+        self.face_policy()?;
+        parse_cidrs(&self.exclude_address_cidrs)?;
+        if let Some(template) = &self.router_name_template {
+            validate_router_name_template(template)?;
+        }
+        for (resource, quantity) in self.resources.iter().flat_map(|resources| resources.requests.iter().chain(resources.limits.iter()).flatten()) {
+            parse_quantity(&quantity.0).map_err(|e| Error::InvalidSpec(format!("resource `{resource}` is invalid: {e}")))?;
+        }
+        if self.content_store_capacity == Some(0) {
+            return Err(Error::InvalidSpec(format!("content store capacity must be between 1 and {}", u16::MAX)));
+        }
+        if let Some(interval) = self.keep_alive_interval_seconds
+            && !(1..=MAX_KEEP_ALIVE_INTERVAL_SECONDS).contains(&interval)
+        {
+            return Err(Error::InvalidSpec(format!(
+                "keep-alive interval must be between 1 and {MAX_KEEP_ALIVE_INTERVAL_SECONDS} seconds"
+            )));
+        }
+        if let Some(port) = self.metrics_port
+            && (port <= 0 || port > u16::MAX as i32 || port == self.udp_unicast_port)
+        {
+            return Err(Error::InvalidSpec(format!(
+                "metrics port {port} must be between 1 and {} and differ from the UDP unicast port",
+                u16::MAX
+            )));
+        }
+        if self.link_concurrency == Some(0) {
+            return Err(Error::InvalidSpec("link concurrency must be positive".to_string()));
+        }
+        if self.worker_threads == Some(0) {
+            return Err(Error::InvalidSpec("worker threads must be positive".to_string()));
+        }
+        if self.health_check_interval_seconds == Some(0) {
+            return Err(Error::InvalidSpec("health check interval must be at least one second".to_string()));
+        }
+        for arch in self.ndnd.iter().flat_map(|ndnd| ndnd.arch_images.iter().flatten()).map(|(arch, _)| arch) {
+            if !is_dns_label(arch) {
+                return Err(Error::InvalidSpec(format!("architecture `{arch}` must be a lowercase alphanumeric label")));
+            }
+        }
+        if let Some(mode) = &self.socket_mode {
+            parse_file_mode(mode)?;
+        }
+        if self.socket_group.is_some_and(|gid| gid < 0 || gid > u32::MAX as i64) {
+            return Err(Error::InvalidSpec("socket group must be a valid group ID".to_string()));
+        }
+        if self.scale_in.as_ref().and_then(|scale_in| scale_in.withdraw_seconds).is_some_and(|seconds| seconds <= 0) {
+            return Err(Error::InvalidSpec("scale-in withdraw seconds must be positive".to_string()));
+        }
+        // The sidecars resolve the faces of the neighbors through the management client
+        if self.auto_register_prefix == Some(true) && !cfg!(feature = "mgmt") {
+            return Err(Error::InvalidSpec("auto-registering the prefix requires the `mgmt` feature".to_string()));
+        }
+        if let Some(node_roles) = &self.node_roles {
+            node_roles.validate()?;
+        }
+        if let Some(fan_out) = &self.fan_out {
+            fan_out.validate()?;
+        }
+        if let Some(topology) = &self.external_topology {
+            if self.fan_out.is_some() {
+                return Err(Error::InvalidSpec("an external topology and a fan-out can't be combined".to_string()));
+            }
+            validate_dns_subdomain(&topology.config_map)
+                .map_err(|e| Error::InvalidSpec(format!("external topology ConfigMap name is invalid: {e}")))?;
+        }
+        if let Some(window) = &self.maintenance_window {
+            window.validate()?;
+        }
+        if let Some(remote) = &self.remote_config {
+            validate_http_url(&remote.url)?;
+        }
+        for env in self.extra_env.iter().flatten() {
+            if let Some(value) = &env.value {
+                expand_env_template(value, |_| Some(String::new()))
+                    .map_err(|e| Error::InvalidSpec(format!("env var `{}` is invalid: {e}", env.name)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn face_policy(&self) -> Result<FacePolicy> {
+        Ok(FacePolicy {
+            allow: parse_cidrs(&self.face_allow_cidrs)?,
+            deny: parse_cidrs(&self.face_deny_cidrs)?,
+        })
+    }
+
+    /// Fields of a raw `spec` object that are not part of `NetworkSpec`, e.g. misspelled ones
+    pub fn unknown_fields(raw: &serde_json::Value) -> Result<Vec<String>> {
+        let spec: NetworkSpec = serde_json::from_value(raw.clone()).map_err(Error::SerializationError)?;
+        let known = serde_json::to_value(spec).map_err(Error::SerializationError)?;
+        Ok(unknown_fields(raw, &known, "spec"))
+    }
 }
 
 #[skip_serializing_none]
@@ -50,24 +424,106 @@ pub struct NetworkSpec {
 #[serde(rename_all = "camelCase")]
 pub struct Ndnd {
     pub image: String,
+    /// Pull the ndnd image in a first init container, so it is cached before the router starts.
+    /// Image pulls are normally the kubelet's job; this is a workaround for slow or
+    /// air-gapped registries
+    pub pre_pull: Option<bool>,
+    /// Images by node architecture (the `kubernetes.io/arch` label, e.g. `arm64`), overriding
+    /// `image` on those nodes with a DaemonSet per architecture. A multi-arch image manifest
+    /// as `image` is recommended instead, this is for images only published per architecture
+    pub arch_images: Option<BTreeMap<String, String>>,
 }
 
 impl Default for Ndnd {
     fn default() -> Self {
         Self {
             image: "ghcr.io/named-data/ndnd:latest".to_string(),
+            pre_pull: None,
+            arch_images: None,
         }
     }
 }
 
+/// Outcome of `Network::apply_owned`
+#[derive(Default)]
+struct DaemonSetApply {
+    /// DaemonSets created, or whose pod template changed
+    applied: Vec<String>,
+    /// DaemonSets whose roll or removal waits for the maintenance window
+    deferred: Vec<String>,
+    /// DaemonSet whose selector changed, which the API server refuses to update
+    selector_immutable: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NetworkStatus {
     ds_created: Option<bool>,
+    pub conditions: Option<Vec<Condition>>,
+    /// Routers of the Network and their neighbors, see `MeshTopology`
+    pub topology: Option<MeshTopology>,
+    /// Routers running each ndnd version, telling a partial upgrade apart. Routers whose
+    /// version is unknown yet are left out
+    pub ndnd_versions: Option<BTreeMap<String, u32>>,
 }
 
 impl Network {
+    /// Network CRD with unknown `spec` fields preserved instead of pruned by the API server,
+    /// so the validating webhook can report misspelled fields
+    pub fn crd_preserving_unknown_fields() -> CustomResourceDefinition {
+        let mut crd = Network::crd();
+        for version in crd.spec.versions.iter_mut() {
+            if let Some(spec) = version
+                .schema
+                .as_mut()
+                .and_then(|s| s.open_api_v3_schema.as_mut())
+                .and_then(|s| s.properties.as_mut())
+                .and_then(|p| p.get_mut("spec"))
+            {
+                spec.x_kubernetes_preserve_unknown_fields = Some(true);
+            }
+        }
+        crd
+    }
+
     pub async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
+        let network = match self.resolve_template(ctx.client.clone()).await {
+            Ok(network) => network,
+            Err(e) => {
+                ctx.publish_event(
+                    Event {
+                        type_: EventType::Warning,
+                        reason: "InvalidTemplate".into(),
+                        note: Some(e.to_string()),
+                        action: "Validating".into(),
+                        secondary: None,
+                    },
+                    &self.object_ref(&()),
+                    self.spec.event_types.as_ref(),
+                )
+                .await?;
+                return Err(e);
+            }
+        };
+        network.reconcile_resolved(ctx).await
+    }
+
+    async fn reconcile_resolved(&self, ctx: Arc<Context>) -> Result<Action> {
+        if let Err(e) = self.spec.validate() {
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "InvalidSpec".into(),
+                    note: Some(e.to_string()),
+                    action: "Validating".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                self.spec.event_types.as_ref(),
+            )
+            .await?;
+            return Err(e);
+        }
         let api_nw: Api<Network> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
         let serverside = PatchParams::apply(NETWORK_MANAGER_NAME);
         let my_pod = get_my_pod(ctx.client.clone())
@@ -78,63 +534,891 @@ impl Network {
             .expect("Failed to get pod spec");
         let my_image = my_pod_spec.containers.first().expect("Failed to get my container").image.clone();
         let ns = self.namespace().unwrap();
-        let api_sa: Api<ServiceAccount> = Api::namespaced(ctx.client.clone(), &ns);
-        let api_role: Api<Role> = Api::namespaced(ctx.client.clone(), &ns);
-        let api_role_binding: Api<RoleBinding> = Api::namespaced(ctx.client.clone(), &ns);
-        let api_ds: Api<DaemonSet> = Api::namespaced(ctx.client.clone(), &ns);
         let sa_data = self.create_owned_sa();
         let role_date = self.create_owned_role();
         let role_binding_data = self.create_owned_role_binding(sa_data.name_any(), role_date.name_any());
-        let ds_data = self.create_owned_daemonset(my_image, Some(sa_data.name_any()));
-        // Create ServiceAccount
-        let _sa = api_sa.patch(&self.name_any(), &serverside, &Patch::Apply(sa_data)).await.map_err(Error::KubeError)?;
-        let _role = api_role.patch(&self.name_any(), &serverside, &Patch::Apply(role_date)).await.map_err(Error::KubeError)?;
-        let _role_binding = api_role_binding.patch(&self.name_any(), &serverside, &Patch::Apply(role_binding_data)).await.map_err(Error::KubeError)?;
-        // Create DaemonSet
-        let ds = api_ds.patch(&self.name_any(), &serverside, &Patch::Apply(ds_data)).await.map_err(Error::KubeError)?;
-        // Publish event
-        ctx.recorder
-            .publish(
-                &Event {
+        let mut ds_data = self.create_owned_daemonsets(my_image, Some(sa_data.name_any()));
+        // Roll the router pods when a Secret their config is read from changes
+        if let Some(version) = self.config_version(ctx.clone()).await? {
+            for ds in ds_data.iter_mut() {
+                let metadata = ds.spec.as_mut().and_then(|spec| spec.template.metadata.as_mut());
+                if let Some(metadata) = metadata {
+                    metadata.annotations.get_or_insert_default().insert(CONFIG_VERSION_ANNOTATION_KEY.to_string(), version.clone());
+                }
+            }
+        }
+        // The hash of the pod template tells the changes rolling the pods apart
+        for ds in ds_data.iter_mut() {
+            let hash = template_hash(ds);
+            ds.metadata.annotations.get_or_insert_default().insert(TEMPLATE_HASH_ANNOTATION_KEY.to_string(), hash);
+        }
+        // The checks only report problems and never block the DaemonSet, even when they fail,
+        // so e.g. the pods recover once a missing pull secret is created
+        let checks = [
+            ("pull secrets", self.check_pull_secrets(ctx.clone()).await),
+            ("host namespaces", self.check_host_namespaces(ctx.clone()).await),
+            ("prefix overlap", self.check_prefix_overlap(ctx.clone()).await),
+            ("metrics port", self.check_metrics_port(ctx.clone()).await),
+            ("quota", self.check_quota(ctx.clone()).await),
+        ];
+        for (check, result) in checks {
+            if let Err(e) = result {
+                warn!("Failed to check the {} of Network `{}`: {}", check, self.name_any(), e);
+            }
+        }
+        let now = Utc::now();
+        let window = self.spec.maintenance_window.as_ref();
+        let window_open = window.map(|window| window.is_open(now)).transpose()?.unwrap_or(true);
+        let applied = self.apply_and_record(ctx.clone(), sa_data, role_date, role_binding_data, &ds_data, window_open).await?;
+        // Retrying can't fix an immutable selector, only check back for a deleted DaemonSet
+        if let Some(name) = &applied.selector_immutable {
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "SelectorImmutable".into(),
+                    note: Some(format!(
+                        "The selector of DaemonSet `{name}` changed but is immutable, delete the DaemonSet so it is recreated within 5 minutes"
+                    )),
+                    action: "Creating".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                self.spec.event_types.as_ref(),
+            )
+            .await?;
+            return Ok(Action::requeue(Duration::from_secs(5 * 60)));
+        }
+        for name in &applied.applied {
+            ctx.publish_event(
+                Event {
                     type_: EventType::Normal,
                     reason: "DaemonSetCreated".into(),
-                    note: Some(format!("Created `{}` DaemonSet for `{}` Network", ds.name_any(), self.name_any())),
+                    note: Some(format!("Created `{}` DaemonSet for `{}` Network", name, self.name_any())),
                     action: "Created".into(),
                     secondary: None,
                 },
                 &self.object_ref(&()),
+                self.spec.event_types.as_ref(),
             )
+            .await?;
+        }
+        let deferred = applied.deferred;
+        let mut deferred_until = None;
+        let mut deferrals = Vec::new();
+        if !deferred.is_empty() {
+            deferred_until = window.map(|window| window.next_start(now)).transpose()?.flatten();
+            deferrals.push(Event {
+                type_: EventType::Normal,
+                reason: "RolloutDeferred".into(),
+                note: Some(format!(
+                    "Changes to DaemonSets {} are deferred to the maintenance window{}",
+                    deferred.join(", "),
+                    deferred_until.map(|start| format!(" opening at {}", start.to_rfc3339())).unwrap_or_default()
+                )),
+                action: "Deferring".into(),
+                secondary: None,
+            });
+        }
+        ctx.publish_changed_events(&self.object_ref(&()), "RolloutDeferred", deferrals, self.spec.event_types.as_ref()).await?;
+        // Faces move to a new port with the pods, not before
+        if deferred.is_empty() {
+            self.update_router_ports(ctx.clone()).await?;
+            // Likewise, routers leave the nodes no longer selected once the new selector is applied
+            self.remove_deselected_routers(ctx.clone()).await?;
+        }
+        self.label_nodes(ctx.clone()).await?;
+        // A successful reconcile drops the `Stalled` condition
+        if self.current_condition(STALLED_CONDITION).is_some() {
+            let conditions: Vec<&Condition> = self.conditions().filter(|condition| condition.type_ != STALLED_CONDITION).collect();
+            let status = json!({ "status": { "conditions": conditions } });
+            api_nw
+                .patch_status(&self.name_any(), &serverside, &Patch::Merge(&status))
+                .await
+                .map_err(Error::KubeError)?;
+            audit::<Network>("merge-patch", Some("status"), Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        }
+        annotate_version(&api_nw, self).await?;
+        // Check back when the maintenance window opens
+        match deferred_until.and_then(|start| (start - Utc::now()).to_std().ok()) {
+            Some(wait) => Ok(Action::requeue(wait)),
+            None if !deferred.is_empty() => Ok(Action::requeue(Duration::from_secs(60))),
+            None => Ok(Action::await_change()),
+        }
+    }
+
+    /// Apply the owned objects, then record in the status whether the DaemonSets were created
+    /// before any other step can fail, so the status never claims DaemonSets whose apply failed
+    async fn apply_and_record(
+        &self,
+        ctx: Arc<Context>,
+        sa: ServiceAccount,
+        role: Role,
+        role_binding: RoleBinding,
+        ds_data: &[DaemonSet],
+        window_open: bool,
+    ) -> Result<DaemonSetApply> {
+        let ns = self.namespace().unwrap();
+        let api_nw: Api<Network> = Api::namespaced(ctx.client.clone(), &ns);
+        let applied = self.apply_owned(ctx, sa, role, role_binding, ds_data, window_open).await;
+        let created = applied.as_ref().is_ok_and(|applied| applied.selector_immutable.is_none());
+        if created != self.is_reconciled() {
+            let status = json!({ "status": { "dsCreated": created } });
+            api_nw
+                .patch_status(&self.name_any(), &PatchParams::apply(NETWORK_MANAGER_NAME), &Patch::Merge(&status))
+                .await
+                .map_err(Error::KubeError)?;
+            audit::<Network>("merge-patch", Some("status"), Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        }
+        applied
+    }
+
+    /// Apply the ServiceAccount, Role and RoleBinding of the routers, then the DaemonSets, or
+    /// publish them with the `Publish` apply mode. Only talks to the API server, the caller
+    /// reports the outcome once recorded in the status. Every step is an idempotent apply or
+    /// delete, a failed pass is retried as a whole
+    async fn apply_owned(
+        &self,
+        ctx: Arc<Context>,
+        sa: ServiceAccount,
+        role: Role,
+        role_binding: RoleBinding,
+        ds_data: &[DaemonSet],
+        window_open: bool,
+    ) -> Result<DaemonSetApply> {
+        let ns = self.namespace().unwrap();
+        let serverside = PatchParams::apply(NETWORK_MANAGER_NAME);
+        let api_sa: Api<ServiceAccount> = Api::namespaced(ctx.client.clone(), &ns);
+        let api_role: Api<Role> = Api::namespaced(ctx.client.clone(), &ns);
+        let api_role_binding: Api<RoleBinding> = Api::namespaced(ctx.client.clone(), &ns);
+        let api_ds: Api<DaemonSet> = Api::namespaced(ctx.client.clone(), &ns);
+        api_sa.patch(&self.name_any(), &serverside, &Patch::Apply(sa)).await.map_err(Error::KubeError)?;
+        audit::<ServiceAccount>("apply", None, Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        api_role.patch(&self.name_any(), &serverside, &Patch::Apply(role)).await.map_err(Error::KubeError)?;
+        audit::<Role>("apply", None, Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        api_role_binding.patch(&self.name_any(), &serverside, &Patch::Apply(role_binding)).await.map_err(Error::KubeError)?;
+        audit::<RoleBinding>("apply", None, Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        if self.apply_mode() == ApplyMode::Publish {
+            self.publish_daemonsets(ctx.clone(), ds_data).await?;
+            return Ok(DaemonSetApply::default());
+        }
+        self.apply_daemonsets(&api_ds, ds_data, window_open).await
+    }
+
+    /// Apply the DaemonSets, deferring the rolls outside the maintenance window. Stops at the
+    /// first DaemonSet whose selector can't be updated, reported in the outcome
+    async fn apply_daemonsets(&self, api_ds: &Api<DaemonSet>, ds_data: &[DaemonSet], window_open: bool) -> Result<DaemonSetApply> {
+        let ns = self.namespace().unwrap();
+        let serverside = PatchParams::apply(NETWORK_MANAGER_NAME);
+        let mut outcome = DaemonSetApply::default();
+        let existing: BTreeMap<String, DaemonSet> = api_ds
+            .list(&ListParams::default().labels(&format!("{DS_LABEL_KEY}={}", self.name_any())))
             .await
-            .map_err(Error::KubeError)?;
-        // Update the status of the Network
-        let status = json!({
-            "status": NetworkStatus {
-                ds_created: Some(true),
+            .map_err(Error::KubeError)?
+            .into_iter()
+            .map(|ds| (ds.name_any(), ds))
+            .collect();
+        for ds_data in ds_data {
+            if !window_open && existing.get(&ds_data.name_any()).is_some_and(|ds| template_hash_annotation(ds) != template_hash_annotation(ds_data)) {
+                info!("Deferring the roll of DaemonSet {} to the maintenance window", ds_data.name_any());
+                outcome.deferred.push(ds_data.name_any());
+                continue;
             }
-        });
-        let _o = api_nw
-            .patch_status(&self.name_any(), &serverside, &Patch::Merge(&status))
-            .await
-            .map_err(Error::KubeError)?;
-        Ok(Action::await_change())
+            let changed = existing.get(&ds_data.name_any()).is_none_or(|ds| template_hash_annotation(ds) != template_hash_annotation(ds_data));
+            match api_ds.patch(&ds_data.name_any(), &serverside, &Patch::Apply(ds_data)).await {
+                Ok(ds) => {
+                    audit::<DaemonSet>("apply", None, Some(&ns), &ds.name_any(), Some(NETWORK_MANAGER_NAME));
+                    if changed {
+                        outcome.applied.push(ds.name_any());
+                    }
+                }
+                Err(e) if is_selector_immutable(&e) => {
+                    warn!("Selector of DaemonSet {} can't be updated: {}", ds_data.name_any(), e);
+                    outcome.selector_immutable = Some(ds_data.name_any());
+                    return Ok(outcome);
+                }
+                Err(e) => return Err(Error::KubeError(e)),
+            }
+        }
+        // Remove the DaemonSets of architectures no longer overridden
+        let arch_lp = ListParams::default().labels(&format!("{DS_LABEL_KEY}={},{ARCH_LABEL_KEY}", self.name_any()));
+        for ds in api_ds.list(&arch_lp).await.map_err(Error::KubeError)? {
+            if !ds_data.iter().any(|desired| desired.name_any() == ds.name_any()) {
+                if !window_open {
+                    outcome.deferred.push(ds.name_any());
+                    continue;
+                }
+                info!("Deleting DaemonSet {} of Network {}", ds.name_any(), self.name_any());
+                api_ds.delete(&ds.name_any(), &Default::default()).await.map_err(Error::KubeError)?;
+                audit::<DaemonSet>("delete", None, Some(&ns), &ds.name_any(), None);
+            }
+        }
+        Ok(outcome)
     }
 
     pub async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
         let oref = self.object_ref(&());
-        ctx.recorder
-            .publish(
-                &Event {
+        ctx.publish_event(
+            Event {
+                type_: EventType::Normal,
+                reason: "DeleteRequested".into(),
+                note: Some(format!("Delete `{}`", self.name_any())),
+                action: "Deleting".into(),
+                secondary: None,
+            },
+            &oref,
+            self.spec.event_types.as_ref(),
+        )
+        .await?;
+        ctx.forget_events(&oref);
+        self.unlabel_nodes(ctx).await?;
+        Ok(Action::await_change())
+    }
+
+    /// Update the status derived from the Routers and router pods, on their changes rather than
+    /// with the full reconcile: the `Degraded` and `MeshPartitioned` conditions, the topology
+    /// and the ndnd versions. The Warnings are only published when a condition turns `True`
+    pub async fn reconcile_status(&self, ctx: Arc<Context>) -> Result<Action> {
+        let network = self.resolve_template(ctx.client.clone()).await?;
+        let ns = self.namespace().unwrap();
+        // Surface crashlooping router pods on the Network
+        let pods = Api::<Pod>::namespaced(ctx.client.clone(), &ns)
+            .list(&ListParams::default().labels(&format!("{DS_LABEL_KEY}={}", self.name_any())))
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        let restarts = recent_restart_count(&pods, Utc::now());
+        let degraded = network.degraded_condition(restarts);
+        if degraded.status == "True" {
+            warn!("Router pods of Network `{}` restarted {} times", self.name_any(), restarts);
+        }
+        network.publish_on_transition(ctx.clone(), &degraded).await?;
+        // Surface online routers split into components that can't reach each other
+        let routers = Api::<Router>::namespaced(ctx.client.clone(), &ns)
+            .list(&ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={}", self.name_any())))
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        let online: Vec<Router> = routers
+            .iter()
+            .filter(|router| router.status.as_ref().is_some_and(|status| status.online))
+            .cloned()
+            .collect();
+        let partitioned = network.partitioned_condition(&MeshGraph::new(&online).components());
+        if partitioned.status == "True" {
+            warn!("Mesh of Network `{}` is partitioned", self.name_any());
+        }
+        network.publish_on_transition(ctx.clone(), &partitioned).await?;
+        let conditions = self.with_conditions(vec![degraded, partitioned]);
+        let topology = MeshTopology::new(&routers);
+        let versions = ndnd_versions(&routers);
+        let status = self.status.as_ref();
+        let unchanged = status.and_then(|status| status.conditions.as_ref()) == Some(&conditions)
+            && status.and_then(|status| status.topology.as_ref()) == Some(&topology)
+            && status.and_then(|status| status.ndnd_versions.as_ref()) == Some(&versions);
+        if !unchanged {
+            // The resource version keeps the conditions set by the full reconcile meanwhile
+            let patch = json!({
+                "metadata": { "resourceVersion": self.resource_version() },
+                "status": {
+                    "conditions": conditions,
+                    "topology": topology,
+                    "ndndVersions": versions,
+                }
+            });
+            Api::<Network>::namespaced(ctx.client.clone(), &ns)
+                .patch_status(&self.name_any(), &PatchParams::apply(NETWORK_MANAGER_NAME), &Patch::Merge(&patch))
+                .await
+                .map_err(Error::KubeError)?;
+            audit::<Network>("merge-patch", Some("status"), Some(&ns), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        }
+        // Restarts stop counting once they leave the window, without any pod change
+        match restarts > 0 {
+            true => Ok(Action::requeue(RESTART_WINDOW)),
+            false => Ok(Action::await_change()),
+        }
+    }
+
+    /// Publish a Warning with the condition reason when the condition turns `True`
+    async fn publish_on_transition(&self, ctx: Arc<Context>, condition: &Condition) -> Result<()> {
+        if condition.status != "True" || self.current_condition(&condition.type_).is_some_and(|current| current.status == "True") {
+            return Ok(());
+        }
+        ctx.publish_event(
+            Event {
+                type_: EventType::Warning,
+                reason: condition.reason.clone(),
+                note: Some(condition.message.clone()),
+                action: "Monitoring".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            self.spec.event_types.as_ref(),
+        )
+        .await
+    }
+
+    /// Move the faces of the owned Routers to the Network's port, replacing the old faces
+    /// in the neighbor sets, so the mesh stays consistent while the pods restart
+    async fn update_router_ports(&self, ctx: Arc<Context>) -> Result<()> {
+        let Ok(port) = u16::try_from(self.spec.udp_unicast_port) else {
+            return Ok(());
+        };
+        let api_router: Api<Router> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        let routers = api_router
+            .list(&ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={}", self.name_any())))
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        for (router, faces, neighbors) in port_change_updates(&routers, port) {
+            info!("Moving faces of router {} to port {}", router.name_any(), port);
+            let patch = Patch::Merge(json!({
+                "status": {
+                    "faces": faces,
+                    "neighbors": neighbors,
+                }
+            }));
+            api_router
+                .patch_status(&router.name_any(), &PatchParams::apply(NETWORK_MANAGER_NAME), &patch)
+                .await
+                .map_err(Error::KubeError)?;
+            audit::<Router>("merge-patch", Some("status"), router.namespace().as_deref(), &router.name_any(), Some(NETWORK_MANAGER_NAME));
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Normal,
+                    reason: "PortChanged".into(),
+                    note: Some(format!("Moved faces to port {port}")),
+                    action: "Updated".into(),
+                    secondary: None,
+                },
+                &router.object_ref(&()),
+                self.spec.event_types.as_ref(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Owned Routers on existing nodes the Network's node selector no longer matches
+    pub fn deselected_routers<'a>(&self, routers: &'a [Router], nodes: &[Node]) -> Vec<&'a Router> {
+        let deselected = nodes
+            .iter()
+            .filter(|node| !self.selects_node(node))
+            .map(|node| node.name_any())
+            .collect::<BTreeSet<_>>();
+        routers
+            .iter()
+            .filter(|router| deselected.contains(&router.spec.node_name))
+            .collect()
+    }
+
+    /// Delete the Routers of the nodes dropped from the node selector, without waiting for the
+    /// DaemonSet to remove their pods. Their finalizer withdraws their faces from the neighbors.
+    /// Routers of newly selected nodes are created by the pod sync once their pods are scheduled
+    async fn remove_deselected_routers(&self, ctx: Arc<Context>) -> Result<()> {
+        let ns = self.namespace().unwrap();
+        let api_router: Api<Router> = Api::namespaced(ctx.client.clone(), &ns);
+        let routers = api_router
+            .list(&ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={}", self.name_any())))
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        if routers.is_empty() {
+            return Ok(());
+        }
+        let nodes = Api::<Node>::all(ctx.client.clone())
+            .list(&ListParams::default())
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        for router in self.deselected_routers(&routers, &nodes) {
+            if router.meta().deletion_timestamp.is_some() {
+                continue;
+            }
+            info!("Deleting router {} of deselected node {}", router.name_any(), router.spec.node_name);
+            api_router
+                .delete(&router.name_any(), &DeleteParams::default())
+                .await
+                .map_err(Error::KubeError)?;
+            audit::<Router>("delete", None, Some(&ns), &router.name_any(), Some(NETWORK_MANAGER_NAME));
+            ctx.publish_event(
+                Event {
                     type_: EventType::Normal,
-                    reason: "DeleteRequested".into(),
-                    note: Some(format!("Delete `{}`", self.name_any())),
+                    reason: "NodeDeselected".into(),
+                    note: Some(format!(
+                        "Deleted `{}` Router, node `{}` is no longer selected",
+                        router.name_any(),
+                        router.spec.node_name
+                    )),
                     action: "Deleting".into(),
                     secondary: None,
                 },
-                &oref,
+                &self.object_ref(&()),
+                self.spec.event_types.as_ref(),
             )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// `Degraded` condition reflecting the restarts of the router pods. The transition
+    /// time is kept as long as the condition status doesn't change
+    pub fn degraded_condition(&self, restarts: i32) -> Condition {
+        let max_restarts = self.spec.max_pod_restarts.unwrap_or(DEFAULT_MAX_POD_RESTARTS);
+        let (status, reason, message) = match restarts > max_restarts {
+            true => ("True", "PodsRestarting", format!("A router pod restarted {restarts} times, more than the maximum of {max_restarts}")),
+            false => ("False", "PodsHealthy", format!("Router pods restarted at most {restarts} times")),
+        };
+        self.condition(DEGRADED_CONDITION, status, reason, message)
+    }
+
+    /// `MeshPartitioned` condition from the connected components of the online routers
+    pub fn partitioned_condition(&self, components: &[BTreeSet<String>]) -> Condition {
+        let (status, reason, message) = match components.len() > 1 {
+            true => ("True", "MeshPartitioned", format!(
+                "Routers form {} disconnected components: {}",
+                components.len(),
+                components
+                    .iter()
+                    .map(|component| format!("[{}]", component.iter().cloned().collect::<Vec<_>>().join(", ")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            false => ("False", "MeshConnected", "Online routers are all connected".to_string()),
+        };
+        self.condition(MESH_PARTITIONED_CONDITION, status, reason, message)
+    }
+
+    /// `Stalled` condition of a Network whose reconcile failed `retries` times in a row
+    pub fn stalled_condition(&self, retries: u32, error: &str) -> Condition {
+        let message = format!("Reconcile failed {retries} times in a row, last with: {error}. Change the spec to retry");
+        self.condition(STALLED_CONDITION, "True", "RetriesExhausted", message)
+    }
+
+    /// Report the Network as stalled with a Warning and its `Stalled` condition, which the
+    /// next successful reconcile drops
+    pub async fn stall(&self, ctx: Arc<Context>, retries: u32, error: String) -> Result<()> {
+        let stalled = self.stalled_condition(retries, &error);
+        ctx.publish_event(
+            Event {
+                type_: EventType::Warning,
+                reason: "Stalled".into(),
+                note: Some(stalled.message.clone()),
+                action: "Reconciling".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            self.spec.event_types.as_ref(),
+        )
+        .await?;
+        let conditions = self.with_conditions(vec![stalled]);
+        let api_nw: Api<Network> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        let patch = Patch::Merge(json!({ "status": { "conditions": conditions } }));
+        api_nw
+            .patch_status(&self.name_any(), &PatchParams::apply(NETWORK_MANAGER_NAME), &patch)
+            .await
+            .map_err(Error::KubeError)?;
+        audit::<Network>("merge-patch", Some("status"), self.namespace().as_deref(), &self.name_any(), Some(NETWORK_MANAGER_NAME));
+        Ok(())
+    }
+
+    /// Current conditions of the Network
+    fn conditions(&self) -> impl Iterator<Item = &Condition> {
+        self.status.iter().flat_map(|status| status.conditions.iter().flatten())
+    }
+
+    /// Current condition of the given type
+    pub fn current_condition(&self, type_: &str) -> Option<&Condition> {
+        self.conditions().find(|condition| condition.type_ == type_)
+    }
+
+    /// Conditions of the Network with the given ones replacing the current ones of their types
+    /// in place, so the order stays stable across updates
+    pub fn with_conditions(&self, conditions: Vec<Condition>) -> Vec<Condition> {
+        let mut merged: Vec<Condition> = self.conditions().cloned().collect();
+        for condition in conditions {
+            match merged.iter_mut().find(|current| current.type_ == condition.type_) {
+                Some(current) => *current = condition,
+                None => merged.push(condition),
+            }
+        }
+        merged
+    }
+
+    /// Condition of the Network, keeping the transition time of the current one of the type if its status is unchanged
+    fn condition(&self, type_: &str, status: &str, reason: &str, message: String) -> Condition {
+        let last_transition_time = self
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .and_then(|conditions| conditions.iter().find(|c| c.type_ == type_))
+            .filter(|condition| condition.status == status)
+            .map(|condition| condition.last_transition_time.clone())
+            .unwrap_or(Time(Utc::now()));
+        Condition {
+            type_: type_.to_string(),
+            status: status.to_string(),
+            reason: reason.to_string(),
+            message,
+            last_transition_time,
+            observed_generation: self.metadata.generation,
+        }
+    }
+
+    /// Name of the Network in the same namespace this Network derives its spec from
+    pub fn template_name(&self) -> Option<&str> {
+        self.annotations().get(TEMPLATE_ANNOTATION_KEY).map(String::as_str)
+    }
+
+    /// The Network with its template's spec as a base: the fields set on the Network
+    /// override the template's, objects like `ndnd` are merged field by field,
+    /// and lists like `faces` are replaced as a whole
+    pub fn with_template(&self, template: &Network) -> Result<Network> {
+        if template.name_any() == self.name_any() {
+            return Err(Error::InvalidSpec("a Network can't be its own template".to_string()));
+        }
+        if template.template_name().is_some() {
+            return Err(Error::InvalidSpec(format!(
+                "template Network `{}` has a template itself, templates can't be chained",
+                template.name_any()
+            )));
+        }
+        let mut spec = serde_json::to_value(&template.spec).map_err(Error::SerializationError)?;
+        merge_json(&mut spec, &serde_json::to_value(&self.spec).map_err(Error::SerializationError)?);
+        Ok(Network {
+            spec: serde_json::from_value(spec).map_err(Error::SerializationError)?,
+            ..self.clone()
+        })
+    }
+
+    /// The Network as the operator reconciles it, with its template applied if it has one
+    pub async fn resolve_template(&self, client: Client) -> Result<Network> {
+        let Some(template_name) = self.template_name() else {
+            return Ok(self.clone());
+        };
+        let api_nw: Api<Network> = Api::namespaced(client, &self.namespace().unwrap());
+        let template = api_nw
+            .get_opt(template_name)
+            .await
+            .map_err(Error::KubeError)?
+            .ok_or_else(|| Error::InvalidSpec(format!("template Network `{template_name}` not found in the namespace")))?;
+        self.with_template(&template)
+    }
+
+    /// Whether the last apply of the Network's DaemonSets succeeded
+    pub fn is_reconciled(&self) -> bool {
+        self.status.as_ref().and_then(|status| status.ds_created).unwrap_or(false)
+    }
+
+    /// Routers each router links to, by name, if the Network doesn't form a full mesh:
+    /// the edges of its external topology, or the links of its fan-out
+    pub async fn router_links(&self, client: Client, routers: &[Router], roles: &RouterRoles) -> Result<Option<BTreeMap<String, BTreeSet<String>>>> {
+        let Some(topology) = &self.spec.external_topology else {
+            return Ok(self.spec.fan_out.as_ref().map(|fan_out| fan_out.links(routers, roles)));
+        };
+        let api_cm: Api<ConfigMap> = Api::namespaced(client, &self.namespace().unwrap());
+        let config_map = api_cm
+            .get_opt(&topology.config_map)
+            .await
+            .map_err(Error::KubeError)?
+            .ok_or_else(|| Error::OtherError(format!("external topology ConfigMap `{}` not found", topology.config_map)))?;
+        let edges = config_map
+            .data
+            .and_then(|mut data| data.remove(topology.key()))
+            .ok_or_else(|| Error::OtherError(format!("external topology ConfigMap `{}` has no `{}` key", topology.config_map, topology.key())))?;
+        parse_edges(&edges).map(Some)
+    }
+
+    /// Roles of the routers from the labels of their node, see `NodeRoles`
+    pub async fn router_roles(&self, client: Client, routers: &[Router]) -> Result<RouterRoles> {
+        let Some(node_roles) = &self.spec.node_roles else {
+            return Ok(RouterRoles::default());
+        };
+        let lp = ListParams::default().labels_from(&Expression::Exists(node_roles.node_label.clone()).into());
+        let nodes = Api::<Node>::all(client).list_metadata(&lp).await.map_err(Error::KubeError)?;
+        let by_node: BTreeMap<String, RouterRole> = nodes
+            .items
+            .iter()
+            .filter_map(|node| Some((node.name_any(), node_roles.role(node.labels())?)))
+            .collect();
+        Ok(routers
+            .iter()
+            .filter_map(|router| Some((router.name_any(), *by_node.get(&router.spec.node_name)?)))
+            .collect())
+    }
+
+    /// Labels of the node listed in the topology labels of the Network, to copy onto its Routers
+    pub fn topology_labels_of(&self, node: &Node) -> BTreeMap<String, String> {
+        self.spec
+            .topology_labels
+            .iter()
+            .flatten()
+            .filter_map(|key| Some((key.clone(), node.labels().get(key)?.clone())))
+            .collect()
+    }
+
+    /// Whether the Network's node selector matches the node
+    pub fn selects_node(&self, node: &Node) -> bool {
+        self.selects_labels(node.labels())
+    }
+
+    /// Whether the Network's node selector matches a node with the labels
+    pub fn selects_labels(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.spec
+            .node_selector
+            .iter()
+            .flatten()
+            .all(|(key, value)| labels.get(key) == Some(value))
+    }
+
+    /// Label of the nodes the Network schedules routers on, `network-<namespace>.<name>` so
+    /// Networks of the same name in other namespaces don't share it. `None` if the namespace
+    /// and name of the Network are too long for a label name
+    pub fn node_label_key(&self) -> Option<String> {
+        let key = format!("{NODE_NETWORK_LABEL_PREFIX}{}.{}", self.namespace().unwrap_or_default(), self.name_any());
+        key.split_once('/').is_some_and(|(_, name)| name.len() <= 63).then_some(key)
+    }
+
+    /// Label the nodes the Network schedules routers on, and unlabel the nodes it no longer selects
+    async fn label_nodes(&self, ctx: Arc<Context>) -> Result<()> {
+        if !ctx.label_nodes {
+            return Ok(());
+        }
+        let Some(key) = self.node_label_key() else {
+            warn!("Namespace and name of Network `{}` are too long to label its nodes", self.name_any());
+            return Ok(());
+        };
+        let api_node = Api::<Node>::all(ctx.client.clone());
+        for node in api_node.list(&ListParams::default()).await.map_err(Error::KubeError)? {
+            let selected = self.selects_node(&node);
+            if node.labels().contains_key(&key) != selected {
+                patch_node_label(&api_node, &node.name_any(), &key, selected).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the Network's label from all nodes
+    async fn unlabel_nodes(&self, ctx: Arc<Context>) -> Result<()> {
+        let Some(key) = self.node_label_key().filter(|_| ctx.label_nodes) else {
+            return Ok(());
+        };
+        let api_node = Api::<Node>::all(ctx.client.clone());
+        for node in api_node.list(&ListParams::default().labels(&key)).await.map_err(Error::KubeError)? {
+            patch_node_label(&api_node, &node.name_any(), &key, false).await?;
+        }
+        Ok(())
+    }
+
+    /// Networks among the given ones whose prefix is an ancestor or descendant of this Network's
+    pub fn overlapping<'a>(&self, networks: &'a [Network]) -> Vec<&'a Network> {
+        networks
+            .iter()
+            .filter(|network| network.name_any() != self.name_any())
+            .filter(|network| prefixes_overlap(&network.spec.prefix, &self.spec.prefix))
+            .collect()
+    }
+
+    /// Warn when the router pods yet to be scheduled on the selected nodes would exceed a
+    /// ResourceQuota of the namespace. Only checked if the Network sets the ndnd resources
+    async fn check_quota(&self, ctx: Arc<Context>) -> Result<()> {
+        let mut events = Vec::new();
+        let Some(resources) = &self.spec.resources else {
+            return ctx.publish_changed_events(&self.object_ref(&()), "QuotaRisk", events, self.spec.event_types.as_ref()).await;
+        };
+        let ns = self.namespace().unwrap();
+        let quotas = Api::<ResourceQuota>::namespaced(ctx.client.clone(), &ns)
+            .list(&ListParams::default())
+            .await
+            .map_err(Error::KubeError)?
+            .items;
+        if quotas.is_empty() {
+            return ctx.publish_changed_events(&self.object_ref(&()), "QuotaRisk", events, self.spec.event_types.as_ref()).await;
+        }
+        let nodes = Api::<Node>::all(ctx.client.clone())
+            .list(&ListParams::default())
+            .await
+            .map_err(Error::KubeError)?
+            .items
+            .iter()
+            .filter(|node| self.selects_node(node))
+            .count();
+        let pods = Api::<Pod>::namespaced(ctx.client.clone(), &ns)
+            .list(&ListParams::default().labels(&format!("{DS_LABEL_KEY}={}", self.name_any())))
+            .await
+            .map_err(Error::KubeError)?
+            .items
+            .len();
+        let per_pod = pod_usage(resources);
+        let risks: Vec<String> = quotas
+            .iter()
+            .flat_map(|quota| quota_risks(quota, &per_pod, nodes.saturating_sub(pods)))
+            .map(|risk| risk.to_string())
+            .collect();
+        if !risks.is_empty() {
+            warn!("Router pods of Network `{}` would exceed the quota: {}", self.name_any(), risks.join("; "));
+            events.push(Event {
+                type_: EventType::Warning,
+                reason: "QuotaRisk".into(),
+                note: Some(format!("Router pods for {nodes} nodes would exceed the quota, {}", risks.join("; "))),
+                action: "Validating".into(),
+                secondary: None,
+            });
+        }
+        ctx.publish_changed_events(&self.object_ref(&()), "QuotaRisk", events, self.spec.event_types.as_ref()).await
+    }
+
+    async fn check_prefix_overlap(&self, ctx: Arc<Context>) -> Result<()> {
+        let api_nw: Api<Network> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        let networks = api_nw.list(&ListParams::default()).await.map_err(Error::KubeError)?.items;
+        let mut events = Vec::new();
+        for network in self.overlapping(&networks) {
+            warn!("Prefix of Network `{}` overlaps Network `{}`", self.name_any(), network.name_any());
+            events.push(Event {
+                type_: EventType::Warning,
+                reason: "PrefixOverlap".into(),
+                note: Some(format!(
+                    "Prefix `{}` overlaps prefix `{}` of Network `{}`",
+                    self.spec.prefix, network.spec.prefix, network.name_any()
+                )),
+                action: "Validating".into(),
+                secondary: Some(network.object_ref(&())),
+            });
+        }
+        ctx.publish_changed_events(&self.object_ref(&()), "PrefixOverlap", events, self.spec.event_types.as_ref()).await
+    }
+
+    async fn check_metrics_port(&self, ctx: Arc<Context>) -> Result<()> {
+        let mut events = Vec::new();
+        if self.spec.metrics_port.is_some() {
+            let api_nw: Api<Network> = Api::all(ctx.client.clone());
+            let networks = api_nw.list(&ListParams::default()).await.map_err(Error::KubeError)?.items;
+            for network in self.metrics_port_conflicts(&networks) {
+                warn!("Metrics port of Network `{}` clashes with Network `{}`", self.name_any(), network.name_any());
+                events.push(Event {
+                    type_: EventType::Warning,
+                    reason: "MetricsPortConflict".into(),
+                    note: Some(format!(
+                        "Metrics port {} is also used by Network `{}/{}`",
+                        network.spec.metrics_port.unwrap_or_default(), network.namespace().unwrap_or_default(), network.name_any()
+                    )),
+                    action: "Validating".into(),
+                    secondary: Some(network.object_ref(&())),
+                });
+            }
+        }
+        ctx.publish_changed_events(&self.object_ref(&()), "MetricsPortConflict", events, self.spec.event_types.as_ref()).await
+    }
+
+    async fn check_pull_secrets(&self, ctx: Arc<Context>) -> Result<()> {
+        let api_secret: Api<Secret> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        let mut events = Vec::new();
+        for secret_ref in self.spec.image_pull_secrets.iter().flatten() {
+            let secret = api_secret
+                .get_opt(&secret_ref.name)
+                .await
+                .map_err(Error::KubeError)?;
+            if secret.is_some() {
+                continue;
+            }
+            warn!("Image pull secret `{}` of Network `{}` not found", secret_ref.name, self.name_any());
+            events.push(Event {
+                type_: EventType::Warning,
+                reason: "MissingPullSecret".into(),
+                note: Some(format!("Image pull secret `{}` does not exist in the namespace", secret_ref.name)),
+                action: "Validating".into(),
+                secondary: None,
+            });
+        }
+        ctx.publish_changed_events(&self.object_ref(&()), "MissingPullSecret", events, self.spec.event_types.as_ref()).await
+    }
+
+    /// Report the host namespaces the router pods share when they are enabled, so enabling them
+    /// stays a visible decision. The Warning can be turned to `Normal` with `event_types`
+    async fn check_host_namespaces(&self, ctx: Arc<Context>) -> Result<()> {
+        let shared: Vec<&str> = [(self.spec.host_pid, "PID"), (self.spec.host_ipc, "IPC")]
+            .into_iter()
+            .filter(|(enabled, _)| *enabled == Some(true))
+            .map(|(_, namespace)| namespace)
+            .collect();
+        let events = match shared.is_empty() {
+            true => Vec::new(),
+            false => vec![Event {
+                type_: EventType::Warning,
+                reason: "HostNamespacesShared".into(),
+                note: Some(format!(
+                    "Router pods share the host {} namespaces, their privileged containers can reach every process of the node",
+                    shared.join(" and ")
+                )),
+                action: "Validating".into(),
+                secondary: None,
+            }],
+        };
+        ctx.publish_changed_events(&self.object_ref(&()), "HostNamespacesShared", events, self.spec.event_types.as_ref()).await
+    }
+
+    /// Secrets in the Network namespace the Network references
+    pub fn referenced_secrets(&self) -> BTreeSet<String> {
+        let pull_secrets = self.spec.image_pull_secrets.iter().flatten().map(|secret| secret.name.clone());
+        let config_secrets = self
+            .spec
+            .remote_config
+            .iter()
+            .filter_map(|remote| remote.authorization_secret.as_ref())
+            .map(|secret| secret.name.clone());
+        pull_secrets.chain(config_secrets).collect()
+    }
+
+    /// Resource version of the Secrets the router config is read from, if any
+    async fn config_version(&self, ctx: Arc<Context>) -> Result<Option<String>> {
+        let Some(secret) = self.spec.remote_config.as_ref().and_then(|remote| remote.authorization_secret.as_ref()) else {
+            return Ok(None);
+        };
+        let api_secret: Api<Secret> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        let secret = api_secret.get_opt(&secret.name).await.map_err(Error::KubeError)?;
+        Ok(secret.and_then(|secret| secret.resource_version()))
+    }
+
+    pub fn apply_mode(&self) -> ApplyMode {
+        self.spec.apply_mode.unwrap_or_default()
+    }
+
+    /// Hand the desired DaemonSets off to an external applier in the Network's ConfigMap
+    async fn publish_daemonsets(&self, ctx: Arc<Context>, daemonsets: &[DaemonSet]) -> Result<()> {
+        let name = format!("{}-{}", self.name_any(), PUBLISHED_DAEMONSETS_SUFFIX);
+        let manifest = serde_yaml::to_string(&json!({
+            "apiVersion": "v1",
+            "kind": "List",
+            "items": daemonsets,
+        }))
+        .map_err(Error::YamlSerializationError)?;
+        let cm = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.clone()),
+                owner_references: Some(vec![self.controller_owner_ref(&()).unwrap()]),
+                labels: Some(BTreeMap::from([(DS_LABEL_KEY.to_string(), self.name_any())])),
+                ..ObjectMeta::default()
+            },
+            data: Some(BTreeMap::from([(PUBLISHED_DAEMONSETS_KEY.to_string(), manifest)])),
+            ..ConfigMap::default()
+        };
+        let api_cm: Api<ConfigMap> = Api::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        api_cm
+            .patch(&name, &PatchParams::apply(NETWORK_MANAGER_NAME), &Patch::Apply(&cm))
             .await
             .map_err(Error::KubeError)?;
-        Ok(Action::await_change())
+        audit::<ConfigMap>("apply", None, self.namespace().as_deref(), &name, Some(NETWORK_MANAGER_NAME));
+        ctx.publish_event(
+            Event {
+                type_: EventType::Normal,
+                reason: "DaemonSetPublished".into(),
+                note: Some(format!("Published the DaemonSets of `{}` Network in ConfigMap `{name}`", self.name_any())),
+                action: "Published".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            self.spec.event_types.as_ref(),
+        )
+        .await
     }
 
     fn socket_file_name(&self) -> String {
@@ -153,8 +1437,31 @@ impl Network {
         format!("{}/{}", self.host_socket_dir(), self.socket_file_name())
     }
 
+    fn safe_to_evict(&self) -> Option<bool> {
+        self.spec.scale_in.as_ref().and_then(|scale_in| scale_in.safe_to_evict)
+    }
+
+    /// Whether the routers register the Network prefix toward their neighbors
+    pub fn auto_register_prefix(&self) -> bool {
+        self.spec.auto_register_prefix.unwrap_or(false)
+    }
+
+    /// Whether the router pods carry the mesh readiness gate, set by their sidecar once linked
+    pub fn mesh_readiness_gate(&self) -> bool {
+        self.spec.mesh_readiness_gate.unwrap_or(false)
+    }
+
+    /// Seconds ndnd keeps forwarding after its Router is withdrawn on termination
+    pub fn withdraw_seconds(&self) -> Option<i64> {
+        self.spec.scale_in.as_ref().and_then(|scale_in| scale_in.withdraw_seconds)
+    }
+
+    pub fn config_format(&self) -> ConfigFormat {
+        self.spec.config_format.unwrap_or_default()
+    }
+
     fn config_file_name(&self) -> String {
-        format!("{}.yml", self.name_any())
+        format!("{}.{}", self.name_any(), self.config_format().extension())
     }
 
     pub fn container_config_path(&self) -> String {
@@ -211,7 +1518,13 @@ impl Network {
                     ],
                     ..PolicyRule::default()
                 },
-            ])
+            ].into_iter().chain(self.mesh_readiness_gate().then(|| PolicyRule {
+                // The sidecars set the readiness gate condition of their pod
+                api_groups: Some(vec!["".to_string()]),
+                resources: Some(vec!["pods/status".to_string()]),
+                verbs: vec!["patch".to_string()],
+                ..PolicyRule::default()
+            })).collect())
         }
     }
 
@@ -239,7 +1552,139 @@ impl Network {
         }
     }
 
-    fn create_owned_daemonset(&self, image: Option<String>, service_account: Option<String>) -> DaemonSet {
+    /// `extra_env` of the spec with the Network variables expanded. Values failing to expand,
+    /// already rejected by the spec validation, are kept as is
+    fn extra_env(&self) -> Vec<EnvVar> {
+        let lookup = |variable: &str| match variable {
+            "network.name" => Some(self.name_any()),
+            "network.namespace" => self.namespace(),
+            "network.prefix" => Some(self.spec.prefix.clone()),
+            "network.udpUnicastPort" => Some(self.spec.udp_unicast_port.to_string()),
+            _ => None,
+        };
+        self.spec
+            .extra_env
+            .iter()
+            .flatten()
+            .map(|env| EnvVar {
+                value: env.value.as_ref().map(|value| expand_env_template(value, lookup).unwrap_or_else(|_| value.clone())),
+                ..env.clone()
+            })
+            .collect()
+    }
+
+    /// Environment the init and sidecar containers derive their router identity from,
+    /// see `RouterIdentity::from_env`
+    fn router_identity_env(&self) -> Vec<EnvVar> {
+        let field_env = |name: &str, field_path: &str| EnvVar {
+            name: name.to_string(),
+            value_from: Some(EnvVarSource {
+                field_ref: Some(ObjectFieldSelector {
+                    field_path: field_path.to_string(),
+                    ..ObjectFieldSelector::default()
+                }),
+                ..EnvVarSource::default()
+            }),
+            ..EnvVar::default()
+        };
+        vec![
+            field_env("NDN_POD_NAME", "metadata.name"),
+            field_env("NDN_NODE_NAME", "spec.nodeName"),
+            EnvVar {
+                name: "NDN_ROUTER_NAME_TEMPLATE".to_string(),
+                value: Some(self.router_name_template().to_string()),
+                ..EnvVar::default()
+            },
+        ]
+    }
+
+    /// Networks among the given ones, in any namespace, serving metrics on the same host port
+    pub fn metrics_port_conflicts<'a>(&self, networks: &'a [Network]) -> Vec<&'a Network> {
+        let Some(port) = self.spec.metrics_port else {
+            return Vec::new();
+        };
+        networks
+            .iter()
+            .filter(|network| network.name_any() != self.name_any() || network.namespace() != self.namespace())
+            .filter(|network| network.spec.metrics_port == Some(port))
+            .collect()
+    }
+
+    /// `GOMAXPROCS` of ndnd, the configured worker threads or the network container's CPU limit
+    pub fn worker_threads_env(&self) -> EnvVar {
+        match self.spec.worker_threads {
+            Some(threads) => EnvVar {
+                name: "GOMAXPROCS".to_string(),
+                value: Some(threads.to_string()),
+                ..EnvVar::default()
+            },
+            None => EnvVar {
+                name: "GOMAXPROCS".to_string(),
+                value_from: Some(EnvVarSource {
+                    resource_field_ref: Some(ResourceFieldSelector {
+                        container_name: Some("network".to_string()),
+                        resource: "limits.cpu".to_string(),
+                        divisor: Some(Quantity("1".to_string())),
+                    }),
+                    ..EnvVarSource::default()
+                }),
+                ..EnvVar::default()
+            },
+        }
+    }
+
+    pub fn router_name_template(&self) -> &str {
+        self.spec.router_name_template.as_deref().unwrap_or(DEFAULT_ROUTER_NAME_TEMPLATE)
+    }
+
+    /// Init container that only makes the kubelet pull the ndnd image, if enabled
+    fn pre_pull_container(&self) -> Option<Container> {
+        let ndnd = self.spec.ndnd.clone().unwrap_or_default();
+        match ndnd.pre_pull {
+            Some(true) => Some(Container {
+                name: "pre-pull".to_string(),
+                image: Some(ndnd.image),
+                command: vec!["/ndnd".to_string(), "--help".to_string()].into(),
+                ..Container::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// DaemonSets running the Network's routers: the one from `create_owned_daemonset`,
+    /// plus one per architecture with an image override, each restricted to its nodes
+    pub fn create_owned_daemonsets(&self, image: Option<String>, service_account: Option<String>) -> Vec<DaemonSet> {
+        let mut ds = self.create_owned_daemonset(image, service_account);
+        let arch_images = self.spec.ndnd.clone().unwrap_or_default().arch_images.unwrap_or_default();
+        if arch_images.is_empty() {
+            return vec![ds];
+        }
+        let arch_ds = arch_images
+            .iter()
+            .map(|(arch, arch_image)| daemonset_for_arch(&ds, arch, arch_image))
+            .collect::<Vec<_>>();
+        if let Some(pod_spec) = ds.spec.as_mut().and_then(|spec| spec.template.spec.as_mut()) {
+            pod_spec.affinity = Some(Affinity {
+                node_affinity: Some(NodeAffinity {
+                    required_during_scheduling_ignored_during_execution: Some(NodeSelector {
+                        node_selector_terms: vec![NodeSelectorTerm {
+                            match_expressions: Some(vec![NodeSelectorRequirement {
+                                key: NODE_ARCH_LABEL_KEY.to_string(),
+                                operator: "NotIn".to_string(),
+                                values: Some(arch_images.keys().cloned().collect()),
+                            }]),
+                            ..NodeSelectorTerm::default()
+                        }],
+                    }),
+                    ..NodeAffinity::default()
+                }),
+                ..Affinity::default()
+            });
+        }
+        std::iter::once(ds).chain(arch_ds).collect()
+    }
+
+    pub fn create_owned_daemonset(&self, image: Option<String>, service_account: Option<String>) -> DaemonSet {
         let oref = self.controller_owner_ref(&()).unwrap();
         let mut labels = BTreeMap::new();
         labels.insert(DS_LABEL_KEY.to_string(), self.name_any());
@@ -260,17 +1705,36 @@ impl Network {
                 template: PodTemplateSpec {
                     metadata: Some(ObjectMeta {
                         labels: Some(labels.clone()),
+                        annotations: self.safe_to_evict().map(|safe| BTreeMap::from([(SAFE_TO_EVICT_ANNOTATION_KEY.to_string(), safe.to_string())])),
                         ..ObjectMeta::default()
                     }),
                     spec: Some(PodSpec {
                         service_account_name: service_account,
+                        termination_grace_period_seconds: self.withdraw_seconds().map(|seconds| seconds + TERMINATION_GRACE_SECONDS),
+                        image_pull_secrets: self.spec.image_pull_secrets.clone(),
+                        scheduling_gates: self.spec.scheduling_gates.clone(),
+                        readiness_gates: self.mesh_readiness_gate().then(|| vec![PodReadinessGate {
+                            condition_type: MESH_CONVERGED_CONDITION.to_string(),
+                        }]),
                         host_network: Some(true),
+                        host_pid: self.spec.host_pid,
+                        host_ipc: self.spec.host_ipc,
                         dns_policy: Some("ClusterFirstWithHostNet".to_string()),
+                        security_context: self.spec.socket_group.map(|gid| PodSecurityContext {
+                            fs_group: Some(gid),
+                            ..PodSecurityContext::default()
+                        }),
                         node_selector: self.spec.node_selector.clone(),
-                        init_containers: Some(vec![Container {
-                            name: "init".to_string(),
+                        init_containers: Some(self.pre_pull_container().into_iter().chain([Container {
+                            name: INIT_CONTAINER_NAME.to_string(),
                             image: image.clone(),
-                            command: vec!["/init".to_string(), "--output".to_string(), container_config_path.clone()].into(),
+                            command: vec![
+                                "/init".to_string(),
+                                "--output".to_string(),
+                                container_config_path.clone(),
+                                "--format".to_string(),
+                                self.config_format().as_str().to_string(),
+                            ].into(),
                             env: Some(vec![
                                 EnvVar {
                                     name: "NDN_NETWORK_NAME".to_string(),
@@ -299,34 +1763,59 @@ impl Network {
                                     ..EnvVar::default()
                                 },
                                 EnvVar {
-                                    // Router name is equal to the pod name
-                                    name: "NDN_ROUTER_NAME".to_string(),
-                                    value_from: Some(EnvVarSource {
-                                        field_ref: Some(ObjectFieldSelector {
-                                            field_path: "metadata.name".to_string(),
-                                            ..ObjectFieldSelector::default()
-                                        }),
-                                        ..EnvVarSource::default()
-                                    }),
+                                    name: "NDN_SOCKET_PATH".to_string(),
+                                    value: Some(container_socket_path.clone()),
                                     ..EnvVar::default()
                                 },
                                 EnvVar {
-                                    name: "NDN_NODE_NAME".to_string(),
-                                    value_from: Some(EnvVarSource {
-                                        field_ref: Some(ObjectFieldSelector {
-                                            field_path: "spec.nodeName".to_string(),
-                                            ..ObjectFieldSelector::default()
-                                        }),
-                                        ..EnvVarSource::default()
-                                    }),
+                                    name: "NDN_SOCKET_MODE".to_string(),
+                                    value: self.spec.socket_mode.clone(),
                                     ..EnvVar::default()
                                 },
                                 EnvVar {
-                                    name: "NDN_SOCKET_PATH".to_string(),
-                                    value: Some(container_socket_path.clone()),
+                                    name: "NDN_SOCKET_GROUP".to_string(),
+                                    value: self.spec.socket_group.map(|gid| gid.to_string()),
                                     ..EnvVar::default()
                                 },
-                            ]),
+                                EnvVar {
+                                    name: "NDN_FACE_SCHEMES".to_string(),
+                                    value: self.spec.face_schemes.as_ref().map(|schemes| json!(schemes).to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_FACES".to_string(),
+                                    value: self.spec.faces.as_ref().map(|faces| json!(faces).to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_EXCLUDE_ADDRESS_CIDRS".to_string(),
+                                    value: self.spec.exclude_address_cidrs.as_ref().map(|cidrs| cidrs.join(",")),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_CS_CAPACITY".to_string(),
+                                    value: self.spec.content_store_capacity.map(|capacity| capacity.to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_KEEP_ALIVE_INTERVAL".to_string(),
+                                    value: self.spec.keep_alive_interval_seconds.map(|interval| interval.to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_CONFIG_URL".to_string(),
+                                    value: self.spec.remote_config.as_ref().map(|remote| remote.url.clone()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_CONFIG_AUTHORIZATION".to_string(),
+                                    value_from: self.spec.remote_config.as_ref().and_then(|remote| remote.authorization_secret.clone()).map(|secret| EnvVarSource {
+                                        secret_key_ref: Some(secret),
+                                        ..EnvVarSource::default()
+                                    }),
+                                    ..EnvVar::default()
+                                },
+                            ].into_iter().chain(self.router_identity_env()).collect()),
                             security_context: Some(SecurityContext {
                                 privileged: Some(true),
                                 ..SecurityContext::default()
@@ -338,14 +1827,26 @@ impl Network {
                                     read_only: Some(false),
                                     ..VolumeMount::default()
                                 },
+                                VolumeMount {
+                                    name: "run-ndnd".to_string(),
+                                    mount_path: CONTAINER_SOCKET_DIR.to_string(),
+                                    ..VolumeMount::default()
+                                },
                             ]),
                             ..Container::default()
-                        }]),
+                        }]).collect()),
                         containers: vec![Container {
                             name: "network".to_string(),
                             image: Some(self.spec.ndnd.clone().unwrap_or_default().image),
                             command: vec!["/ndnd".to_string()].into(),
                             args: Some(vec!["daemon".to_string(), container_config_path.to_string()]),
+                            lifecycle: self.withdraw_seconds().map(|seconds| Lifecycle {
+                                pre_stop: Some(LifecycleHandler {
+                                    sleep: Some(SleepAction { seconds }),
+                                    ..LifecycleHandler::default()
+                                }),
+                                ..Lifecycle::default()
+                            }),
                             security_context: Some(SecurityContext {
                                 privileged: Some(true),
                                 ..SecurityContext::default()
@@ -364,7 +1865,9 @@ impl Network {
                                     value: Some(format!("unix://{}", container_socket_path.clone())),
                                     ..EnvVar::default()
                                 },
-                            ]),
+                                self.worker_threads_env(),
+                            ].into_iter().chain(self.extra_env()).collect()),
+                            resources: self.spec.resources.clone(),
                             volume_mounts: Some(vec![
                                 VolumeMount {
                                     name: "config".to_string(),
@@ -407,22 +1910,48 @@ impl Network {
                                     ..EnvVar::default()
                                 },
                                 EnvVar {
-                                    // Router name is equal to the pod name
-                                    name: "NDN_ROUTER_NAME".to_string(),
-                                    value_from: Some(EnvVarSource {
-                                        field_ref: Some(ObjectFieldSelector {
-                                            field_path: "metadata.name".to_string(),
-                                            ..ObjectFieldSelector::default()
-                                        }),
-                                        ..EnvVarSource::default()
-                                    }),
+                                    name: "NDN_CLIENT_TRANSPORT".to_string(),
+                                    value: Some(format!("unix://{}", container_socket_path)),
                                     ..EnvVar::default()
                                 },
                                 EnvVar {
-                                    name: "NDN_CLIENT_TRANSPORT".to_string(),
-                                    value: Some(format!("unix://{}", container_socket_path)),
+                                    name: "NDN_NETWORK_PREFIX".to_string(),
+                                    value: Some(self.spec.prefix.clone()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_METRICS_PORT".to_string(),
+                                    value: self.spec.metrics_port.map(|port| port.to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_LINK_CONCURRENCY".to_string(),
+                                    value: self.spec.link_concurrency.map(|concurrency| concurrency.to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_MESH_READINESS_GATE".to_string(),
+                                    value: Some(self.mesh_readiness_gate().to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_AUTO_REGISTER_PREFIX".to_string(),
+                                    value: Some(self.auto_register_prefix().to_string()),
+                                    ..EnvVar::default()
+                                },
+                                EnvVar {
+                                    name: "NDN_HEALTH_CHECK_INTERVAL".to_string(),
+                                    value: self.spec.health_check_interval_seconds.map(|seconds| seconds.to_string()),
                                     ..EnvVar::default()
                                 },
+                            ].into_iter().chain(self.router_identity_env()).collect()),
+                            ports: self.spec.metrics_port.map(|port| vec![
+                                ContainerPort {
+                                    name: Some("metrics".to_string()),
+                                    container_port: port,
+                                    protocol: Some("TCP".to_string()),
+                                    ..ContainerPort::default()
+                                },
                             ]),
                             volume_mounts: Some(vec![
                                 VolumeMount {
@@ -498,3 +2027,797 @@ impl TryFrom<OwnerReference> for Network {
         })
     }
 }
+
+/// Set or remove a `"true"` label of a node
+async fn patch_node_label(api_node: &Api<Node>, node: &str, key: &str, set: bool) -> Result<()> {
+    info!("{} node {} with {}", if set { "Labeling" } else { "Unlabeling" }, node, key);
+    let patch = json!({ "metadata": { "labels": { key: set.then_some("true") } } });
+    api_node
+        .patch(node, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .map_err(Error::KubeError)?;
+    audit::<Node>("merge-patch", None, None, node, None);
+    Ok(())
+}
+
+/// Value with its `${variable}` references replaced by `lookup`. Only the variables of
+/// `ENV_TEMPLATE_VARIABLES` are accepted, `$` elsewhere is kept as is
+pub fn expand_env_template(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(Error::InvalidSpec(format!("unclosed variable in `{value}`")))?;
+        let variable = &rest[start + 2..start + end];
+        let substitute = ENV_TEMPLATE_VARIABLES
+            .contains(&variable)
+            .then(|| lookup(variable))
+            .flatten()
+            .ok_or(Error::InvalidSpec(format!(
+                "unknown variable `{variable}`, expected one of {ENV_TEMPLATE_VARIABLES:?}"
+            )))?;
+        expanded.push_str(&substitute);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Hash of the pod template of the DaemonSet. Only compared with the hash of the live
+/// DaemonSet, a hash changed by another build of the operator merely defers a no-op apply
+pub fn template_hash(ds: &DaemonSet) -> String {
+    let mut hasher = DefaultHasher::new();
+    let template = ds.spec.as_ref().map(|spec| json!(spec.template)).unwrap_or_default();
+    template.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn template_hash_annotation(ds: &DaemonSet) -> Option<&String> {
+    ds.annotations().get(TEMPLATE_HASH_ANNOTATION_KEY)
+}
+
+/// Number of routers by the ndnd version they report
+pub fn ndnd_versions(routers: &[Router]) -> BTreeMap<String, u32> {
+    let mut versions = BTreeMap::new();
+    for version in routers.iter().filter_map(|router| router.status.as_ref()?.ndnd_version.clone()) {
+        *versions.entry(version).or_default() += 1;
+    }
+    versions
+}
+
+/// Highest restart count among the containers of the pods that last terminated within
+/// `RESTART_WINDOW`. The count never decreases, so containers that settled are left out
+pub fn recent_restart_count(pods: &[Pod], now: DateTime<Utc>) -> i32 {
+    let since = now - RESTART_WINDOW;
+    pods.iter()
+        .filter_map(|pod| pod.status.as_ref())
+        .flat_map(|status| {
+            status
+                .init_container_statuses
+                .iter()
+                .flatten()
+                .chain(status.container_statuses.iter().flatten())
+        })
+        .filter(|container| {
+            container
+                .last_state
+                .as_ref()
+                .and_then(|state| state.terminated.as_ref())
+                .and_then(|terminated| terminated.finished_at.as_ref())
+                .is_some_and(|finished_at| finished_at.0 >= since)
+        })
+        .map(|container| container.restart_count)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Copy of the DaemonSet restricted to the nodes of an architecture, running its ndnd image
+fn daemonset_for_arch(ds: &DaemonSet, arch: &str, ndnd_image: &str) -> DaemonSet {
+    let mut ds = ds.clone();
+    ds.metadata.name = ds.metadata.name.map(|name| format!("{name}-{arch}"));
+    ds.metadata.labels.get_or_insert_default().insert(ARCH_LABEL_KEY.to_string(), arch.to_string());
+    if let Some(spec) = ds.spec.as_mut() {
+        spec.selector.match_labels.get_or_insert_default().insert(ARCH_LABEL_KEY.to_string(), arch.to_string());
+        let template = &mut spec.template;
+        template.metadata.get_or_insert_default().labels.get_or_insert_default().insert(ARCH_LABEL_KEY.to_string(), arch.to_string());
+        if let Some(pod_spec) = template.spec.as_mut() {
+            pod_spec.node_selector.get_or_insert_default().insert(NODE_ARCH_LABEL_KEY.to_string(), arch.to_string());
+            let ndnd_containers = pod_spec
+                .containers
+                .iter_mut()
+                .filter(|container| container.name == "network")
+                .chain(pod_spec.init_containers.iter_mut().flatten().filter(|container| container.name == "pre-pull"));
+            for container in ndnd_containers {
+                container.image = Some(ndnd_image.to_string());
+            }
+        }
+    }
+    ds
+}
+
+/// Whether the API server rejected an update of a DaemonSet's immutable selector
+pub fn is_selector_immutable(error: &kube::Error) -> bool {
+    match error {
+        kube::Error::Api(response) => {
+            response.code == 422 && response.message.contains("spec.selector") && response.message.contains("field is immutable")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{create_owned_router, RouterIdentity, RouterSpec, RouterStatus};
+
+    #[test]
+    fn face_policy_checks_allowed_and_denied_addresses() {
+        let policy = FacePolicy {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.1.0.0/16".parse().unwrap()],
+        };
+        assert!(policy.allows("udp://10.0.0.1:6363"));
+        assert!(!policy.allows("udp://10.1.0.1:6363"));
+        assert!(!policy.allows("udp://192.168.0.1:6363"));
+        assert!(!policy.allows("udp://[fd00::1]:6363"));
+        // Names can't be checked against an allow list
+        assert!(!policy.allows("udp://router.example.com:6363"));
+        assert!(FacePolicy::default().allows("udp://router.example.com:6363"));
+    }
+
+    #[test]
+    fn face_schemes_accept_known_schemes() {
+        let schemes = FaceSchemes { udp4: Some("udp4".to_string()), udp6: Some("udp6".to_string()), ..FaceSchemes::default() };
+        assert!(schemes.validate().is_ok());
+        assert_eq!(FaceSchemes::default().udp4(), "udp");
+    }
+
+    #[test]
+    fn face_schemes_reject_custom_schemes_unless_allowed() {
+        let schemes = FaceSchemes { udp4: Some("quic".to_string()), ..FaceSchemes::default() };
+        assert!(schemes.validate().is_err());
+        let schemes = FaceSchemes { allow_custom: Some(true), ..schemes };
+        assert!(schemes.validate().is_ok());
+    }
+
+    fn network(namespace: &str, name: &str, metrics_port: Option<i32>) -> Network {
+        let mut network = Network::new(name, NetworkSpec { metrics_port, ..NetworkSpec::default() });
+        network.metadata.namespace = Some(namespace.to_string());
+        network
+    }
+
+    #[test]
+    fn metrics_port_conflicts_span_namespaces() {
+        let networks = vec![
+            network("a", "net", Some(9100)),
+            network("b", "net", Some(9100)),
+            network("b", "other", Some(9200)),
+            network("c", "plain", None),
+        ];
+        let conflicts = networks[0].metrics_port_conflicts(&networks);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].namespace().as_deref(), Some("b"));
+        assert!(networks[3].metrics_port_conflicts(&networks).is_empty());
+    }
+
+    #[test]
+    fn metrics_port_must_differ_from_the_udp_port() {
+        let spec = NetworkSpec { udp_unicast_port: 6363, metrics_port: Some(6363), ..NetworkSpec::default() };
+        assert!(spec.validate().is_err());
+        let spec = NetworkSpec { metrics_port: Some(70000), ..spec };
+        assert!(spec.validate().is_err());
+        let spec = NetworkSpec { metrics_port: Some(9100), ..spec };
+        assert!(spec.validate().is_ok());
+    }
+
+    fn owned_network(spec: NetworkSpec) -> Network {
+        let mut network = Network::new("net", spec);
+        network.metadata.namespace = Some("ns".to_string());
+        network.metadata.uid = Some("uid".to_string());
+        network
+    }
+
+    /// The DaemonSets are the core of the operator and must not depend on optional features,
+    /// this also runs with `--no-default-features`
+    #[test]
+    fn daemonset_runs_the_init_and_network_containers() {
+        let network = owned_network(NetworkSpec { prefix: "/net".to_string(), udp_unicast_port: 6363, ..NetworkSpec::default() });
+        let ds = network.create_owned_daemonset(Some("operator:latest".to_string()), None);
+        let pod_spec = ds.spec.unwrap().template.spec.unwrap();
+        let init = pod_spec.init_containers.unwrap();
+        assert!(init.iter().any(|container| container.name == INIT_CONTAINER_NAME));
+        let network_container = pod_spec.containers.iter().find(|container| container.name == "network").unwrap();
+        assert!(network_container.ports.iter().flatten().any(|port| port.host_port == Some(6363)));
+        assert_eq!(pod_spec.host_network, Some(true));
+    }
+
+    #[test]
+    fn config_format_selects_the_config_file() {
+        for (format, file, arg) in [(None, "net.yml", "yaml"), (Some(ConfigFormat::Json), "net.json", "json")] {
+            let network = owned_network(NetworkSpec { config_format: format, ..NetworkSpec::default() });
+            assert_eq!(network.container_config_path(), format!("{CONTAINER_CONFIG_DIR}/{file}"));
+            assert_eq!(network.host_config_path(), format!("{HOST_CONFIG_ROOT_DIR}/ns/{file}"));
+            let ds = network.create_owned_daemonset(None, None);
+            let init = ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap();
+            let command = init.into_iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap().command.unwrap();
+            assert!(command.windows(2).any(|args| args == ["--format", arg]), "{command:?}");
+            assert!(command.windows(2).any(|args| args[0] == "--output" && args[1].ends_with(file)), "{command:?}");
+        }
+    }
+
+    #[test]
+    fn scheduling_gates_reach_the_pod_template() {
+        let gates = vec![PodSchedulingGate { name: "example.com/capacity".to_string() }];
+        let network = owned_network(NetworkSpec { scheduling_gates: Some(gates.clone()), ..NetworkSpec::default() });
+        let ds = network.create_owned_daemonset(None, None);
+        assert_eq!(ds.spec.unwrap().template.spec.unwrap().scheduling_gates, Some(gates));
+        assert!(network.spec.validate().is_ok());
+        let spec = NetworkSpec { scheduling_gates: Some(vec![PodSchedulingGate { name: "not a gate".to_string() }]), ..NetworkSpec::default() };
+        assert!(spec.validate().is_err());
+    }
+
+    #[test]
+    fn ndnd_image_is_pre_pulled_first_if_enabled() {
+        let init_containers = |network: &Network| {
+            let ds = network.create_owned_daemonset(None, None);
+            ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap()
+        };
+        assert!(init_containers(&owned_network(NetworkSpec::default())).iter().all(|container| container.name != "pre-pull"));
+        let ndnd = Ndnd { image: "ndnd:1".to_string(), pre_pull: Some(true), ..Ndnd::default() };
+        let network = owned_network(NetworkSpec { ndnd: Some(ndnd), ..NetworkSpec::default() });
+        let init = init_containers(&network);
+        assert_eq!(init[0].name, "pre-pull");
+        assert_eq!(init[0].image.as_deref(), Some("ndnd:1"));
+        assert_eq!(init[1].name, INIT_CONTAINER_NAME);
+    }
+
+    #[test]
+    fn node_selector_labels_are_validated() {
+        let spec = |key: &str, value: &str| NetworkSpec {
+            node_selector: Some(BTreeMap::from([(key.to_string(), value.to_string())])),
+            ..NetworkSpec::default()
+        };
+        for (key, value) in [("kubernetes.io/os", "linux"), ("ndn", ""), ("example.com/tier", "edge_1.a")] {
+            assert!(spec(key, value).validate().is_ok(), "{key}={value}");
+        }
+        for (key, value) in [("Example_.com/tier", "edge"), ("/tier", "edge"), ("a/b/c", "edge"), ("tier!", "edge")] {
+            let error = spec(key, value).validate().unwrap_err().to_string();
+            assert!(error.contains("node selector key"), "{key}: {error}");
+        }
+        for value in ["v".repeat(64), "-edge".to_string(), "edge zone".to_string()] {
+            let error = spec("tier", &value).validate().unwrap_err().to_string();
+            assert!(error.contains("node selector value"), "{value}: {error}");
+        }
+    }
+
+    #[test]
+    fn init_container_and_pod_sync_derive_the_same_router_identity() {
+        let network = owned_network(NetworkSpec { router_name_template: Some("{network}-{node}".to_string()), ..NetworkSpec::default() });
+        let pod = Pod {
+            metadata: ObjectMeta { name: Some("net-x7k2p".to_string()), ..ObjectMeta::default() },
+            spec: Some(PodSpec { node_name: Some("Node-1.example.com".to_string()), ..PodSpec::default() }),
+            ..Pod::default()
+        };
+        let ds = network.create_owned_daemonset(None, None);
+        let init = ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap();
+        let init = init.into_iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap();
+        // The values the kubelet sets in the pod
+        let env = |name: &str| {
+            let var = init.env.iter().flatten().find(|env| env.name == name).unwrap();
+            let field_path = var.value_from.as_ref().and_then(|from| from.field_ref.as_ref()).map(|field| field.field_path.as_str());
+            match field_path {
+                Some("metadata.name") => pod.name_any(),
+                Some("spec.nodeName") => pod.spec.as_ref().unwrap().node_name.clone().unwrap(),
+                _ => var.value.clone().unwrap(),
+            }
+        };
+        let from_env = RouterIdentity::new(&env("NDN_ROUTER_NAME_TEMPLATE"), &env("NDN_NETWORK_NAME"), &env("NDN_NODE_NAME"), &env("NDN_POD_NAME")).unwrap();
+        let of_pod = RouterIdentity::of_pod(&network, &pod).unwrap();
+        assert_eq!(from_env, of_pod);
+        assert_eq!(of_pod.name, "net-node-1-example-com");
+        assert_eq!(create_owned_router(&network, &of_pod).name_any(), of_pod.name);
+    }
+
+    #[test]
+    fn node_label_key_includes_the_namespace() {
+        assert_eq!(owned_network(NetworkSpec::default()).node_label_key().as_deref(), Some("named-data.net/network-ns.net"));
+        let mut long = Network::new(&"n".repeat(60), NetworkSpec::default());
+        long.metadata.namespace = Some("ns".to_string());
+        assert_eq!(long.node_label_key(), None);
+    }
+
+    #[test]
+    fn content_store_capacity_reaches_the_init_container() {
+        let network = owned_network(NetworkSpec { content_store_capacity: Some(4096), ..NetworkSpec::default() });
+        let ds = network.create_owned_daemonset(None, None);
+        let init = ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap();
+        let init = init.iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap();
+        let capacity = init.env.iter().flatten().find(|env| env.name == "NDN_CS_CAPACITY").unwrap();
+        assert_eq!(capacity.value.as_deref(), Some("4096"));
+        let error = NetworkSpec { content_store_capacity: Some(0), ..NetworkSpec::default() }.validate().unwrap_err();
+        assert!(error.to_string().contains("content store capacity"));
+    }
+
+    #[test]
+    fn keep_alive_interval_reaches_the_init_container() {
+        let network = owned_network(NetworkSpec { keep_alive_interval_seconds: Some(10), ..NetworkSpec::default() });
+        let ds = network.create_owned_daemonset(None, None);
+        let init = ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap();
+        let init = init.iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap();
+        let interval = init.env.iter().flatten().find(|env| env.name == "NDN_KEEP_ALIVE_INTERVAL").unwrap();
+        assert_eq!(interval.value.as_deref(), Some("10"));
+        for interval in [0, 61] {
+            let spec = NetworkSpec { keep_alive_interval_seconds: Some(interval), ..NetworkSpec::default() };
+            assert!(spec.validate().unwrap_err().to_string().contains("keep-alive interval"));
+        }
+        assert!(NetworkSpec { keep_alive_interval_seconds: Some(60), ..NetworkSpec::default() }.validate().is_ok());
+    }
+
+    #[test]
+    fn worker_threads_reach_the_ndnd_container() {
+        let gomaxprocs = |network: &Network| {
+            let ds = network.create_owned_daemonset(None, None);
+            let containers = ds.spec.unwrap().template.spec.unwrap().containers;
+            let ndnd = containers.into_iter().find(|container| container.name == "network").unwrap();
+            ndnd.env.into_iter().flatten().find(|env| env.name == "GOMAXPROCS").unwrap()
+        };
+        let configured = gomaxprocs(&owned_network(NetworkSpec { worker_threads: Some(4), ..NetworkSpec::default() }));
+        assert_eq!(configured.value.as_deref(), Some("4"));
+        // The CPU limit of the container otherwise
+        let limit = gomaxprocs(&owned_network(NetworkSpec::default())).value_from.unwrap().resource_field_ref.unwrap();
+        assert_eq!((limit.container_name.as_deref(), limit.resource.as_str()), (Some("network"), "limits.cpu"));
+        let error = NetworkSpec { worker_threads: Some(0), ..NetworkSpec::default() }.validate().unwrap_err();
+        assert!(error.to_string().contains("worker threads"));
+    }
+
+    #[test]
+    fn scale_in_options_reach_the_pod_template() {
+        let network = owned_network(NetworkSpec {
+            scale_in: Some(ScaleIn { safe_to_evict: Some(false), withdraw_seconds: Some(10) }),
+            ..NetworkSpec::default()
+        });
+        let template = network.create_owned_daemonset(None, None).spec.unwrap().template;
+        let annotations = template.metadata.unwrap().annotations.unwrap();
+        assert_eq!(annotations.get(SAFE_TO_EVICT_ANNOTATION_KEY).map(String::as_str), Some("false"));
+        let pod_spec = template.spec.unwrap();
+        // ndnd keeps forwarding for the withdrawal, then gets the default grace period to stop
+        assert_eq!(pod_spec.termination_grace_period_seconds, Some(10 + TERMINATION_GRACE_SECONDS));
+        let ndnd = pod_spec.containers.iter().find(|container| container.name == "network").unwrap();
+        let pre_stop = ndnd.lifecycle.clone().unwrap().pre_stop.unwrap();
+        assert_eq!(pre_stop.sleep.map(|sleep| sleep.seconds), Some(10));
+        // Neither is set by default
+        let pod_spec = owned_network(NetworkSpec::default()).create_owned_daemonset(None, None).spec.unwrap().template.spec.unwrap();
+        assert!(pod_spec.termination_grace_period_seconds.is_none());
+        let error = NetworkSpec { scale_in: Some(ScaleIn { withdraw_seconds: Some(0), ..ScaleIn::default() }), ..NetworkSpec::default() }.validate();
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn remote_config_is_downloaded_by_the_init_container() {
+        let remote = |url: &str| NetworkSpec {
+            remote_config: Some(RemoteConfig {
+                url: url.to_string(),
+                authorization_secret: Some(SecretKeySelector { name: "config-token".to_string(), key: "header".to_string(), ..SecretKeySelector::default() }),
+            }),
+            ..NetworkSpec::default()
+        };
+        let network = owned_network(remote("https://configs.example.com/ndnd.yml"));
+        let ds = network.create_owned_daemonset(None, None);
+        let init = ds.spec.unwrap().template.spec.unwrap().init_containers.unwrap();
+        let env = init.into_iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap().env.unwrap();
+        let env = |name: &str| env.iter().find(|env| env.name == name).unwrap().clone();
+        assert_eq!(env("NDN_CONFIG_URL").value.as_deref(), Some("https://configs.example.com/ndnd.yml"));
+        let secret = env("NDN_CONFIG_AUTHORIZATION").value_from.unwrap().secret_key_ref.unwrap();
+        assert_eq!((secret.name.as_str(), secret.key.as_str()), ("config-token", "header"));
+        // The Secret is watched, so the routers restart when it rotates
+        assert!(network.referenced_secrets().contains("config-token"));
+        for url in ["ftp://configs.example.com/ndnd.yml", "configs.example.com/ndnd.yml", "https:///ndnd.yml"] {
+            assert!(remote(url).validate().is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn env_templates_expand_the_network_variables() {
+        let lookup = |variable: &str| Some(format!("<{variable}>"));
+        assert_eq!(
+            expand_env_template("${network.name}.${network.namespace}:${network.udpUnicastPort}", lookup).unwrap(),
+            "<network.name>.<network.namespace>:<network.udpUnicastPort>"
+        );
+        // Other dollar signs are kept
+        assert_eq!(expand_env_template("$HOME costs $5", lookup).unwrap(), "$HOME costs $5");
+        assert!(expand_env_template("${network.name", lookup).is_err());
+        assert!(expand_env_template("${pod.name}", lookup).is_err());
+    }
+
+    #[test]
+    fn extra_env_reaches_the_ndnd_container() {
+        let network = owned_network(NetworkSpec {
+            extra_env: Some(vec![EnvVar { name: "ROUTER_PREFIX".to_string(), value: Some("${network.prefix}/r".to_string()), ..EnvVar::default() }]),
+            ..NetworkSpec::default()
+        });
+        let ds = network.create_owned_daemonset(None, None);
+        let containers = ds.spec.unwrap().template.spec.unwrap().containers;
+        let ndnd = containers.iter().find(|container| container.name == "network").unwrap();
+        let env = ndnd.env.iter().flatten().find(|env| env.name == "ROUTER_PREFIX").unwrap();
+        assert_eq!(env.value, Some(format!("{}/r", network.spec.prefix)));
+    }
+
+    #[test]
+    fn daemonsets_are_split_by_architecture() {
+        let ndnd = Ndnd {
+            arch_images: Some(BTreeMap::from([("arm64".to_string(), "ndnd:arm64".to_string())])),
+            ..Ndnd::default()
+        };
+        let network = owned_network(NetworkSpec { ndnd: Some(ndnd), ..NetworkSpec::default() });
+        let daemonsets = network.create_owned_daemonsets(None, None);
+        let names: Vec<String> = daemonsets.iter().map(|ds| ds.name_any()).collect();
+        assert_eq!(names, ["net", "net-arm64"]);
+        let arm = daemonsets[1].spec.as_ref().unwrap().template.spec.as_ref().unwrap();
+        assert_eq!(arm.node_selector.as_ref().unwrap().get(NODE_ARCH_LABEL_KEY).map(String::as_str), Some("arm64"));
+        let ndnd_image = arm.containers.iter().find(|container| container.name == "network").unwrap().image.as_deref();
+        assert_eq!(ndnd_image, Some("ndnd:arm64"));
+    }
+
+    fn pod_restarted(restart_count: i32, finished_at: DateTime<Utc>) -> Pod {
+        serde_json::from_value(json!({
+            "status": {
+                "containerStatuses": [{
+                    "name": "network",
+                    "image": "ndnd",
+                    "imageID": "",
+                    "ready": true,
+                    "restartCount": restart_count,
+                    "lastState": { "terminated": { "exitCode": 1, "finishedAt": finished_at } },
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_recent_restarts_count() {
+        let now = Utc::now();
+        let settled = pod_restarted(20, now - chrono::Duration::hours(1));
+        let crashing = pod_restarted(7, now - chrono::Duration::minutes(1));
+        assert_eq!(recent_restart_count(std::slice::from_ref(&settled), now), 0);
+        assert_eq!(recent_restart_count(&[settled, crashing], now), 7);
+    }
+
+    #[test]
+    fn overlapping_networks_leave_out_the_network_itself() {
+        let prefixed = |name: &str, prefix: &str| Network::new(name, NetworkSpec { prefix: prefix.to_string(), ..NetworkSpec::default() });
+        let networks = vec![prefixed("a", "/ndn"), prefixed("b", "/ndn/edu"), prefixed("c", "/other")];
+        let overlapping: Vec<String> = networks[0].overlapping(&networks).iter().map(|network| network.name_any()).collect();
+        assert_eq!(overlapping, ["b"]);
+    }
+
+    #[test]
+    fn socket_mode_and_group_are_set_before_ndnd_starts() {
+        let network = owned_network(NetworkSpec {
+            socket_mode: Some("0750".to_string()),
+            socket_group: Some(2000),
+            ..NetworkSpec::default()
+        });
+        let pod_spec = network.create_owned_daemonset(None, None).spec.unwrap().template.spec.unwrap();
+        assert_eq!(pod_spec.security_context.and_then(|context| context.fs_group), Some(2000));
+        let init = pod_spec.init_containers.unwrap().into_iter().find(|container| container.name == INIT_CONTAINER_NAME).unwrap();
+        let env: BTreeMap<String, Option<String>> = init.env.unwrap().into_iter().map(|env| (env.name, env.value)).collect();
+        assert_eq!(env["NDN_SOCKET_MODE"].as_deref(), Some("0750"));
+        assert_eq!(env["NDN_SOCKET_GROUP"].as_deref(), Some("2000"));
+        assert!(init.volume_mounts.unwrap().iter().any(|mount| mount.mount_path == CONTAINER_SOCKET_DIR));
+    }
+
+    /// Client answering DaemonSet lists with no items and DaemonSet applies with the 422
+    /// the API server returns for a changed selector
+    fn immutable_selector_client() -> kube::Client {
+        let service = tower::service_fn(|request: http::Request<kube::client::Body>| async move {
+            let (status, body) = match *request.method() {
+                http::Method::GET => (200, json!({ "apiVersion": "apps/v1", "kind": "DaemonSetList", "metadata": {}, "items": [] })),
+                _ => (422, json!({
+                    "apiVersion": "v1",
+                    "kind": "Status",
+                    "status": "Failure",
+                    "message": "DaemonSet.apps \"net\" is invalid: spec.selector: Invalid value: {}: field is immutable",
+                    "reason": "Invalid",
+                    "code": 422,
+                })),
+            };
+            let response = http::Response::builder()
+                .status(status)
+                .body(kube::client::Body::from(body.to_string().into_bytes()))
+                .unwrap();
+            Ok::<_, std::convert::Infallible>(response)
+        });
+        kube::Client::new(service, "ns")
+    }
+
+    #[test]
+    fn conditions_are_replaced_in_place() {
+        let mut network = owned_network(NetworkSpec::default());
+        network.status = serde_json::from_value(json!({ "conditions": [] })).unwrap();
+        let stalled = network.stalled_condition(3, "failed");
+        network.status.as_mut().unwrap().conditions = Some(vec![network.degraded_condition(5), stalled.clone()]);
+        let conditions = network.with_conditions(vec![network.degraded_condition(0), network.partitioned_condition(&[])]);
+        let types: Vec<&str> = conditions.iter().map(|condition| condition.type_.as_str()).collect();
+        assert_eq!(types, vec![DEGRADED_CONDITION, STALLED_CONDITION, MESH_PARTITIONED_CONDITION]);
+        assert_eq!(conditions[0].status, "False");
+        assert_eq!(conditions[1], stalled);
+    }
+
+    #[test]
+    fn ndnd_versions_count_the_reporting_routers() {
+        let router = |version: Option<&str>| {
+            let mut router = Router::new("r", RouterSpec::default());
+            router.status = Some(RouterStatus { ndnd_version: version.map(String::from), ..RouterStatus::default() });
+            router
+        };
+        let routers = vec![
+            router(Some("1.4.0")),
+            router(None),
+            router(Some("1.5.0")),
+            router(Some("1.4.0")),
+            Router::new("new", RouterSpec::default()),
+        ];
+        assert_eq!(ndnd_versions(&routers), BTreeMap::from([("1.4.0".to_string(), 2), ("1.5.0".to_string(), 1)]));
+        assert!(ndnd_versions(&[]).is_empty());
+    }
+
+    #[test]
+    fn routers_of_nodes_no_longer_selected_are_deselected() {
+        let node = |name: &str, labels: &[(&str, &str)]| Node {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some(labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+                ..ObjectMeta::default()
+            },
+            ..Node::default()
+        };
+        let router = |node: &str| Router::new(node, RouterSpec { prefix: format!("/net/{node}"), node_name: node.to_string() });
+        let nodes = vec![node("a", &[("zone", "x"), ("tier", "edge")]), node("b", &[("zone", "x")]), node("c", &[("zone", "y")])];
+        // The Router of `gone` has no node left, its pod cleanup deletes it
+        let routers = vec![router("a"), router("b"), router("c"), router("gone")];
+        let deselected = |selector: &[(&str, &str)]| {
+            let node_selector = selector.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+            let network = owned_network(NetworkSpec { node_selector: Some(node_selector), ..NetworkSpec::default() });
+            network.deselected_routers(&routers, &nodes).into_iter().map(|router| router.name_any()).collect::<Vec<_>>()
+        };
+        assert_eq!(deselected(&[("zone", "x")]), vec!["c"]);
+        // Narrowing the selector deselects more routers
+        assert_eq!(deselected(&[("zone", "x"), ("tier", "edge")]), vec!["b", "c"]);
+        // Widening it deselects none
+        assert!(deselected(&[]).is_empty());
+    }
+
+    #[test]
+    fn mesh_readiness_gate_holds_the_router_pods() {
+        let gate = |enabled: Option<bool>| {
+            let network = owned_network(NetworkSpec { mesh_readiness_gate: enabled, ..NetworkSpec::default() });
+            let daemonsets = network.create_owned_daemonsets(None, None);
+            let pod = daemonsets[0].spec.as_ref().unwrap().template.spec.clone().unwrap();
+            pod.readiness_gates.unwrap_or_default().into_iter().map(|gate| gate.condition_type).collect::<Vec<_>>()
+        };
+        assert_eq!(gate(Some(true)), vec![MESH_CONVERGED_CONDITION]);
+        assert!(gate(None).is_empty());
+        assert!(gate(Some(false)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn immutable_selector_is_reported_in_the_outcome() {
+        let network = owned_network(NetworkSpec::default());
+        let ds_data = network.create_owned_daemonsets(None, None);
+        let api_ds: Api<DaemonSet> = Api::namespaced(immutable_selector_client(), "ns");
+        let outcome = network.apply_daemonsets(&api_ds, &ds_data, true).await.unwrap();
+        assert_eq!(outcome.selector_immutable.as_deref(), Some("net"));
+        assert!(outcome.applied.is_empty());
+    }
+
+    /// API server failing the request numbered `fail_at`, counting from 1, and recording the
+    /// bodies of the status patches. Applies are echoed, and lists are empty
+    fn failing_client(
+        fail_at: usize,
+        requests: Arc<std::sync::atomic::AtomicUsize>,
+        status_patches: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+        network: serde_json::Value,
+    ) -> kube::Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let (requests, status_patches, network) = (requests.clone(), status_patches.clone(), network.clone());
+            async move {
+                let number = requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                let is_status = request.uri().path().ends_with("/status");
+                let is_get = request.method() == http::Method::GET;
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let (status, body) = if number == fail_at {
+                    (500, json!({ "apiVersion": "v1", "kind": "Status", "status": "Failure", "message": "injected", "reason": "InternalError", "code": 500 }))
+                } else if is_status {
+                    status_patches.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                    (200, network)
+                } else if is_get {
+                    (200, json!({ "apiVersion": "v1", "kind": "List", "metadata": {}, "items": [] }))
+                } else {
+                    (200, serde_json::from_slice(&body).unwrap())
+                };
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(kube::client::Body::from(body.to_string().into_bytes()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        kube::Client::new(service, "ns")
+    }
+
+    #[tokio::test]
+    async fn failed_applies_are_never_recorded_as_created() {
+        let mut network = owned_network(NetworkSpec::default());
+        network.status = serde_json::from_value(json!({ "dsCreated": true })).unwrap();
+        let apply = async |fail_at: usize| {
+            let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let status_patches = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let client = failing_client(fail_at, requests.clone(), status_patches.clone(), serde_json::to_value(&network).unwrap());
+            let ctx = crate::controller::State::default().to_context(client).await;
+            let sa = network.create_owned_sa();
+            let role = network.create_owned_role();
+            let role_binding = network.create_owned_role_binding(sa.name_any(), role.name_any());
+            let ds_data = network.create_owned_daemonsets(None, None);
+            let result = network.apply_and_record(ctx, sa, role, role_binding, &ds_data, true).await;
+            let status_patches = status_patches.lock().unwrap().clone();
+            (result, requests.load(std::sync::atomic::Ordering::SeqCst), status_patches)
+        };
+        let (result, steps, status_patches) = apply(0).await;
+        assert_eq!(result.unwrap().applied, vec!["net"]);
+        assert!(status_patches.is_empty());
+        // A failure at any step of the apply is recorded before it is returned
+        for fail_at in 1..=steps {
+            let (result, _, status_patches) = apply(fail_at).await;
+            assert!(result.is_err(), "step {fail_at}");
+            assert_eq!(status_patches, vec![json!({ "status": { "dsCreated": false } })], "step {fail_at}");
+        }
+    }
+
+    /// API server answering each request with `respond`, from its method, path and body
+    fn api_client<F>(respond: F) -> kube::Client
+    where
+        F: Fn(&http::Method, &str, serde_json::Value) -> (u16, serde_json::Value) + Clone + Send + Sync + 'static,
+    {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let respond = respond.clone();
+            async move {
+                let (method, path) = (request.method().clone(), request.uri().path().to_string());
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let body = serde_json::from_slice(&body).unwrap_or_default();
+                let (status, body) = respond(&method, &path, body);
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(kube::client::Body::from(body.to_string().into_bytes()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        kube::Client::new(service, "ns")
+    }
+
+    fn not_found() -> (u16, serde_json::Value) {
+        (404, json!({ "apiVersion": "v1", "kind": "Status", "status": "Failure", "reason": "NotFound", "code": 404 }))
+    }
+
+    #[tokio::test]
+    async fn missing_pull_secrets_are_reported() {
+        let network = owned_network(NetworkSpec {
+            image_pull_secrets: Some(vec![LocalObjectReference { name: "present".to_string() }, LocalObjectReference { name: "missing".to_string() }]),
+            ..NetworkSpec::default()
+        });
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = api_client({
+            let events = events.clone();
+            move |method, path, body| match (method.as_str(), path) {
+                ("GET", "/api/v1/namespaces/ns/secrets/present") => (200, json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "present" } })),
+                ("GET", _) => not_found(),
+                _ => {
+                    events.lock().unwrap().push(body.clone());
+                    (201, body)
+                }
+            }
+        });
+        let ctx = crate::controller::State::default().to_context(client).await;
+        network.check_pull_secrets(ctx).await.unwrap();
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["reason"], "MissingPullSecret");
+        assert!(events[0]["note"].as_str().unwrap().contains("`missing`"));
+        // The DaemonSet still references it, the pods pull once it is created
+        let ds = network.create_owned_daemonset(None, None);
+        let pull_secrets = ds.spec.unwrap().template.spec.unwrap().image_pull_secrets.unwrap();
+        assert_eq!(pull_secrets.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn derived_networks_override_their_template() {
+        let template = Network::new("edge", NetworkSpec {
+            ndnd: Some(Ndnd { image: "ndnd:1".to_string(), pre_pull: Some(true), ..Ndnd::default() }),
+            face_allow_cidrs: Some(vec!["10.0.0.0/8".to_string(), "192.168.0.0/16".to_string()]),
+            metrics_port: Some(9100),
+            ..NetworkSpec::default()
+        });
+        let mut derived = owned_network(NetworkSpec {
+            prefix: "/derived".to_string(),
+            udp_unicast_port: 6364,
+            ndnd: Some(Ndnd { image: "ndnd:2".to_string(), pre_pull: None, ..Ndnd::default() }),
+            face_allow_cidrs: Some(vec!["172.16.0.0/12".to_string()]),
+            ..NetworkSpec::default()
+        });
+        derived.metadata.annotations = Some(BTreeMap::from([(TEMPLATE_ANNOTATION_KEY.to_string(), "edge".to_string())]));
+        let client = api_client({
+            let template = serde_json::to_value(&template).unwrap();
+            move |_, path, _| match path {
+                "/apis/named-data.net/v1alpha1/namespaces/ns/networks/edge" => (200, template.clone()),
+                _ => not_found(),
+            }
+        });
+        let resolved = derived.resolve_template(client.clone()).await.unwrap();
+        assert_eq!((resolved.spec.prefix.as_str(), resolved.spec.udp_unicast_port), ("/derived", 6364));
+        // Objects are merged field by field, lists replaced as a whole
+        let ndnd = resolved.spec.ndnd.unwrap();
+        assert_eq!((ndnd.image.as_str(), ndnd.pre_pull), ("ndnd:2", Some(true)));
+        assert_eq!(resolved.spec.face_allow_cidrs, Some(vec!["172.16.0.0/12".to_string()]));
+        assert_eq!(resolved.spec.metrics_port, Some(9100));
+        // Networks without the annotation are kept as they are
+        assert_eq!(owned_network(NetworkSpec::default()).resolve_template(client.clone()).await.unwrap().spec.metrics_port, None);
+        derived.metadata.annotations = Some(BTreeMap::from([(TEMPLATE_ANNOTATION_KEY.to_string(), "core".to_string())]));
+        assert!(derived.resolve_template(client).await.unwrap_err().to_string().contains("`core` not found"));
+        let mut chained = template.clone();
+        chained.metadata.annotations = Some(BTreeMap::from([(TEMPLATE_ANNOTATION_KEY.to_string(), "base".to_string())]));
+        assert!(derived.with_template(&chained).is_err());
+    }
+
+    #[tokio::test]
+    async fn published_daemonsets_are_left_to_the_external_applier() {
+        let network = owned_network(NetworkSpec { apply_mode: Some(ApplyMode::Publish), ..NetworkSpec::default() });
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = api_client({
+            let requests = requests.clone();
+            move |method, path, body| {
+                requests.lock().unwrap().push((method.to_string(), path.to_string(), body.clone()));
+                (200, body)
+            }
+        });
+        let ctx = crate::controller::State::default().to_context(client).await;
+        let sa = network.create_owned_sa();
+        let role = network.create_owned_role();
+        let role_binding = network.create_owned_role_binding(sa.name_any(), role.name_any());
+        let ds_data = network.create_owned_daemonsets(None, None);
+        let outcome = network.apply_owned(ctx, sa, role, role_binding, &ds_data, true).await.unwrap();
+        assert!(outcome.applied.is_empty());
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|(_, path, _)| !path.contains("/daemonsets")), "{requests:?}");
+        let (method, _, config_map) = requests.iter().find(|(_, path, _)| path == "/api/v1/namespaces/ns/configmaps/net-daemonsets").unwrap();
+        assert_eq!(method, "PATCH");
+        let manifest: serde_json::Value = serde_yaml::from_str(config_map["data"][PUBLISHED_DAEMONSETS_KEY].as_str().unwrap()).unwrap();
+        assert_eq!(manifest["items"][0], serde_json::to_value(&ds_data[0]).unwrap());
+        // Garbage collected with the Network
+        assert_eq!(config_map["metadata"]["ownerReferences"][0]["uid"], "uid");
+    }
+
+    #[tokio::test]
+    async fn shared_host_namespaces_are_warned_about() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = api_client({
+            let events = events.clone();
+            move |_, _, body| {
+                events.lock().unwrap().push(body.clone());
+                (201, body)
+            }
+        });
+        let ctx = crate::controller::State::default().to_context(client).await;
+        owned_network(NetworkSpec::default()).check_host_namespaces(ctx.clone()).await.unwrap();
+        assert!(events.lock().unwrap().is_empty());
+        let network = owned_network(NetworkSpec { host_pid: Some(true), host_ipc: Some(true), ..NetworkSpec::default() });
+        let pod_spec = network.create_owned_daemonset(None, None).spec.unwrap().template.spec.unwrap();
+        assert_eq!((pod_spec.host_pid, pod_spec.host_ipc, pod_spec.host_network), (Some(true), Some(true), Some(true)));
+        network.check_host_namespaces(ctx).await.unwrap();
+        let events = events.lock().unwrap();
+        assert_eq!((events[0]["type"].as_str(), events[0]["reason"].as_str()), (Some("Warning"), Some("HostNamespacesShared")));
+        assert!(events[0]["note"].as_str().unwrap().contains("host PID and IPC namespaces"));
+    }
+}
@@ -0,0 +1,162 @@
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::runtime::events::{Event, EventType};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::*;
+
+/// Reason of the notifications of failed reconciles, which have no event
+pub static RECONCILE_FAILED_REASON: &str = "ReconcileFailed";
+
+/// JSON payload posted for a significant event. `text` summarizes the other fields for
+/// Slack incoming webhooks, which only display it
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub text: String,
+    pub reason: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub message: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub cluster: Option<String>,
+}
+
+impl Notification {
+    pub fn new(event: &Event, reference: &ObjectReference, cluster: Option<String>) -> Self {
+        let type_ = match event.type_ {
+            EventType::Normal => "Normal",
+            EventType::Warning => "Warning",
+        };
+        let kind = reference.kind.clone().unwrap_or_default();
+        let name = reference.name.clone().unwrap_or_default();
+        let message = event.note.clone().unwrap_or_default();
+        let text = format!(
+            "{type_} {} on {kind} {}{name}{}: {message}",
+            event.reason,
+            reference.namespace.as_ref().map(|namespace| format!("{namespace}/")).unwrap_or_default(),
+            cluster.as_ref().map(|cluster| format!(" in cluster {cluster}")).unwrap_or_default(),
+        );
+        Self {
+            text,
+            reason: event.reason.clone(),
+            type_: type_.to_string(),
+            message,
+            kind,
+            namespace: reference.namespace.clone(),
+            name,
+            cluster,
+        }
+    }
+}
+
+/// Posts notifications of significant events to a webhook, e.g. a Slack incoming webhook.
+/// Each reason is sent at most once per interval for an object, a lasting condition
+/// is reported again on every reconcile otherwise
+pub struct Notifier {
+    url: String,
+    reasons: BTreeSet<String>,
+    interval: Duration,
+    client: reqwest::Client,
+    last_sent: Mutex<HashMap<(String, String, String), Instant>>,
+}
+
+impl Notifier {
+    pub fn new(url: String, reasons: impl IntoIterator<Item = String>, interval: Duration, client: reqwest::Client) -> Self {
+        Self {
+            url,
+            reasons: reasons.into_iter().collect(),
+            interval,
+            client,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the notification should be sent now, recording it as sent if so. Notifications
+    /// sent longer than the interval ago are forgotten, they no longer throttle anything
+    fn should_send(&self, notification: &Notification) -> bool {
+        if !self.reasons.contains(&notification.reason) {
+            return false;
+        }
+        let key = (
+            notification.reason.clone(),
+            notification.namespace.clone().unwrap_or_default(),
+            format!("{}/{}", notification.kind, notification.name),
+        );
+        let now = Instant::now();
+        let mut last_sent = self.last_sent.lock().unwrap();
+        last_sent.retain(|_, sent| now.duration_since(*sent) < self.interval);
+        if last_sent.contains_key(&key) {
+            return false;
+        }
+        last_sent.insert(key, now);
+        true
+    }
+
+    /// Post the notification if its reason is configured and it isn't throttled.
+    /// Failures are only logged, notifications must not fail the reconcile
+    pub async fn notify(&self, notification: Notification) {
+        if !self.should_send(&notification) {
+            return;
+        }
+        debug!("Posting notification {:?}", notification);
+        let result = self
+            .client
+            .post(&self.url)
+            .json(&notification)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to post the {} notification: {}", notification.reason, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(name: &str) -> Notification {
+        let event = Event {
+            type_: EventType::Warning,
+            reason: "PodsRestarting".into(),
+            note: Some("A router pod restarted 5 times".into()),
+            action: "Monitoring".into(),
+            secondary: None,
+        };
+        let reference = ObjectReference {
+            kind: Some("Network".into()),
+            namespace: Some("ns".into()),
+            name: Some(name.into()),
+            ..ObjectReference::default()
+        };
+        Notification::new(&event, &reference, Some("prod".into()))
+    }
+
+    #[test]
+    fn notification_text_summarizes_the_event() {
+        assert_eq!(
+            notification("net").text,
+            "Warning PodsRestarting on Network ns/net in cluster prod: A router pod restarted 5 times"
+        );
+    }
+
+    #[test]
+    fn notifications_are_throttled_per_object() {
+        let notifier = Notifier::new("http://localhost".into(), ["PodsRestarting".to_string()], Duration::from_secs(60), reqwest::Client::new());
+        assert!(notifier.should_send(&notification("a")));
+        assert!(!notifier.should_send(&notification("a")));
+        assert!(notifier.should_send(&notification("b")));
+        // Past the interval, the records are pruned
+        let expired = Instant::now() - Duration::from_secs(120);
+        notifier.last_sent.lock().unwrap().values_mut().for_each(|sent| *sent = expired);
+        assert!(notifier.should_send(&notification("a")));
+        assert_eq!(notifier.last_sent.lock().unwrap().len(), 1);
+    }
+}
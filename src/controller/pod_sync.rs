@@ -1,15 +1,15 @@
 use std::sync::Arc;
 
 use crate::{Error, Result};
-use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::api::core::v1::{Node, Pod};
 use kube::{
-    api::{DeleteParams, Patch, PatchParams},
+    api::{DeleteParams, ListParams, Patch, PatchParams},
     runtime::controller::Action,
     ResourceExt,
 };
 use tracing::*;
 
-use super::{create_owned_router, Context, DS_LABEL_KEY, Network, Router};
+use super::{audit, create_owned_router, Context, RouterIdentity, DS_LABEL_KEY, Network, Router, POD_LABEL_KEY};
 
 pub static POD_FINALIZER: &str = "pod.named-data.net/finalizer";
 pub static POD_SYNC_MANAGER_NAME: &str = "pod-sync";
@@ -27,21 +27,29 @@ pub async fn pod_apply(pod: Arc<Pod>, ctx: Context) -> Result<Action> {
     let nw = api_nw
         .get(nw_name)
         .await
-        .map_err(Error::KubeError)?;
-    let node_name = pod
-        .as_ref()
-        .spec
-        .as_ref()
-        .and_then(|spec| spec.node_name.clone())
-        .ok_or(Error::MissingAnnotation("node_name".to_string()))?;
-    let router_name = pod.name_any().clone();
-    info!("Creating router for pod {} on node {}", pod.name_any(), node_name);
-    let router_data = create_owned_router(&nw, &router_name, &node_name);
+        .map_err(Error::KubeError)?
+        .resolve_template(client.clone())
+        .await?;
+    let identity = RouterIdentity::of_pod(&nw, &pod)?;
+    info!("Creating router {} for pod {} on node {}", identity.name, pod.name_any(), identity.node_name);
+    let mut router_data = create_owned_router(&nw, &identity);
+    // The pod label lets the router be found on pod deletion, whatever its name
+    router_data
+        .labels_mut()
+        .insert(POD_LABEL_KEY.to_string(), pod.name_any());
+    // Topology labels follow the node on the next reconcile of the pod, not when the node is relabeled
+    if nw.spec.topology_labels.as_ref().is_some_and(|keys| !keys.is_empty()) {
+        let api_node = kube::Api::<Node>::all(client.clone());
+        if let Some(node) = api_node.get_opt(&identity.node_name).await.map_err(Error::KubeError)? {
+            router_data.labels_mut().extend(nw.topology_labels_of(&node));
+        }
+    }
     let pp = PatchParams::apply(POD_SYNC_MANAGER_NAME);
     let _ = api_rt
-      .patch(&router_name, &pp, &Patch::Apply(router_data))
+      .patch(&identity.name, &pp, &Patch::Apply(router_data))
       .await
       .map_err(Error::KubeError)?;
+    audit::<Router>("apply", None, Some(&ns), &identity.name, Some(POD_SYNC_MANAGER_NAME));
 
     Ok(Action::await_change())
 }
@@ -52,13 +60,88 @@ pub async fn pod_cleanup(pod: Arc<Pod>, ctx: Context) -> Result<Action> {
     let ns = pod.namespace().unwrap();
     let api_rt = kube::Api::<Router>::namespaced(client.clone(), &ns);
     let pod_name = pod.name_any();
-    let router_name = pod_name.clone();
     let dp = DeleteParams::default();
+    let lp = ListParams::default().labels(&format!("{POD_LABEL_KEY}={pod_name}"));
     info!("Deleting router for pod {}", pod_name);
-    let _ = api_rt
-      .delete(&router_name, &dp)
-      .await
-      .map_err(Error::KubeError);
+    if let Ok(deleted) = api_rt.delete_collection(&dp, &lp).await {
+      for router in deleted.left().into_iter().flat_map(|list| list.items) {
+        audit::<Router>("delete", None, Some(&ns), &router.name_any(), None);
+      }
+    }
+    // Routers created before the pod label was introduced are named after the pod
+    if api_rt.delete(&pod_name, &dp).await.is_ok() {
+      audit::<Router>("delete", None, Some(&ns), &pod_name, None);
+    }
 
     Ok(Action::await_change())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{NetworkSpec, State};
+    use k8s_openapi::api::core::v1::PodSpec;
+    use kube::api::ObjectMeta;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn topology_labels_are_copied_from_the_node() {
+        let mut network = Network::new("net", NetworkSpec {
+            topology_labels: Some(vec!["topology.kubernetes.io/zone".to_string(), "example.com/rack".to_string()]),
+            ..NetworkSpec::default()
+        });
+        network.metadata.namespace = Some("ns".to_string());
+        network.metadata.uid = Some("uid".to_string());
+        let node = Node {
+            metadata: ObjectMeta {
+                name: Some("node-1".to_string()),
+                labels: Some(BTreeMap::from([
+                    ("topology.kubernetes.io/zone".to_string(), "zone-a".to_string()),
+                    ("kubernetes.io/hostname".to_string(), "node-1".to_string()),
+                ])),
+                ..ObjectMeta::default()
+            },
+            ..Node::default()
+        };
+        let applied = Arc::new(std::sync::Mutex::new(None));
+        let service = tower::service_fn({
+            let (network, node, applied) = (json!(network), json!(node), applied.clone());
+            move |request: http::Request<kube::client::Body>| {
+                let (network, node, applied) = (network.clone(), node.clone(), applied.clone());
+                async move {
+                    let (method, path) = (request.method().clone(), request.uri().path().to_string());
+                    let body = request.into_body().collect_bytes().await.unwrap();
+                    let body = match (method, path.as_str()) {
+                        (http::Method::GET, "/api/v1/nodes/node-1") => node,
+                        (http::Method::GET, _) => network,
+                        _ => {
+                            let router: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                            *applied.lock().unwrap() = Some(router.clone());
+                            router
+                        }
+                    };
+                    Ok::<_, std::convert::Infallible>(http::Response::builder().body(kube::client::Body::from(body.to_string().into_bytes())).unwrap())
+                }
+            }
+        });
+        let ctx = State::default().to_context(kube::Client::new(service, "ns")).await;
+        let pod = Pod {
+            metadata: ObjectMeta {
+                name: Some("net-x7k2p".to_string()),
+                namespace: Some("ns".to_string()),
+                labels: Some(BTreeMap::from([(DS_LABEL_KEY.to_string(), "net".to_string())])),
+                ..ObjectMeta::default()
+            },
+            spec: Some(PodSpec { node_name: Some("node-1".to_string()), ..PodSpec::default() }),
+            ..Pod::default()
+        };
+        pod_apply(Arc::new(pod), (*ctx).clone()).await.unwrap();
+        let router = applied.lock().unwrap().clone().unwrap();
+        let labels = router["metadata"]["labels"].as_object().unwrap();
+        assert_eq!(labels["topology.kubernetes.io/zone"], "zone-a");
+        // Labels the node doesn't have, or that aren't topology labels, aren't copied
+        assert!(!labels.contains_key("example.com/rack"));
+        assert!(!labels.contains_key("kubernetes.io/hostname"));
+        assert_eq!(labels[POD_LABEL_KEY], "net-x7k2p");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Error, Result};
+use k8s_openapi::api::core::v1::{ResourceQuota, ResourceRequirements};
+use std::{collections::BTreeMap, fmt};
+
+/// Value of a Kubernetes quantity, like `500m` or `128Mi`, in base units
+pub fn parse_quantity(quantity: &str) -> Result<f64> {
+    let invalid = || Error::InvalidSpec(format!("invalid quantity `{quantity}`"));
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match suffix {
+        "" => 1.0,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024f64,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        exponent if exponent.starts_with(['e', 'E']) => {
+            10f64.powi(exponent[1..].parse::<i32>().map_err(|_| invalid())?)
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(number * multiplier)
+}
+
+/// Quota usage of one router pod, by quota resource name. Only the ndnd container has
+/// resources, and requests default to limits like the API server does
+pub fn pod_usage(resources: &ResourceRequirements) -> BTreeMap<String, f64> {
+    let mut usage = BTreeMap::from([("pods".to_string(), 1.0)]);
+    let requests = resources.requests.clone().unwrap_or_default();
+    let limits = resources.limits.clone().unwrap_or_default();
+    for resource in ["cpu", "memory"] {
+        if let Some(Ok(request)) = requests.get(resource).or(limits.get(resource)).map(|q| parse_quantity(&q.0)) {
+            usage.insert(resource.to_string(), request);
+            usage.insert(format!("requests.{resource}"), request);
+        }
+        if let Some(Ok(limit)) = limits.get(resource).map(|q| parse_quantity(&q.0)) {
+            usage.insert(format!("limits.{resource}"), limit);
+        }
+    }
+    usage
+}
+
+/// Quota resource the new router pods would exceed
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuotaRisk {
+    pub quota: String,
+    pub resource: String,
+    /// Usage the new pods add
+    pub projected: f64,
+    /// Headroom left by the quota
+    pub available: f64,
+}
+
+impl fmt::Display for QuotaRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of quota `{}`: {} projected, {} available", self.resource, self.quota, self.projected, self.available)
+    }
+}
+
+/// Resources of the quota that `new_pods` pods using `per_pod` each would exceed
+pub fn quota_risks(quota: &ResourceQuota, per_pod: &BTreeMap<String, f64>, new_pods: usize) -> Vec<QuotaRisk> {
+    let hard = quota.status.as_ref().and_then(|status| status.hard.clone()).unwrap_or_default();
+    let used = quota.status.as_ref().and_then(|status| status.used.clone()).unwrap_or_default();
+    per_pod
+        .iter()
+        .filter_map(|(resource, usage)| {
+            let hard = parse_quantity(&hard.get(resource)?.0).ok()?;
+            let used = used.get(resource).and_then(|q| parse_quantity(&q.0).ok()).unwrap_or_default();
+            let projected = usage * new_pods as f64;
+            let available = hard - used;
+            (projected > available).then(|| QuotaRisk {
+                quota: quota.metadata.name.clone().unwrap_or_default(),
+                resource: resource.clone(),
+                projected,
+                available,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::{api::core::v1::ResourceQuotaStatus, apimachinery::pkg::api::resource::Quantity};
+    use kube::api::ObjectMeta;
+
+    fn quantities(pairs: &[(&str, &str)]) -> Option<BTreeMap<String, Quantity>> {
+        Some(pairs.iter().map(|(resource, quantity)| (resource.to_string(), Quantity(quantity.to_string()))).collect())
+    }
+
+    #[test]
+    fn quantities_are_parsed_in_base_units() {
+        assert_eq!(parse_quantity("500m").unwrap(), 0.5);
+        assert_eq!(parse_quantity("128Mi").unwrap(), 128.0 * 1024.0 * 1024.0);
+        assert_eq!(parse_quantity("2").unwrap(), 2.0);
+        assert_eq!(parse_quantity("1e3").unwrap(), 1000.0);
+        assert!(parse_quantity("1x").is_err());
+    }
+
+    #[test]
+    fn tight_quotas_are_at_risk() {
+        let quota = ResourceQuota {
+            metadata: ObjectMeta { name: Some("team".to_string()), ..ObjectMeta::default() },
+            status: Some(ResourceQuotaStatus {
+                hard: quantities(&[("requests.cpu", "2"), ("limits.memory", "1Gi"), ("pods", "10")]),
+                used: quantities(&[("requests.cpu", "1500m"), ("limits.memory", "256Mi"), ("pods", "2")]),
+            }),
+            ..ResourceQuota::default()
+        };
+        // Requests default to the limits
+        let per_pod = pod_usage(&ResourceRequirements {
+            limits: quantities(&[("cpu", "200m"), ("memory", "128Mi")]),
+            ..ResourceRequirements::default()
+        });
+        assert_eq!(per_pod.get("requests.cpu"), Some(&0.2));
+        assert!(quota_risks(&quota, &per_pod, 2).is_empty());
+        let risks = quota_risks(&quota, &per_pod, 3);
+        assert_eq!(risks.iter().map(|risk| risk.resource.as_str()).collect::<Vec<_>>(), ["requests.cpu"]);
+        assert_eq!(risks[0].quota, "team");
+        let risks = quota_risks(&quota, &per_pod, 7);
+        assert_eq!(risks.iter().map(|risk| risk.resource.as_str()).collect::<Vec<_>>(), ["limits.memory", "requests.cpu"]);
+    }
+}
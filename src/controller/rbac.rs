@@ -0,0 +1,154 @@
+use super::Config;
+use crate::{Error, Result};
+use futures::future::try_join_all;
+use k8s_openapi::api::authorization::v1::{ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec};
+use kube::{api::PostParams, Api, Client};
+use std::fmt;
+
+/// API access the operator needs, cluster-wide
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permission {
+    pub group: &'static str,
+    pub resource: &'static str,
+    pub verb: &'static str,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.group {
+            "" => write!(f, "{} {}", self.verb, self.resource),
+            group => write!(f, "{} {}.{}", self.verb, self.resource, group),
+        }
+    }
+}
+
+/// Permissions the controllers use with the configuration, mirroring the ClusterRole of the chart
+pub fn required_permissions(config: &Config) -> Vec<Permission> {
+    let mut rules: Vec<(&str, &str, &[&str])> = vec![
+        ("named-data.net", "networks", &["get", "list", "watch", "patch"]),
+        ("named-data.net", "networks/status", &["patch"]),
+        ("named-data.net", "routers", &["get", "list", "watch", "patch", "delete"]),
+        ("named-data.net", "routers/status", &["patch"]),
+        ("apps", "daemonsets", &["list", "patch", "delete"]),
+        ("", "pods", &["get", "list", "watch", "patch"]),
+        // Granted to the router sidecars of Networks with a mesh readiness gate
+        ("", "pods/status", &["patch"]),
+        ("", "nodes", &["get", "list"]),
+        ("", "secrets", &["get"]),
+        ("", "configmaps", &["get", "patch"]),
+        ("", "resourcequotas", &["list"]),
+        ("", "serviceaccounts", &["patch"]),
+        ("rbac.authorization.k8s.io", "roles", &["patch"]),
+        ("rbac.authorization.k8s.io", "rolebindings", &["patch"]),
+    ];
+    if !config.disable_events {
+        rules.push(("events.k8s.io", "events", &["create", "patch"]));
+    }
+    if config.watch_nodes {
+        rules.push(("", "nodes", &["watch"]));
+    }
+    if config.label_nodes {
+        rules.push(("", "nodes", &["patch"]));
+    }
+    if config.watch_secrets {
+        rules.push(("", "secrets", &["list", "watch"]));
+    }
+    if config.watch_config_maps {
+        rules.push(("", "configmaps", &["list", "watch"]));
+    }
+    if config.leader_election {
+        rules.push(("coordination.k8s.io", "leases", &["get", "create", "update"]));
+    }
+    rules
+        .into_iter()
+        .flat_map(|(group, resource, verbs)| verbs.iter().map(move |verb| Permission { group, resource, verb }))
+        .collect()
+}
+
+/// Whether the operator's own identity is allowed the access, asked to the API server
+async fn is_allowed(client: Client, permission: &Permission) -> Result<bool> {
+    let (resource, subresource) = match permission.resource.split_once('/') {
+        Some((resource, subresource)) => (resource, Some(subresource.to_string())),
+        None => (permission.resource, None),
+    };
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                group: Some(permission.group.to_string()),
+                resource: Some(resource.to_string()),
+                subresource,
+                verb: Some(permission.verb.to_string()),
+                ..ResourceAttributes::default()
+            }),
+            ..SelfSubjectAccessReviewSpec::default()
+        },
+        ..SelfSubjectAccessReview::default()
+    };
+    let review = Api::<SelfSubjectAccessReview>::all(client)
+        .create(&PostParams::default(), &review)
+        .await
+        .map_err(Error::KubeError)?;
+    Ok(review.status.is_some_and(|status| status.allowed))
+}
+
+/// Check at startup that the operator has all the permissions it needs, so missing RBAC
+/// fails fast with the list of what is missing instead of failing the reconciles later
+pub async fn check_permissions(client: Client, config: &Config) -> Result<()> {
+    let permissions = required_permissions(config);
+    let allowed = try_join_all(permissions.iter().map(|permission| is_allowed(client.clone(), permission))).await?;
+    let missing: Vec<String> = permissions
+        .iter()
+        .zip(allowed)
+        .filter(|(_, allowed)| !allowed)
+        .map(|(permission, _)| permission.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::OtherError(format!(
+            "The operator is missing permissions, check its ClusterRole: {}",
+            missing.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// API server allowing every access review but the denied `verb resource/subresource`
+    fn reviewing_client(denied: &'static [&'static str]) -> Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| async move {
+            let body = request.into_body().collect_bytes().await.unwrap();
+            let mut review: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let attributes = &review["spec"]["resourceAttributes"];
+            let resource = match attributes["subresource"].as_str() {
+                Some(subresource) => format!("{}/{subresource}", attributes["resource"].as_str().unwrap()),
+                None => attributes["resource"].as_str().unwrap().to_string(),
+            };
+            let access = format!("{} {resource}", attributes["verb"].as_str().unwrap());
+            review["status"] = json!({ "allowed": !denied.contains(&access.as_str()) });
+            let response = http::Response::builder()
+                .status(201)
+                .body(kube::client::Body::from(review.to_string().into_bytes()))
+                .unwrap();
+            Ok::<_, std::convert::Infallible>(response)
+        });
+        Client::new(service, "default")
+    }
+
+    #[tokio::test]
+    async fn missing_permissions_are_listed() {
+        let config = Config::default();
+        check_permissions(reviewing_client(&[]), &config).await.unwrap();
+        let error = check_permissions(reviewing_client(&["get secrets", "patch routers/status"]), &config)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with("patch routers/status.named-data.net, get secrets"), "{error}");
+        // Only the permissions the configuration uses are required
+        check_permissions(reviewing_client(&["update leases"]), &config).await.unwrap();
+        let config = Config { leader_election: true, ..Config::default() };
+        assert!(check_permissions(reviewing_client(&["update leases"]), &config).await.is_err());
+    }
+}
@@ -1,8 +1,12 @@
 use std::{
-    collections::{BTreeMap, BTreeSet}, sync::Arc
+    collections::{BTreeMap, BTreeSet}, net::SocketAddr, sync::Arc
 };
 
-// use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use chrono::Utc;
+use k8s_openapi::{
+    api::core::v1::{Node, Pod},
+    apimachinery::pkg::apis::meta::v1::{Condition as StatusCondition, Time},
+};
 use kube::{
     api::{ListParams, ObjectMeta, Patch, PatchParams},
     core::Expression,
@@ -14,15 +18,32 @@ use kube::{
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use serde_with::skip_serializing_none;
 use json_patch::{jsonptr::PointerBuf, Patch as JsonPatch, PatchOperation, ReplaceOperation};
 use tracing::*;
 
-use super::{Context, Network, NETWORK_LABEL_KEY};
+use super::{annotate_version, audit, is_dns_label, split_face_uri, validate_label_value, validate_qualified_name, Context, EventSeverity, FacePolicy, Network, NETWORK_LABEL_KEY, STALLED_CONDITION};
 use crate::{Error, Result};
 
 pub static ROUTER_FINALIZER: &str = "router.named-data.net/finalizer";
 pub static ROUTER_MANAGER_NAME: &str = "router-controller";
+pub static POD_LABEL_KEY: &str = "network.named-data.net/pod";
+pub static DEFAULT_ROUTER_NAME_TEMPLATE: &str = "{pod}";
+pub static DATA_PLANE_HEALTHY_CONDITION: &str = "DataPlaneHealthy";
+pub static ISOLATED_CONDITION: &str = "Isolated";
+pub static NODE_DEGRADED_CONDITION: &str = "NodeDegraded";
+pub static FACES_REJECTED_CONDITION: &str = "FacesRejected";
+pub static NEIGHBORS_CAPPED_CONDITION: &str = "NeighborsCapped";
+/// Node conditions reported as problems when `True`
+static NODE_PRESSURE_CONDITIONS: &[&str] = &["MemoryPressure", "DiskPressure", "PIDPressure"];
+pub static HEALTH_PROBE_COMPONENT: &str = "_health";
+/// Bytes the neighbors of a Router status may take, leaving room for the rest of the object
+/// under the 1.5 MiB etcd limit. About 10k IPv6 UDP faces
+pub static MAX_NEIGHBORS_BYTES: usize = 512 * 1024;
+/// Times a conditions patch is retried after conflicting with a concurrent status change
+static CONDITIONS_CONFLICT_RETRIES: u32 = 3;
+static ROUTER_NAME_VARIABLES: &[&str] = &["network", "node", "pod"];
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -33,6 +54,66 @@ pub struct RouterSpec {
     pub node_name: String,
 }
 
+/// Which routers a router neighbors. Links the roles no longer allow, e.g. between two leaves
+/// after a role change, are removed
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum RouterRole {
+    /// Neighbors all routers but leaves
+    #[default]
+    Full,
+    /// Neighbors all routers
+    Hub,
+    /// Only neighbors hubs
+    Leaf,
+}
+
+impl RouterRole {
+    /// Whether routers of the two roles neighbor each other
+    pub fn links_to(self, other: RouterRole) -> bool {
+        let leaf_ok = |role: RouterRole, other: RouterRole| role != RouterRole::Leaf || other == RouterRole::Hub;
+        leaf_ok(self, other) && leaf_ok(other, self)
+    }
+}
+
+/// Roles of the routers of a Network by a label of their node. Routers are recreated with
+/// their pods, so their role is kept by the Network rather than the Routers
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRoles {
+    /// Key of the node label, like `named-data.net/router-role`
+    pub node_label: String,
+    /// Role of the routers by the value of the node label. Routers of other nodes are `Full`
+    pub roles: BTreeMap<String, RouterRole>,
+}
+
+impl NodeRoles {
+    pub fn validate(&self) -> Result<()> {
+        validate_qualified_name(&self.node_label)?;
+        self.roles.keys().try_for_each(|value| validate_label_value(value))
+    }
+
+    /// Role of the routers of a node with the labels, if the labels set one
+    pub fn role(&self, labels: &BTreeMap<String, String>) -> Option<RouterRole> {
+        labels.get(&self.node_label).and_then(|value| self.roles.get(value)).copied()
+    }
+}
+
+/// Role of each router by name, from the node roles of their Network. Routers left out are `Full`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouterRoles(BTreeMap<String, RouterRole>);
+
+impl RouterRoles {
+    pub fn of(&self, router: &Router) -> RouterRole {
+        self.0.get(&router.name_any()).copied().unwrap_or_default()
+    }
+}
+
+impl FromIterator<(String, RouterRole)> for RouterRoles {
+    fn from_iter<I: IntoIterator<Item = (String, RouterRole)>>(roles: I) -> Self {
+        Self(roles.into_iter().collect())
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -42,6 +123,10 @@ pub struct RouterStatus {
     pub online: bool,
     pub faces: RouterFaces,
     pub neighbors: BTreeSet<String>,
+    /// `Isolated`, `NodeDegraded`, `FacesRejected`, `NeighborsCapped`, `Stalled`, and `DataPlaneHealthy` set by the router sidecar if the Network enables health checks
+    pub conditions: Option<Vec<StatusCondition>>,
+    /// Version ndnd reports, read by the router sidecar whenever ndnd starts
+    pub ndnd_version: Option<String>,
 }
 
 #[skip_serializing_none]
@@ -57,6 +142,17 @@ pub struct RouterFaces {
 
 impl RouterFaces {
 
+    /// Faces with their port replaced, keeping the faces whose address can't be parsed
+    pub fn with_port(&self, port: u16) -> Self {
+        let replace = |face: &Option<String>| face.as_ref().map(|face| face_with_port(face, port));
+        Self {
+            udp4: replace(&self.udp4),
+            tcp4: replace(&self.tcp4),
+            udp6: replace(&self.udp6),
+            tcp6: replace(&self.tcp6),
+        }
+    }
+
     pub fn to_btree_set(&self) -> BTreeSet<String> {
         let mut faces = BTreeSet::new();
         if let Some(ref udp4) = self.udp4 {
@@ -76,9 +172,181 @@ impl RouterFaces {
 }
 
 impl Router {
+    /// `DataPlaneHealthy` condition from the routers whose health probe got no data back
+    pub fn data_plane_condition(&self, probed: usize, unreachable: &[String]) -> StatusCondition {
+        let (status, reason, message) = match unreachable.is_empty() {
+            true => ("True", "ProbesAnswered", format!("All {probed} probed routers answered")),
+            false => ("False", "ProbesUnanswered", format!("No data from {} of {probed} probed routers: {}", unreachable.len(), unreachable.join(", "))),
+        };
+        self.condition(DATA_PLANE_HEALTHY_CONDITION, status, reason, message)
+    }
+
+    /// `Isolated` condition of a router left without neighbors while it has online siblings
+    pub fn isolated_condition(&self, neighbors: usize, siblings: usize) -> StatusCondition {
+        let (status, reason, message) = match neighbors == 0 && siblings > 0 {
+            true => ("True", "NoNeighbors", format!("The router has no neighbors while {siblings} other routers are online")),
+            false => ("False", "HasNeighbors", format!("The router has {neighbors} neighbors")),
+        };
+        self.condition(ISOLATED_CONDITION, status, reason, message)
+    }
+
+    /// `FacesRejected` condition from the faces of the router the Network face policy rejects
+    pub fn faces_rejected_condition(&self, rejected: &[String]) -> StatusCondition {
+        let (status, reason, message) = match rejected.is_empty() {
+            true => ("False", "FacesAllowed", "The Network allows all faces of the router".to_string()),
+            false => ("True", "FacePolicy", format!(
+                "Faces {} are not propagated to neighbors, their addresses are not allowed by the Network",
+                rejected.join(", ")
+            )),
+        };
+        self.condition(FACES_REJECTED_CONDITION, status, reason, message)
+    }
+
+    /// `NeighborsCapped` condition from the bytes the neighbors of the router would take uncapped
+    pub fn neighbors_capped_condition(&self, wanted: usize) -> StatusCondition {
+        let (status, reason, message) = match wanted > MAX_NEIGHBORS_BYTES {
+            true => ("True", "NeighborsCapped", format!(
+                "The faces of all other routers take {wanted} bytes, only the neighbors fitting in {MAX_NEIGHBORS_BYTES} bytes are kept"
+            )),
+            false => ("False", "NeighborsFit", format!("The neighbors take {wanted} of {MAX_NEIGHBORS_BYTES} bytes")),
+        };
+        self.condition(NEIGHBORS_CAPPED_CONDITION, status, reason, message)
+    }
+
+    /// `NodeDegraded` condition from the problems of the router's node, see `node_problems`
+    pub fn node_degraded_condition(&self, problems: &[String]) -> StatusCondition {
+        let (status, reason, message) = match problems.is_empty() {
+            true => ("False", "NodeHealthy", format!("Node {} reports no problem", self.spec.node_name)),
+            false => ("True", "NodeConditions", format!("Node {} reports {}", self.spec.node_name, problems.join(", "))),
+        };
+        self.condition(NODE_DEGRADED_CONDITION, status, reason, message)
+    }
+
+    pub fn is_node_degraded(&self) -> bool {
+        self.current_condition(NODE_DEGRADED_CONDITION).is_some_and(|condition| condition.status == "True")
+    }
+
+    /// `Stalled` condition of a Router whose reconcile failed `retries` times in a row
+    pub fn stalled_condition(&self, retries: u32, error: &str) -> StatusCondition {
+        let message = format!("Reconcile failed {retries} times in a row, last with: {error}. Change the spec to retry");
+        self.condition(STALLED_CONDITION, "True", "RetriesExhausted", message)
+    }
+
+    /// Report the Router as stalled with a Warning and its `Stalled` condition
+    pub async fn stall(&self, ctx: Arc<Context>, retries: u32, error: String) -> Result<()> {
+        let stalled = self.stalled_condition(retries, &error);
+        let network = self.network(&ctx).await?;
+        ctx.publish_event(
+            Event {
+                type_: EventType::Warning,
+                reason: "Stalled".into(),
+                note: Some(stalled.message.clone()),
+                action: "Reconciling".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            event_types(network.as_ref()),
+        )
+        .await?;
+        let api_router = Api::<Router>::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        self.set_conditions(&api_router, None, vec![stalled]).await
+    }
+
+    /// Set the `Stalled` condition of a Router reconciled again to `False`
+    pub async fn clear_stalled(&self, api_router: &Api<Router>) -> Result<()> {
+        if self.current_condition(STALLED_CONDITION).is_none_or(|condition| condition.status != "True") {
+            return Ok(());
+        }
+        let cleared = self.condition(STALLED_CONDITION, "False", "Reconciled", "The last reconcile succeeded".to_string());
+        self.set_conditions(api_router, None, vec![cleared]).await
+    }
+
+    /// Condition of the Router, keeping the transition time of the current one of the type if its status is unchanged
+    fn condition(&self, type_: &str, status: &str, reason: &str, message: String) -> StatusCondition {
+        let last_transition_time = self
+            .current_condition(type_)
+            .filter(|condition| condition.status == status)
+            .map(|condition| condition.last_transition_time.clone())
+            .unwrap_or(Time(Utc::now()));
+        StatusCondition {
+            type_: type_.to_string(),
+            status: status.to_string(),
+            reason: reason.to_string(),
+            message,
+            last_transition_time,
+            observed_generation: self.metadata.generation,
+        }
+    }
+
+    pub fn current_condition(&self, type_: &str) -> Option<&StatusCondition> {
+        self.status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .and_then(|conditions| conditions.iter().find(|c| c.type_ == type_))
+    }
+
+    /// Conditions of the Router with the conditions replacing the current ones of their types.
+    /// Status patches carry the whole list, conditions are set by both the operator and the sidecar
+    pub fn with_conditions(&self, conditions: Vec<StatusCondition>) -> Vec<StatusCondition> {
+        let mut merged: Vec<StatusCondition> = self
+            .status
+            .iter()
+            .flat_map(|status| status.conditions.iter().flatten())
+            .filter(|c| !conditions.iter().any(|condition| condition.type_ == c.type_))
+            .cloned()
+            .collect();
+        merged.extend(conditions);
+        merged
+    }
+
+    /// Set the conditions of the Router in a single status patch. The patch carries the resource
+    /// version the list was read at, so a condition set meanwhile, e.g. by the sidecar, fails it
+    /// with a conflict rather than being overwritten: the Router is read again and the patch retried
+    pub async fn set_conditions(&self, api_router: &Api<Router>, field_manager: Option<&str>, conditions: Vec<StatusCondition>) -> Result<()> {
+        if conditions.is_empty() {
+            return Ok(());
+        }
+        let pp = field_manager.map(PatchParams::apply).unwrap_or_default();
+        let mut router = self.clone();
+        let mut retries = 0;
+        loop {
+            let patch = json!({
+                "metadata": { "resourceVersion": router.resource_version() },
+                "status": { "conditions": router.with_conditions(conditions.clone()) },
+            });
+            match api_router.patch_status(&self.name_any(), &pp, &Patch::Merge(&patch)).await {
+                Ok(_) => break,
+                Err(kube::Error::Api(e)) if e.code == 409 && retries < CONDITIONS_CONFLICT_RETRIES => {
+                    debug!("Conditions of router {} changed meanwhile, retrying", self.name_any());
+                    retries += 1;
+                    router = api_router.get_status(&self.name_any()).await.map_err(Error::KubeError)?;
+                }
+                Err(e) => return Err(Error::KubeError(e)),
+            }
+        }
+        audit::<Router>("merge-patch", Some("status"), self.namespace().as_deref(), &self.name_any(), field_manager);
+        Ok(())
+    }
+
+    /// Network of the Router, resolved from its template, if it exists
+    async fn network(&self, ctx: &Context) -> Result<Option<Network>> {
+        let name = self.labels().get(NETWORK_LABEL_KEY).ok_or(Error::OtherError("Network label not found".to_owned()))?;
+        let api_nw = Api::<Network>::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        match api_nw.get_opt(name).await.map_err(Error::KubeError)? {
+            Some(network) => Ok(Some(network.resolve_template(ctx.client.clone()).await?)),
+            None => Ok(None),
+        }
+    }
+
     pub async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action> {
 
         debug!("Reconciling router: {:?}", self);
+        let api_router = Api::<Router>::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        annotate_version(&api_router, self).await?;
+        let network = self.network(&ctx).await?;
+        // The checks return their changed conditions, which are all set in a single patch:
+        // each patch carries the whole list, later ones would undo the earlier ones
+        let mut conditions: Vec<StatusCondition> = self.check_node(&ctx, network.as_ref()).await?.into_iter().collect();
         let my_status = self.status.clone().unwrap_or_default();
         // Proceed only if status.online is true
         match &my_status.online{
@@ -87,83 +355,239 @@ impl Router {
             }
             false => {
                 debug!("Router {} is offline, skipping reconciliation", self.name_any());
+                self.set_conditions(&api_router, None, conditions).await?;
                 return Ok(Action::await_change());
             }
         }
 
-        // Update status.neighbors of all other routers in the network
-        let api_router = Api::<Router>::namespaced(ctx.client.clone(), &self.namespace().unwrap());
+        // Update status.neighbors of all routers in the network
         let my_network_name = self.labels().get(NETWORK_LABEL_KEY).ok_or(Error::OtherError("Network label not found".to_owned()))?;
-        let my_faces = my_status.faces.to_btree_set();
         let lp = ListParams::default()
             .labels_from(&Expression::Equal(NETWORK_LABEL_KEY.into(), my_network_name.into()).into());
-
-        // List all routers in the network, excluding self
-        for router in api_router
+        let routers = api_router
             .list(&lp)
             .await
             .map_err(Error::KubeError)?
-            .iter()
-            .filter(|router| router.name_any() != self.name_any())
-        {
-            // Adding self to the set of neighbors
-            let router_neighbors = match &router.status {
-                Some(status) => status.neighbors.clone(),
-                None => BTreeSet::new(),
-            };
+            .items;
+        // After a restart Routers may be reconciled before their Network, whose spec
+        // decides which faces are propagated: wait for it rather than guess. The Network
+        // watch reconciles the Router again once its Network is reconciled
+        let network = match network {
+            Some(network) if network.is_reconciled() => network,
+            _ => {
+                debug!("Network {} of router {} is not reconciled yet", my_network_name, self.name_any());
+                self.set_conditions(&api_router, None, conditions).await?;
+                return Ok(Action::await_change());
+            }
+        };
+        let face_policy = network.spec.face_policy()?;
+        conditions.extend(self.check_face_policy(&ctx, &network, &face_policy).await?);
 
-            // add self.faces to the neighbors
-            let mut new_neighbors = router_neighbors.clone();
-            for face in &my_faces {
-                new_neighbors.insert(face.to_string());
+        let roles = network.router_roles(ctx.client.clone(), &routers).await?;
+        let links = network.router_links(ctx.client.clone(), &routers, &roles).await?;
+        if network.spec.external_topology.is_some() {
+            let unknown: BTreeSet<&String> = links
+                .iter()
+                .flat_map(|links| links.keys())
+                .filter(|name| !routers.iter().any(|router| router.name_any() == **name))
+                .collect();
+            let mut events = Vec::new();
+            if !unknown.is_empty() {
+                warn!("External topology of Network {} names unknown routers {:?}", network.name_any(), unknown);
+                events.push(Event {
+                    type_: EventType::Warning,
+                    reason: "UnknownTopologyRouters".into(),
+                    note: Some(format!(
+                        "The external topology links routers that don't exist: {}",
+                        unknown.into_iter().cloned().collect::<Vec<_>>().join(", ")
+                    )),
+                    action: "Validating".into(),
+                    secondary: None,
+                });
             }
+            // Every Router of the Network sees the same edges, so the warning is only
+            // published when the unknown routers change
+            ctx.publish_changed_events(
+                &network.object_ref(&()),
+                "UnknownTopologyRouters",
+                events,
+                network.spec.event_types.as_ref(),
+            )
+            .await?;
+        }
+        let expected = expected_neighbors(&routers, &self.name_any(), &face_policy, &roles, links.as_ref());
+        ctx.router_neighbors(self, my_status.neighbors.len(), expected.len());
+        conditions.extend(self.check_isolation(&ctx, &network, &routers, &my_status.neighbors, &expected).await?);
+        conditions.extend(self.check_neighbors_capped(&ctx, &network, neighbors_size(my_status.neighbors.union(&expected))).await?);
+        self.set_conditions(&api_router, None, conditions).await?;
+
+        // Each router is patched at most once per pass, no matter how many siblings changed
+        for (router, new_neighbors) in pending_neighbor_updates(&routers, &face_policy, &roles, links.as_ref()) {
             debug!("Router {} neighbors: {:?}", router.name_any(), new_neighbors);
-            let patches = vec![
-                PatchOperation::Replace(
-                    ReplaceOperation{
-                        path: PointerBuf::from_tokens(vec!["status", "neighbors"]),
-                        value: serde_json::to_value(new_neighbors).unwrap_or(serde_json::Value::Null),
-                    }
-                )
-            ];
-            let patch = Patch::Json::<()>(JsonPatch(patches));
+            let patch = Patch::Merge(json!({
+                "status": {
+                    "neighbors": new_neighbors,
+                }
+            }));
             info!("Updating neigbors of router {}...", router.name_any());
             debug!("Status patch: {:?}", patch);
             let serverside = PatchParams::apply(ROUTER_MANAGER_NAME);
             let _ = api_router.patch_status(&router.name_any(), &serverside, &patch).await
                 .map_err(Error::KubeError)?;
+            audit::<Router>("merge-patch", Some("status"), router.namespace().as_deref(), &router.name_any(), Some(ROUTER_MANAGER_NAME));
 
-            ctx.recorder
-                .publish(
-                    &Event {
-                        type_: EventType::Normal,
-                        reason: "NeighborsInserted".into(),
-                        note: Some(format!("From `{}` Router", self.name_any())),
-                        action: "Updated".into(),
-                        secondary: None,
-                    },
-                    &router.object_ref(&()),
-                )
-                .await
-                .map_err(Error::KubeError)?;
-        }
-        // Publish event
-        ctx.recorder
-            .publish(
-                &Event {
+            ctx.publish_event(
+                Event {
                     type_: EventType::Normal,
-                    reason: "RouterUpdated".into(),
-                    note: Some("Propagated my faces to all routers in the network".to_string()),
+                    reason: "NeighborsInserted".into(),
+                    note: Some(format!("From `{}` Router", self.name_any())),
                     action: "Updated".into(),
                     secondary: None,
                 },
-                &self.object_ref(&()),
+                &router.object_ref(&()),
+                network.spec.event_types.as_ref(),
             )
-            .await
-            .map_err(Error::KubeError)?;
+            .await?;
+        }
+        // Publish event
+        ctx.publish_event(
+            Event {
+                type_: EventType::Normal,
+                reason: "RouterUpdated".into(),
+                note: Some("Propagated my faces to all routers in the network".to_string()),
+                action: "Updated".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            network.spec.event_types.as_ref(),
+        )
+        .await?;
         Ok(Action::await_change())
     }
 
+    /// Report a router left without neighbors by this pass, which only adds the expected ones,
+    /// while other routers of the Network are online. A lone router is not isolated
+    async fn check_isolation(
+        &self,
+        ctx: &Context,
+        network: &Network,
+        routers: &[Router],
+        neighbors: &BTreeSet<String>,
+        expected: &BTreeSet<String>,
+    ) -> Result<Option<StatusCondition>> {
+        let siblings = routers
+            .iter()
+            .filter(|router| router.name_any() != self.name_any())
+            .filter(|router| router.status.as_ref().is_some_and(|status| status.online))
+            .count();
+        let isolated = self.isolated_condition(neighbors.union(expected).count(), siblings);
+        ctx.router_isolated(self, isolated.status == "True");
+        if self.current_condition(ISOLATED_CONDITION).is_some_and(|current| current.status == isolated.status) {
+            return Ok(None);
+        }
+        if isolated.status == "True" {
+            warn!("Router {} is isolated", self.name_any());
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "IsolatedRouter".into(),
+                    note: Some(isolated.message.clone()),
+                    action: "Monitoring".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                network.spec.event_types.as_ref(),
+            )
+            .await?;
+        }
+        Ok(Some(isolated))
+    }
+
+    /// Report a router whose neighbors don't all fit in its status. The Warning is only
+    /// published when the cap starts to apply
+    async fn check_neighbors_capped(&self, ctx: &Context, network: &Network, wanted: usize) -> Result<Option<StatusCondition>> {
+        let capped = self.neighbors_capped_condition(wanted);
+        if self.current_condition(NEIGHBORS_CAPPED_CONDITION).is_some_and(|current| current.status == capped.status) {
+            return Ok(None);
+        }
+        // Routers without the condition aren't reported as fitting, it is set once capped
+        if capped.status != "True" && self.current_condition(NEIGHBORS_CAPPED_CONDITION).is_none() {
+            return Ok(None);
+        }
+        if capped.status == "True" {
+            warn!("Neighbors of router {} take {} bytes, capped at {}", self.name_any(), wanted, MAX_NEIGHBORS_BYTES);
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "NeighborsCapped".into(),
+                    note: Some(capped.message.clone()),
+                    action: "Updating".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                network.spec.event_types.as_ref(),
+            )
+            .await?;
+        }
+        Ok(Some(capped))
+    }
+
+    /// Report the faces of the router the Network face policy keeps from its neighbors.
+    /// The Warning is only published when the rejected faces change
+    async fn check_face_policy(&self, ctx: &Context, network: &Network, face_policy: &FacePolicy) -> Result<Option<StatusCondition>> {
+        let faces = self.status.as_ref().map(|status| status.faces.to_btree_set()).unwrap_or_default();
+        let rejected: Vec<String> = faces.into_iter().filter(|face| !face_policy.allows(face)).collect();
+        let condition = self.faces_rejected_condition(&rejected);
+        if self.current_condition(FACES_REJECTED_CONDITION).is_some_and(|current| current.status == condition.status && current.message == condition.message) {
+            return Ok(None);
+        }
+        if condition.status == "True" {
+            warn!("Faces {:?} of router {} are rejected by the Network face policy", rejected, self.name_any());
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "FaceRejected".into(),
+                    note: Some(condition.message.clone()),
+                    action: "Updating".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                network.spec.event_types.as_ref(),
+            )
+            .await?;
+        }
+        Ok(Some(condition))
+    }
+
+    /// Report the problems of the router's node, like memory pressure or a lost kubelet,
+    /// which precede the failure of the router pod. A deleted node is left to the pod cleanup
+    async fn check_node(&self, ctx: &Context, network: Option<&Network>) -> Result<Option<StatusCondition>> {
+        let api_node = Api::<Node>::all(ctx.client.clone());
+        let Some(node) = api_node.get_opt(&self.spec.node_name).await.map_err(Error::KubeError)? else {
+            return Ok(None);
+        };
+        let degraded = self.node_degraded_condition(&node_problems(&node));
+        if self.current_condition(NODE_DEGRADED_CONDITION).is_some_and(|current| current.status == degraded.status && current.message == degraded.message) {
+            return Ok(None);
+        }
+        if degraded.status == "True" {
+            warn!("Node of router {} is degraded: {}", self.name_any(), degraded.message);
+            ctx.publish_event(
+                Event {
+                    type_: EventType::Warning,
+                    reason: "NodeDegraded".into(),
+                    note: Some(degraded.message.clone()),
+                    action: "Monitoring".into(),
+                    secondary: None,
+                },
+                &self.object_ref(&()),
+                event_types(network),
+            )
+            .await?;
+        }
+        Ok(Some(degraded))
+    }
+
     pub async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action> {
 
         // Update status.neighbors of all other routers in the network
@@ -173,6 +597,7 @@ impl Router {
             .labels(&format!("{NETWORK_LABEL_KEY}={my_network_name}"));
         let my_status = self.status.clone().unwrap_or_default();
         let my_faces = my_status.faces.to_btree_set();
+        let network = self.network(&ctx).await?;
         for router in api_router
             .list(&lp)
             .await
@@ -204,44 +629,189 @@ impl Router {
             let serverside = PatchParams::apply(ROUTER_MANAGER_NAME);
             let _ = api_router.patch_status(&router.name_any(), &serverside, &patch).await
                 .map_err(Error::KubeError)?;
-            ctx.recorder
-                .publish(
-                    &Event {
-                        type_: EventType::Normal,
-                        reason: "NeighborsRemoved".into(),
-                        note: Some(format!("From `{}` Router", self.name_any())),
-                        action: "Updated".into(),
-                        secondary: None,
-                    },
-                    &router.object_ref(&()),
-                )
-                .await
-                .map_err(Error::KubeError)?;
-        }
-
-        // Publish event
-        ctx.recorder
-            .publish(
-                &Event {
+            audit::<Router>("json-patch", Some("status"), router.namespace().as_deref(), &router.name_any(), Some(ROUTER_MANAGER_NAME));
+            ctx.publish_event(
+                Event {
                     type_: EventType::Normal,
-                    reason: "RouterDeleted".into(),
-                    note: Some(format!("Deleted `{}` Router", self.name_any())),
-                    action: "Deleted".into(),
+                    reason: "NeighborsRemoved".into(),
+                    note: Some(format!("From `{}` Router", self.name_any())),
+                    action: "Updated".into(),
                     secondary: None,
                 },
-                &self.object_ref(&()),
+                &router.object_ref(&()),
+                event_types(network.as_ref()),
             )
-            .await
-            .map_err(Error::KubeError)?;
+            .await?;
+        }
+
+        ctx.router_deleted(self);
+        // Publish event
+        ctx.publish_event(
+            Event {
+                type_: EventType::Normal,
+                reason: "RouterDeleted".into(),
+                note: Some(format!("Deleted `{}` Router", self.name_any())),
+                action: "Deleted".into(),
+                secondary: None,
+            },
+            &self.object_ref(&()),
+            event_types(network.as_ref()),
+        )
+        .await?;
         Ok(Action::await_change())
     }
 }
 
-pub fn create_owned_router(source: &Network, name: &String, node_name: &String) -> Router {
+/// Event types of the Network, see `NetworkSpec::event_types`
+fn event_types(network: Option<&Network>) -> Option<&BTreeMap<String, EventSeverity>> {
+    network.and_then(|network| network.spec.event_types.as_ref())
+}
+
+/// Validate that a Router naming template only uses known variables and
+/// characters allowed in DNS labels, and tells the routers of the Network apart
+/// by `{node}` or `{pod}`
+pub fn validate_router_name_template(template: &str) -> Result<()> {
+    let mut literal = String::new();
+    let mut rest = template;
+    let mut unique = false;
+    while let Some(start) = rest.find('{') {
+        literal.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(Error::InvalidSpec(format!("unclosed variable in router name template `{template}`")))?;
+        let variable = &rest[start + 1..start + end];
+        if !ROUTER_NAME_VARIABLES.contains(&variable) {
+            return Err(Error::InvalidSpec(format!(
+                "unknown variable `{variable}` in router name template, expected one of {ROUTER_NAME_VARIABLES:?}"
+            )));
+        }
+        unique |= variable == "node" || variable == "pod";
+        rest = &rest[start + end + 1..];
+    }
+    literal.push_str(rest);
+    if !unique {
+        return Err(Error::InvalidSpec(format!(
+            "router name template `{template}` must use `{{node}}` or `{{pod}}`, the Routers of a Network would share a name otherwise"
+        )));
+    }
+    if !literal.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err(Error::InvalidSpec(format!(
+            "router name template `{template}` may only contain lowercase alphanumerics and `-` besides variables"
+        )));
+    }
+    Ok(())
+}
+
+/// Adverse conditions of a node: the pressure conditions that are `True`, and `NotReady`
+/// unless its `Ready` condition is `True`
+pub fn node_problems(node: &Node) -> Vec<String> {
+    let conditions = node.status.iter().flat_map(|status| status.conditions.iter().flatten());
+    let mut problems: Vec<String> = conditions
+        .clone()
+        .filter(|condition| NODE_PRESSURE_CONDITIONS.contains(&condition.type_.as_str()) && condition.status == "True")
+        .map(|condition| condition.type_.clone())
+        .collect();
+    if !conditions.clone().any(|condition| condition.type_ == "Ready" && condition.status == "True") {
+        problems.push("NotReady".to_string());
+    }
+    problems
+}
+
+/// FNV-1a hash, stable across builds so the operator and the pods agree on the values derived from it
+pub fn fnv1a(value: &str) -> u32 {
+    fnv1a_extend(0x811c9dc5, value)
+}
+
+/// FNV-1a hash of a value following the one hashed into `hash`, so `fnv1a_extend(fnv1a(a), b)`
+/// is the hash of `a` and `b` concatenated
+pub fn fnv1a_extend(hash: u32, value: &str) -> u32 {
+    value.bytes().fold(hash, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+/// Expand a Router naming template. Characters of the variables that are not allowed in
+/// DNS labels, like the dots in node names, are replaced with `-`. Names longer than 63
+/// characters are truncated and suffixed with a hash of the full name to stay unique
+pub fn router_name(template: &str, network: &str, node: &str, pod: &str) -> Result<String> {
+    validate_router_name_template(template)?;
+    let sanitize = |value: &str| {
+        value
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+            .collect::<String>()
+    };
+    let name = template
+        .replace("{network}", &sanitize(network))
+        .replace("{node}", &sanitize(node))
+        .replace("{pod}", &sanitize(pod));
+    let name = match name.len() > 63 {
+        true => {
+            format!("{}-{:08x}", name[..54].trim_end_matches('-'), fnv1a(&name))
+        }
+        false => name,
+    };
+    if !is_dns_label(&name) {
+        return Err(Error::InvalidSpec(format!("router name `{name}` is not a valid DNS label")));
+    }
+    Ok(name)
+}
+
+/// Identity of the router of a Network pod. It is the single source of truth for the name
+/// of the Router object, created by the pod sync, and of the ndnd router, configured by the
+/// init container: both derive it from the Network's naming template and the pod's placement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouterIdentity {
+    /// Name of the Router object
+    pub name: String,
+    /// Node the router runs on
+    pub node_name: String,
+}
+
+impl RouterIdentity {
+    pub fn new(template: &str, network: &str, node_name: &str, pod: &str) -> Result<Self> {
+        Ok(Self {
+            name: router_name(template, network, node_name, pod)?,
+            node_name: node_name.to_string(),
+        })
+    }
+
+    /// Identity of the router of a Network pod, as created by the pod sync
+    pub fn of_pod(network: &Network, pod: &Pod) -> Result<Self> {
+        let node_name = pod
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.node_name.as_deref())
+            .ok_or(Error::MissingAnnotation("node_name".to_string()))?;
+        Self::new(network.router_name_template(), &network.name_any(), node_name, &pod.name_any())
+    }
+
+    /// Identity of the router of the current pod, from the environment set by `Network::router_identity_env`
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).map_err(|_| Error::OtherError(format!("{name} is not set")));
+        Self::new(
+            &var("NDN_ROUTER_NAME_TEMPLATE")?,
+            &var("NDN_NETWORK_NAME")?,
+            &var("NDN_NODE_NAME")?,
+            &var("NDN_POD_NAME")?,
+        )
+    }
+
+    /// Name of the router in the ndnd config
+    pub fn ndnd_router_name(&self, network: &str) -> String {
+        format!("/{network}/{}", self.name)
+    }
+}
+
+/// Name a router serves for the data-plane health probe of the other routers, `<prefix>/_health/<router>`
+pub fn health_probe_name(prefix: &str, router: &str) -> String {
+    format!("{}/{HEALTH_PROBE_COMPONENT}/{router}", prefix.trim_end_matches('/'))
+}
+
+pub fn create_owned_router(source: &Network, identity: &RouterIdentity) -> Router {
     let oref = source.controller_owner_ref(&()).unwrap();
     Router {
         metadata: ObjectMeta {
-            name: Some(name.to_string()),
+            name: Some(identity.name.clone()),
             namespace: source.namespace(),
             owner_references: Some(vec![oref]),
             labels: {
@@ -254,12 +824,144 @@ pub fn create_owned_router(source: &Network, name: &String, node_name: &String)
         },
         spec: RouterSpec {
             prefix: source.spec.prefix.clone(),
-            node_name: node_name.to_string(),
+            node_name: identity.node_name.clone(),
         },
         status: None,
     }
 }
 
+/// Neighbor sets that make every router know the faces of all other online routers.
+/// Faces rejected by the Network's face policy are skipped, and removed from the current sets,
+/// like the faces of routers the roles or `links` no longer link. Other neighbors are only added here,
+/// removal is done by `Router::cleanup`, so a cold reconcile over a populated mesh (e.g. after
+/// an operator restart) never drops a live link. Routers whose neighbor set is already complete,
+/// or full, are left out. Neighbor sets are capped by `cap_neighbors`, and restricted to `links`
+/// if set, see `Network::router_links`
+pub fn pending_neighbor_updates<'a>(
+    routers: &'a [Router],
+    face_policy: &FacePolicy,
+    roles: &RouterRoles,
+    links: Option<&BTreeMap<String, BTreeSet<String>>>,
+) -> Vec<(&'a Router, BTreeSet<String>)> {
+    let owners: BTreeMap<String, &Router> = routers
+        .iter()
+        .flat_map(|router| {
+            let faces = router.status.as_ref().map(|status| status.faces.to_btree_set()).unwrap_or_default();
+            faces.into_iter().map(move |face| (face, router))
+        })
+        .collect();
+    routers
+        .iter()
+        .filter_map(|router| {
+            let current = router.status.as_ref().map(|status| status.neighbors.clone()).unwrap_or_default();
+            let role = roles.of(router);
+            let linked = links.map(|links| links.get(&router.name_any()).cloned().unwrap_or_default());
+            // Faces the policy denies are unlinked, e.g. once the policy changed, and so are the
+            // routers the roles or links don't link, e.g. two leaves once a full router became a
+            // leaf, or routers a fan-out picked before more routers came online
+            let allowed = current
+                .iter()
+                .filter(|face| face_policy.allows(face))
+                .filter(|face| {
+                    owners.get(*face).is_none_or(|owner| match &linked {
+                        Some(linked) => linked.contains(&owner.name_any()),
+                        None => role.links_to(roles.of(owner)),
+                    })
+                })
+                .cloned()
+                .collect();
+            let desired = cap_neighbors(&allowed, expected_neighbors(routers, &router.name_any(), face_policy, roles, links));
+            (desired != current).then_some((router, desired))
+        })
+        .collect()
+}
+
+/// Size of the neighbors in the JSON of a Router status
+pub fn neighbors_size<'a>(neighbors: impl IntoIterator<Item = &'a String>) -> usize {
+    neighbors.into_iter().map(|neighbor| neighbor.len() + 3).sum()
+}
+
+/// Current neighbors with the new ones added while they fit in `MAX_NEIGHBORS_BYTES`,
+/// so a huge full mesh degrades to a partial one ndnd still routes over instead of
+/// failing the status patches. The current neighbors are never dropped
+pub fn cap_neighbors(current: &BTreeSet<String>, new: impl IntoIterator<Item = String>) -> BTreeSet<String> {
+    let mut size = neighbors_size(current);
+    let mut neighbors = current.clone();
+    for neighbor in new {
+        if neighbors.contains(&neighbor) {
+            continue;
+        }
+        size += neighbors_size([&neighbor]);
+        if size > MAX_NEIGHBORS_BYTES {
+            break;
+        }
+        neighbors.insert(neighbor);
+    }
+    neighbors
+}
+
+/// Face URI with its port replaced, or unchanged if its authority isn't a socket address
+pub fn face_with_port(uri: &str, port: u16) -> String {
+    match split_face_uri(uri).map(|(scheme, authority)| (scheme, authority.parse::<SocketAddr>())) {
+        Ok((scheme, Ok(mut addr))) => {
+            addr.set_port(port);
+            format!("{scheme}://{addr}")
+        }
+        _ => uri.to_string(),
+    }
+}
+
+/// Routers whose faces use another port than the Network's, with their faces moved to the
+/// new port, and the neighbor sets in which the old faces are replaced by the new ones
+pub fn port_change_updates(routers: &[Router], port: u16) -> Vec<(&Router, RouterFaces, BTreeSet<String>)> {
+    let moved: BTreeMap<String, String> = routers
+        .iter()
+        .filter_map(|router| router.status.as_ref())
+        .flat_map(|status| status.faces.to_btree_set())
+        .map(|face| (face.clone(), face_with_port(&face, port)))
+        .filter(|(old, new)| old != new)
+        .collect();
+    routers
+        .iter()
+        .filter_map(|router| {
+            let status = router.status.clone().unwrap_or_default();
+            let faces = status.faces.with_port(port);
+            let neighbors: BTreeSet<String> = status
+                .neighbors
+                .iter()
+                .map(|neighbor| moved.get(neighbor).unwrap_or(neighbor).clone())
+                .collect();
+            (faces.to_btree_set() != status.faces.to_btree_set() || neighbors != status.neighbors)
+                .then_some((router, faces, neighbors))
+        })
+        .collect()
+}
+
+/// Faces of the other online routers allowed by the face policy and the roles of the routers,
+/// the neighbors the router should have. With `links` from `Network::router_links`, only the
+/// faces of the routers linked to this one, the links already accounting for the roles
+pub fn expected_neighbors(
+    routers: &[Router],
+    router_name: &str,
+    face_policy: &FacePolicy,
+    roles: &RouterRoles,
+    links: Option<&BTreeMap<String, BTreeSet<String>>>,
+) -> BTreeSet<String> {
+    let role = routers.iter().find(|router| router.name_any() == router_name).map(|router| roles.of(router)).unwrap_or_default();
+    let linked = links.map(|links| links.get(router_name).cloned().unwrap_or_default());
+    routers
+        .iter()
+        .filter(|router| router.name_any() != router_name)
+        .filter(|router| match &linked {
+            Some(linked) => linked.contains(&router.name_any()),
+            None => role.links_to(roles.of(router)),
+        })
+        .filter_map(|router| router.status.as_ref().filter(|status| status.online))
+        .flat_map(|status| status.faces.to_btree_set())
+        .filter(|face| face_policy.allows(face))
+        .collect()
+}
+
 pub fn is_router_created() -> impl Condition<Router> {
     |obj: Option<&Router>| {
         obj.is_some()
@@ -268,10 +970,9 @@ pub fn is_router_created() -> impl Condition<Router> {
 
 pub fn is_router_online() -> impl Condition<Router> {
     |obj: Option<&Router>| {
-        if let Some(router) = &obj {
-            if let Some(status) = &router.status {
-                return status.online
-            }
+        if let Some(router) = &obj
+            && let Some(status) = &router.status {
+            return status.online
         }
         false
     }
@@ -279,11 +980,314 @@ pub fn is_router_online() -> impl Condition<Router> {
 
 pub fn is_router_initialized() -> impl Condition<Router> {
     |obj: Option<&Router>| {
-        if let Some(router) = &obj {
-            if let Some(status) = &router.status {
-                return status.initialized
-            }
+        if let Some(router) = &obj
+            && let Some(status) = &router.status {
+            return status.initialized
         }
         false
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn router(name: &str, ip: &str, neighbors: &[&str]) -> Router {
+        let mut router = Router::new(name, RouterSpec { prefix: format!("/test/{name}"), node_name: format!("node-{name}") });
+        router.metadata.namespace = Some("default".to_string());
+        router.metadata.labels = Some(BTreeMap::from([(NETWORK_LABEL_KEY.to_string(), "test".to_string())]));
+        router.status = Some(RouterStatus {
+            initialized: true,
+            online: true,
+            faces: RouterFaces { udp4: Some(face(ip)), ..RouterFaces::default() },
+            neighbors: neighbors.iter().map(|ip| face(ip)).collect(),
+            ..RouterStatus::default()
+        });
+        router
+    }
+
+    pub(crate) fn face(ip: &str) -> String {
+        format!("udp://{ip}:6363")
+    }
+
+    fn policy(allow: &[&str], deny: &[&str]) -> FacePolicy {
+        FacePolicy {
+            allow: allow.iter().map(|cidr| cidr.parse().unwrap()).collect(),
+            deny: deny.iter().map(|cidr| cidr.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn neighbors_are_added_for_online_routers() {
+        let routers = vec![router("a", "10.0.0.1", &[]), router("b", "10.0.0.2", &[])];
+        let updates = pending_neighbor_updates(&routers, &FacePolicy::default(), &RouterRoles::default(), None);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].1, BTreeSet::from([face("10.0.0.2")]));
+    }
+
+    #[test]
+    fn denied_neighbors_are_removed() {
+        let routers = vec![router("a", "10.0.0.1", &["10.0.0.2", "192.168.0.3"]), router("b", "10.0.0.2", &["10.0.0.1"])];
+        let updates = pending_neighbor_updates(&routers, &policy(&[], &["192.168.0.0/16"]), &RouterRoles::default(), None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0.name_any(), "a");
+        assert_eq!(updates[0].1, BTreeSet::from([face("10.0.0.2")]));
+    }
+
+    #[test]
+    fn simultaneous_changes_update_each_router_once() {
+        // `c` and `d` come online together, `a` and `b` already know each other
+        let routers = vec![
+            router("a", "10.0.0.1", &["10.0.0.2"]),
+            router("b", "10.0.0.2", &["10.0.0.1"]),
+            router("c", "10.0.0.3", &[]),
+            router("d", "10.0.0.4", &[]),
+        ];
+        let updates = pending_neighbor_updates(&routers, &FacePolicy::default(), &RouterRoles::default(), None);
+        let names: Vec<String> = updates.iter().map(|(router, _)| router.name_any()).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+        // Each with the faces of both new routers in a single update
+        assert_eq!(updates[0].1, BTreeSet::from([face("10.0.0.2"), face("10.0.0.3"), face("10.0.0.4")]));
+        assert_eq!(updates[2].1, BTreeSet::from([face("10.0.0.1"), face("10.0.0.2"), face("10.0.0.4")]));
+    }
+
+    #[test]
+    fn cold_reconcile_keeps_the_populated_mesh() {
+        let mut routers = vec![
+            router("a", "10.0.0.1", &["10.0.0.2", "10.0.0.3"]),
+            router("b", "10.0.0.2", &["10.0.0.1", "10.0.0.3"]),
+            router("c", "10.0.0.3", &["10.0.0.1", "10.0.0.2"]),
+        ];
+        assert!(pending_neighbor_updates(&routers, &FacePolicy::default(), &RouterRoles::default(), None).is_empty());
+        // A sibling not back online yet after the restart is left to the cleanup
+        routers[2].status.as_mut().unwrap().online = false;
+        assert!(pending_neighbor_updates(&routers, &FacePolicy::default(), &RouterRoles::default(), None).is_empty());
+    }
+
+    #[test]
+    fn port_changes_move_every_face_and_neighbor() {
+        let routers = vec![router("a", "10.0.0.1", &["10.0.0.2"]), router("b", "10.0.0.2", &["10.0.0.1"])];
+        let updates = port_change_updates(&routers, 6364);
+        assert_eq!(updates.len(), 2);
+        for (router, faces, neighbors) in &updates {
+            let other = if router.name_any() == "a" { "10.0.0.2" } else { "10.0.0.1" };
+            assert!(faces.to_btree_set().iter().all(|face| face.ends_with(":6364")), "{faces:?}");
+            assert_eq!(*neighbors, BTreeSet::from([format!("udp://{other}:6364")]));
+        }
+        // Routers already on the port of the Network are left
+        assert!(port_change_updates(&routers, 6363).is_empty());
+    }
+
+    #[test]
+    fn unanswered_health_probes_make_the_data_plane_unhealthy() {
+        let router = router("a", "10.0.0.1", &[]);
+        assert_eq!(health_probe_name("/net/", "b"), "/net/_health/b");
+        let healthy = router.data_plane_condition(2, &[]);
+        assert_eq!((healthy.status.as_str(), healthy.reason.as_str()), ("True", "ProbesAnswered"));
+        let unhealthy = router.data_plane_condition(2, &["c".to_string()]);
+        assert_eq!((unhealthy.status.as_str(), unhealthy.reason.as_str()), ("False", "ProbesUnanswered"));
+        assert_eq!(unhealthy.message, "No data from 1 of 2 probed routers: c");
+    }
+
+    fn roles(roles: &[(&str, RouterRole)]) -> RouterRoles {
+        roles.iter().map(|(name, role)| (name.to_string(), *role)).collect()
+    }
+
+    #[test]
+    fn leaves_only_link_to_hubs() {
+        use RouterRole::*;
+        assert!(Hub.links_to(Hub) && Hub.links_to(Full) && Hub.links_to(Leaf));
+        assert!(Full.links_to(Full) && Full.links_to(Hub));
+        assert!(!Full.links_to(Leaf) && !Leaf.links_to(Full) && !Leaf.links_to(Leaf));
+        assert!(Leaf.links_to(Hub));
+    }
+
+    #[test]
+    fn node_roles_follow_the_node_label() {
+        let node_roles = NodeRoles {
+            node_label: "named-data.net/router-role".to_string(),
+            roles: BTreeMap::from([("core".to_string(), RouterRole::Hub), ("edge".to_string(), RouterRole::Leaf)]),
+        };
+        let labels = |value: &str| BTreeMap::from([("named-data.net/router-role".to_string(), value.to_string())]);
+        assert_eq!(node_roles.role(&labels("core")), Some(RouterRole::Hub));
+        assert_eq!(node_roles.role(&labels("other")), None);
+        assert_eq!(node_roles.role(&BTreeMap::new()), None);
+        assert!(node_roles.validate().is_ok());
+        assert!(NodeRoles { node_label: "not a label".to_string(), ..node_roles }.validate().is_err());
+    }
+
+    #[test]
+    fn links_the_roles_no_longer_allow_are_removed() {
+        // `a` and `b` were linked as full routers before becoming leaves of the hub `c`
+        let routers = vec![
+            router("a", "10.0.0.1", &["10.0.0.2", "10.0.0.3"]),
+            router("b", "10.0.0.2", &["10.0.0.1", "10.0.0.3"]),
+            router("c", "10.0.0.3", &["10.0.0.1", "10.0.0.2"]),
+        ];
+        let roles = roles(&[("a", RouterRole::Leaf), ("b", RouterRole::Leaf), ("c", RouterRole::Hub)]);
+        let updates = pending_neighbor_updates(&routers, &FacePolicy::default(), &roles, None);
+        let updates: Vec<(String, BTreeSet<String>)> = updates.into_iter().map(|(router, neighbors)| (router.name_any(), neighbors)).collect();
+        assert_eq!(updates, vec![
+            ("a".to_string(), BTreeSet::from([face("10.0.0.3")])),
+            ("b".to_string(), BTreeSet::from([face("10.0.0.3")])),
+        ]);
+    }
+
+    #[test]
+    fn neighbors_outside_the_links_are_removed() {
+        // A fan-out picked `b` for `a`, then `c` replaced it
+        let routers = vec![router("a", "10.0.0.1", &["10.0.0.2"]), router("b", "10.0.0.2", &["10.0.0.1"]), router("c", "10.0.0.3", &[])];
+        let links = BTreeMap::from([
+            ("a".to_string(), BTreeSet::from(["c".to_string()])),
+            ("c".to_string(), BTreeSet::from(["a".to_string()])),
+        ]);
+        let updates = pending_neighbor_updates(&routers, &FacePolicy::default(), &RouterRoles::default(), Some(&links));
+        let updates: Vec<(String, BTreeSet<String>)> = updates.into_iter().map(|(router, neighbors)| (router.name_any(), neighbors)).collect();
+        assert_eq!(updates, vec![
+            ("a".to_string(), BTreeSet::from([face("10.0.0.3")])),
+            ("b".to_string(), BTreeSet::new()),
+            ("c".to_string(), BTreeSet::from([face("10.0.0.1")])),
+        ]);
+    }
+
+    fn node_with(conditions: &[(&str, &str)]) -> Node {
+        Node {
+            status: Some(k8s_openapi::api::core::v1::NodeStatus {
+                conditions: Some(conditions.iter().map(|(type_, status)| k8s_openapi::api::core::v1::NodeCondition {
+                    type_: type_.to_string(),
+                    status: status.to_string(),
+                    ..Default::default()
+                }).collect()),
+                ..Default::default()
+            }),
+            ..Node::default()
+        }
+    }
+
+    #[test]
+    fn node_problems_are_pressures_and_not_ready() {
+        assert!(node_problems(&node_with(&[("Ready", "True"), ("MemoryPressure", "False")])).is_empty());
+        assert_eq!(
+            node_problems(&node_with(&[("Ready", "True"), ("DiskPressure", "True"), ("PIDPressure", "True"), ("NetworkUnavailable", "True")])),
+            vec!["DiskPressure", "PIDPressure"]
+        );
+        assert_eq!(node_problems(&node_with(&[("Ready", "Unknown")])), vec!["NotReady"]);
+        // A node without conditions isn't known to be ready
+        assert_eq!(node_problems(&Node::default()), vec!["NotReady"]);
+    }
+
+    #[test]
+    fn faces_outside_the_allow_list_are_rejected() {
+        let me = router("a", "10.0.0.1", &[]);
+        let rejected = vec![face("10.0.0.1")];
+        assert_eq!(me.faces_rejected_condition(&rejected).status, "True");
+        assert_eq!(me.faces_rejected_condition(&[]).status, "False");
+        let routers = vec![me, router("b", "10.1.0.2", &[])];
+        let expected = expected_neighbors(&routers, "b", &policy(&["10.1.0.0/16"], &[]), &RouterRoles::default(), None);
+        assert!(expected.is_empty());
+    }
+
+    #[test]
+    fn capped_neighbors_keep_the_current_ones() {
+        let current: BTreeSet<String> = (0..3).map(|i| face(&format!("10.0.0.{i}"))).collect();
+        let new: Vec<String> = (0..40_000).map(|i| face(&format!("10.{}.{}.{}", 1 + i / 65536, (i / 256) % 256, i % 256))).collect();
+        let capped = cap_neighbors(&current, new.clone());
+        assert!(current.is_subset(&capped));
+        assert!(neighbors_size(&capped) <= MAX_NEIGHBORS_BYTES);
+        // The new neighbors are added in order until the next one doesn't fit
+        let added = capped.len() - current.len();
+        assert!(added > 0 && added < new.len());
+        assert!(neighbors_size(&capped) + neighbors_size([&new[added]]) > MAX_NEIGHBORS_BYTES);
+        // Neighbors already known take no room
+        assert_eq!(cap_neighbors(&current, current.clone()), current);
+    }
+
+    #[test]
+    fn router_names_sanitize_node_names() {
+        let name = router_name("{network}-{node}", "edge", "Node-1.us-east.example.com", "pod").unwrap();
+        assert_eq!(name, "edge-node-1-us-east-example-com");
+    }
+
+    #[test]
+    fn long_router_names_are_hashed() {
+        let node = "n".repeat(70);
+        let name = router_name("{network}-{node}", "edge", &node, "pod").unwrap();
+        assert_eq!(name.len(), 63);
+        let (head, hash) = name.split_at(54);
+        assert_eq!(head, format!("edge-{}", "n".repeat(49)));
+        assert!(hash.starts_with('-') && hash[1..].chars().all(|c| c.is_ascii_hexdigit()) && hash.len() == 9);
+        // Names sharing the first 54 characters stay apart
+        assert_ne!(router_name("{network}-{node}", "edge", &"n".repeat(71), "pod").unwrap(), name);
+    }
+
+    #[test]
+    fn invalid_router_name_templates_are_rejected() {
+        for template in ["{pod", "{network}-{zone}-{pod}", "{Pod}", "Router-{pod}", "{network}", "router"] {
+            assert!(validate_router_name_template(template).is_err(), "{template}");
+            assert!(router_name(template, "edge", "node", "pod").is_err(), "{template}");
+        }
+        for template in ["{pod}", "{network}-{node}", "rt-{node}-{pod}"] {
+            assert!(validate_router_name_template(template).is_ok(), "{template}");
+        }
+    }
+
+    #[test]
+    fn conditions_keep_the_other_types() {
+        let mut router = router("a", "10.0.0.1", &[]);
+        let healthy = router.data_plane_condition(1, &[]);
+        router.status.as_mut().unwrap().conditions = Some(vec![healthy.clone()]);
+        let conditions = router.with_conditions(vec![router.isolated_condition(0, 1), router.node_degraded_condition(&[])]);
+        let types: Vec<&str> = conditions.iter().map(|c| c.type_.as_str()).collect();
+        assert_eq!(types, [DATA_PLANE_HEALTHY_CONDITION, ISOLATED_CONDITION, NODE_DEGRADED_CONDITION]);
+        assert_eq!(conditions[0], healthy);
+    }
+
+    /// API server keeping a single Router, rejecting status patches of stale resource versions
+    fn router_client(stored: Arc<std::sync::Mutex<serde_json::Value>>) -> kube::Client {
+        let service = tower::service_fn(move |request: http::Request<kube::client::Body>| {
+            let stored = stored.clone();
+            async move {
+                let method = request.method().clone();
+                let body = request.into_body().collect_bytes().await.unwrap();
+                let mut stored = stored.lock().unwrap();
+                let (status, body) = match method {
+                    http::Method::PATCH => {
+                        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        if patch["metadata"]["resourceVersion"] != stored["metadata"]["resourceVersion"] {
+                            (409, json!({ "kind": "Status", "apiVersion": "v1", "status": "Failure", "reason": "Conflict", "code": 409 }))
+                        } else {
+                            stored["status"]["conditions"] = patch["status"]["conditions"].clone();
+                            (200, stored.clone())
+                        }
+                    }
+                    _ => (200, stored.clone()),
+                };
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(kube::client::Body::from(body.to_string().into_bytes()))
+                    .unwrap();
+                Ok::<_, std::convert::Infallible>(response)
+            }
+        });
+        kube::Client::new(service, "default")
+    }
+
+    #[tokio::test]
+    async fn conditions_set_meanwhile_are_kept() {
+        let mut read = router("a", "10.0.0.1", &[]);
+        read.metadata.resource_version = Some("1".to_string());
+        // The sidecar reported the data plane health since the Router was read
+        let mut current = read.clone();
+        current.metadata.resource_version = Some("2".to_string());
+        current.status.as_mut().unwrap().conditions = Some(vec![current.data_plane_condition(1, &[])]);
+        let stored = Arc::new(std::sync::Mutex::new(serde_json::to_value(&current).unwrap()));
+        let api_router = Api::<Router>::namespaced(router_client(stored.clone()), "default");
+        let conditions = vec![read.isolated_condition(0, 1), read.node_degraded_condition(&[])];
+        read.set_conditions(&api_router, None, conditions).await.unwrap();
+        let types: Vec<String> = serde_json::from_value::<Vec<StatusCondition>>(stored.lock().unwrap()["status"]["conditions"].clone())
+            .unwrap()
+            .into_iter()
+            .map(|c| c.type_)
+            .collect();
+        assert_eq!(types, [DATA_PLANE_HEALTHY_CONDITION, ISOLATED_CONDITION, NODE_DEGRADED_CONDITION]);
+    }
+}
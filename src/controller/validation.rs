@@ -0,0 +1,326 @@
+use crate::{Error, Result};
+use kube::core::{Expression, Selector};
+use std::net::{IpAddr, SocketAddr};
+
+/// Face URI schemes understood by ndnd
+pub static FACE_SCHEMES: &[&str] = &["udp", "udp4", "udp6", "tcp", "tcp4", "tcp6", "unix", "ws", "wss"];
+
+/// Split a face URI like `udp://10.0.0.1:6363` into its scheme and authority
+pub fn split_face_uri(uri: &str) -> Result<(&str, &str)> {
+    let (scheme, authority) = uri
+        .split_once("://")
+        .ok_or(Error::InvalidSpec(format!("face URI `{uri}` has no scheme")))?;
+    if authority.is_empty() {
+        return Err(Error::InvalidSpec(format!("face URI `{uri}` has no address")));
+    }
+    Ok((scheme, authority))
+}
+
+/// Validate a face URI scheme. Unless `allow_custom` is set it must be one of `FACE_SCHEMES`,
+/// otherwise any syntactically valid scheme is accepted
+pub fn validate_face_scheme(scheme: &str, allow_custom: bool) -> Result<()> {
+    if FACE_SCHEMES.contains(&scheme) {
+        return Ok(());
+    }
+    if !allow_custom {
+        return Err(Error::InvalidSpec(format!(
+            "unsupported face scheme `{scheme}`, expected one of {FACE_SCHEMES:?}"
+        )));
+    }
+    // RFC 3986: ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    if !valid {
+        return Err(Error::InvalidSpec(format!("`{scheme}` is not a valid URI scheme")));
+    }
+    Ok(())
+}
+
+/// Validate a remote face URI. The host may be a name or an address
+pub fn validate_face_uri(uri: &str, allow_custom: bool) -> Result<()> {
+    let (scheme, _) = split_face_uri(uri)?;
+    validate_face_scheme(scheme, allow_custom)
+}
+
+/// Validate a local face endpoint. It must use the same scheme as the remote URI
+/// and an IP address with a port, e.g. `udp://192.168.1.10:6363` or `udp://[fe80::1]:6363`
+pub fn validate_local_endpoint(local: &str, remote: &str) -> Result<()> {
+    let (local_scheme, authority) = split_face_uri(local)?;
+    let (remote_scheme, _) = split_face_uri(remote)?;
+    if local_scheme != remote_scheme {
+        return Err(Error::InvalidSpec(format!(
+            "local endpoint `{local}` scheme doesn't match remote `{remote}`"
+        )));
+    }
+    authority.parse::<SocketAddr>().map_err(|e| {
+        Error::InvalidSpec(format!("local endpoint `{local}` is not an address with a port: {e}"))
+    })?;
+    Ok(())
+}
+
+/// Paths of fields present in `raw` but missing from `known`, which is the same
+/// object after a round-trip through its typed representation. Null values are
+/// ignored since optional fields are skipped when serialized
+pub fn unknown_fields(raw: &serde_json::Value, known: &serde_json::Value, path: &str) -> Vec<String> {
+    use serde_json::Value;
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => raw
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .flat_map(|(key, value)| {
+                let field_path = format!("{path}.{key}");
+                match known.get(key) {
+                    Some(known_value) => unknown_fields(value, known_value, &field_path),
+                    None => vec![field_path],
+                }
+            })
+            .collect(),
+        (Value::Array(raw), Value::Array(known)) => raw
+            .iter()
+            .zip(known.iter())
+            .enumerate()
+            .flat_map(|(i, (value, known_value))| unknown_fields(value, known_value, &format!("{path}[{i}]")))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Merge `overlay` into `base`: objects are merged key by key, other values are replaced.
+/// Null values in `overlay` are unset optional fields and keep the value of `base`
+pub fn merge_json(base: &mut serde_json::Value, overlay: &serde_json::Value) {
+    use serde_json::Value;
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay.iter().filter(|(_, value)| !value.is_null()) {
+                match base.get_mut(key) {
+                    Some(base_value) => merge_json(base_value, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) if !overlay.is_null() => *base = overlay.clone(),
+        _ => {}
+    }
+}
+
+/// Validate a Kubernetes qualified name, like a label key: an optional DNS subdomain
+/// prefix followed by `/` and a name of at most 63 alphanumerics, `-`, `_` or `.`
+pub fn validate_qualified_name(name: &str) -> Result<()> {
+    let (prefix, local) = match name.split_once('/') {
+        Some((prefix, local)) => (Some(prefix), local),
+        None => (None, name),
+    };
+    if let Some(prefix) = prefix {
+        validate_dns_subdomain(prefix)
+            .map_err(|e| Error::InvalidSpec(format!("prefix of `{name}` is invalid: {e}")))?;
+    }
+    if local.is_empty() || local.len() > 63 {
+        return Err(Error::InvalidSpec(format!("name part of `{name}` must be 1-63 characters")));
+    }
+    if !is_label_value_like(local) {
+        return Err(Error::InvalidSpec(format!(
+            "name part of `{name}` must consist of alphanumerics, `-`, `_` or `.`, and start and end with an alphanumeric"
+        )));
+    }
+    Ok(())
+}
+
+/// Parse a file permission mode written in octal, like `0660`
+pub fn parse_file_mode(mode: &str) -> Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    match u32::from_str_radix(digits, 8) {
+        Ok(parsed) if !digits.is_empty() && parsed <= 0o777 => Ok(parsed),
+        _ => Err(Error::InvalidSpec(format!("`{mode}` is not an octal permission mode between 0000 and 0777"))),
+    }
+}
+
+/// Validate a Kubernetes label value: empty, or at most 63 alphanumerics, `-`, `_` or `.`
+/// starting and ending with an alphanumeric
+pub fn validate_label_value(value: &str) -> Result<()> {
+    if value.len() > 63 {
+        return Err(Error::InvalidSpec(format!("label value `{value}` must be at most 63 characters")));
+    }
+    if !value.is_empty() && !is_label_value_like(value) {
+        return Err(Error::InvalidSpec(format!(
+            "label value `{value}` must consist of alphanumerics, `-`, `_` or `.`, and start and end with an alphanumeric"
+        )));
+    }
+    Ok(())
+}
+
+/// Parse an equality-based label selector, like `team=platform,tier!=dev,canary,!legacy`.
+/// Set-based requirements (`in`, `notin`) are not supported
+pub fn parse_label_selector(selector: &str) -> Result<Selector> {
+    selector
+        .split(',')
+        .map(str::trim)
+        .filter(|requirement| !requirement.is_empty())
+        .map(|requirement| {
+            let (key, value) = match requirement.split_once("!=").or_else(|| requirement.split_once('=')) {
+                Some((key, value)) => (key.trim(), Some(value.trim_start_matches('=').trim())),
+                None => (requirement.trim_start_matches('!').trim(), None),
+            };
+            validate_qualified_name(key)?;
+            if let Some(value) = value {
+                validate_label_value(value)?;
+            }
+            Ok(match value {
+                Some(value) if requirement.contains("!=") => Expression::NotEqual(key.to_string(), value.to_string()),
+                Some(value) => Expression::Equal(key.to_string(), value.to_string()),
+                None if requirement.starts_with('!') => Expression::DoesNotExist(key.to_string()),
+                None => Expression::Exists(key.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Validate a URL to download from: an `http` or `https` scheme and a host
+pub fn validate_http_url(url: &str) -> Result<()> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| Error::InvalidSpec(format!("URL `{url}` must start with `http://` or `https://`")))?;
+    if scheme != "http" && scheme != "https" {
+        return Err(Error::InvalidSpec(format!("URL `{url}` has scheme `{scheme}`, only `http` and `https` are supported")));
+    }
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if host.is_empty() || host.chars().any(char::is_whitespace) {
+        return Err(Error::InvalidSpec(format!("URL `{url}` has no valid host")));
+    }
+    Ok(())
+}
+
+/// Validate a DNS subdomain (RFC 1123): at most 253 characters of dot-separated lowercase labels
+pub fn validate_dns_subdomain(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 253 {
+        return Err(Error::InvalidSpec(format!("`{name}` must be 1-253 characters")));
+    }
+    if !name.split('.').all(is_dns_label) {
+        return Err(Error::InvalidSpec(format!(
+            "`{name}` must consist of lowercase alphanumerics, `-` or `.`, and start and end with an alphanumeric"
+        )));
+    }
+    Ok(())
+}
+
+/// Whether the string is a DNS label (RFC 1123): at most 63 lowercase alphanumerics or `-`,
+/// starting and ending with an alphanumeric
+pub fn is_dns_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && label.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+}
+
+fn is_label_value_like(value: &str) -> bool {
+    let alphanumeric = |c: char| c.is_ascii_alphanumeric();
+    value.starts_with(alphanumeric)
+        && value.ends_with(alphanumeric)
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+/// IP address of a face URI, if its host is an address rather than a name
+pub fn face_ip(uri: &str) -> Option<IpAddr> {
+    let (_, authority) = split_face_uri(uri).ok()?;
+    match authority.parse::<SocketAddr>() {
+        Ok(addr) => Some(addr.ip()),
+        Err(_) => authority.trim_start_matches('[').trim_end_matches(']').parse().ok(),
+    }
+}
+
+/// Components of an NDN name in URI form, percent-decoded so that equivalent
+/// encodings compare equal. The `ndn:` scheme and empty components are ignored
+pub fn name_components(name: &str) -> Vec<Vec<u8>> {
+    name.trim_start_matches("ndn:")
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .map(percent_decode)
+        .collect()
+}
+
+fn percent_decode(component: &str) -> Vec<u8> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Whether one of the NDN names is a prefix of the other, comparing whole components
+/// so `/a/b` overlaps `/a/b/c` but not `/a/bc`
+pub fn prefixes_overlap(a: &str, b: &str) -> bool {
+    let (a, b) = (name_components(a), name_components(b));
+    a.iter().zip(b.iter()).all(|(a, b)| a == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_prefixes_overlap() {
+        assert!(prefixes_overlap("/ndn/edu", "/ndn/edu/ucla"));
+        assert!(prefixes_overlap("/ndn/edu/ucla", "/ndn/edu"));
+        assert!(prefixes_overlap("/ndn/edu", "ndn:/ndn/edu/"));
+    }
+
+    #[test]
+    fn disjoint_prefixes_dont_overlap() {
+        assert!(!prefixes_overlap("/ndn/edu", "/ndn/com"));
+        // Components are compared whole, not as strings
+        assert!(!prefixes_overlap("/ndn/edu", "/ndn/education"));
+    }
+
+    #[test]
+    fn percent_encoded_components_overlap_their_decoded_form() {
+        assert!(prefixes_overlap("/ndn/a%20b", "/ndn/a b/c"));
+    }
+
+    #[test]
+    fn local_endpoints_are_addresses_of_the_face_scheme() {
+        assert!(validate_local_endpoint("udp://192.168.1.10:6363", "udp://10.0.0.1:6363").is_ok());
+        assert!(validate_local_endpoint("udp://[fe80::1]:6363", "udp://router.example:6363").is_ok());
+        // Schemes must match, and the local endpoint is an address with a port, not a name
+        assert!(validate_local_endpoint("tcp://192.168.1.10:6363", "udp://10.0.0.1:6363").is_err());
+        assert!(validate_local_endpoint("udp://192.168.1.10", "udp://10.0.0.1:6363").is_err());
+        assert!(validate_local_endpoint("udp://eth0:6363", "udp://10.0.0.1:6363").is_err());
+    }
+
+    #[test]
+    fn label_selector_requirements_are_parsed() {
+        let selector = parse_label_selector("team=platform, tier!=dev,canary,!legacy,zone==a").unwrap();
+        let expected: Selector = [
+            Expression::Equal("team".to_string(), "platform".to_string()),
+            Expression::NotEqual("tier".to_string(), "dev".to_string()),
+            Expression::Exists("canary".to_string()),
+            Expression::DoesNotExist("legacy".to_string()),
+            Expression::Equal("zone".to_string(), "a".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(selector, expected);
+        assert!(parse_label_selector("").unwrap().selects_all());
+    }
+
+    #[test]
+    fn invalid_label_selectors_are_rejected() {
+        assert!(parse_label_selector("team in (a,b)").is_err());
+        assert!(parse_label_selector("=platform").is_err());
+        assert!(parse_label_selector("team=plat form").is_err());
+    }
+}
@@ -5,6 +5,9 @@ pub enum Error {
     #[error("SerializationError: {0}")]
     SerializationError(#[source] serde_json::Error),
 
+    #[error("YamlSerializationError: {0}")]
+    YamlSerializationError(#[source] serde_yaml::Error),
+
     #[error("Kube Error: {0}")]
     KubeError(#[source] kube::Error),
 
@@ -18,10 +21,13 @@ pub enum Error {
 
     #[error("Missing Label: {0}")]
     MissingLabel(String),
-    
+
     #[error("Missing Annotation: {0}")]
     MissingAnnotation(String),
 
+    #[error("Invalid Spec: {0}")]
+    InvalidSpec(String),
+
     /// NB: this is a catch-all for any other errors
     #[error("Other Error: {0}")]
     OtherError(String),
@@ -33,4 +39,8 @@ pub mod controller;
 pub use crate::ndnd::*;
 
 /// Log and trace integrations
-pub mod telemetry;
\ No newline at end of file
+pub mod telemetry;
+
+/// Prometheus metrics
+#[cfg(feature = "metrics")]
+pub mod metrics;
\ No newline at end of file
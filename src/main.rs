@@ -1,38 +1,172 @@
-use actix_web::{get, middleware, web::Data, App, HttpRequest, HttpResponse, HttpServer, Responder};
-use operator::{self, telemetry, controller::{State, run_nw, run_router, run_pod_sync}};
+use actix_web::{get, middleware, post, web::{Data, Query, ServiceConfig}, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use clap::Parser;
+use kube::{api::ListParams, Api, Client};
+use operator::{self, telemetry, controller::{check_permissions, Config, LeaderLease, MeshGraph, Network, Router, State, NETWORK_LABEL_KEY, run_nw, run_nw_status, run_router, run_pod_sync}};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
 
 #[get("/health")]
 async fn health(_: HttpRequest) -> impl Responder {
     HttpResponse::Ok().json("healthy")
 }
 
+/// Ready once every controller listed its objects, so the pod isn't sent traffic mid-sync.
+/// Replicas waiting for the leader Lease are ready, so rollouts don't wait for the leader
+#[get("/readyz")]
+async fn readyz(c: Data<State>, _req: HttpRequest) -> impl Responder {
+    if c.config().config.leader_election && !c.is_leading() {
+        return HttpResponse::Ok().json("standby");
+    }
+    let pending = c.pending_caches().await;
+    match pending.is_empty() {
+        true => HttpResponse::Ok().json("ready"),
+        false => HttpResponse::ServiceUnavailable().json(json!({ "pendingCaches": pending })),
+    }
+}
+
 #[get("/")]
 async fn index(c: Data<State>, _req: HttpRequest) -> impl Responder {
     let d = c.diagnostics().await;
     HttpResponse::Ok().json(&d)
 }
 
+#[get("/config")]
+async fn effective_config(c: Data<State>, _req: HttpRequest) -> impl Responder {
+    HttpResponse::Ok().json(c.config())
+}
+
+#[derive(Deserialize)]
+struct PathQuery {
+    /// Namespace of the Network, the operator's own by default
+    namespace: Option<String>,
+    network: String,
+    from: String,
+    to: String,
+}
+
+/// Fewest-hop path between two routers of a managed Network over their neighbor links
+#[get("/path")]
+async fn path(c: Data<State>, client: Data<Client>, query: Query<PathQuery>) -> impl Responder {
+    let client = client.get_ref().clone();
+    let namespace = query.namespace.clone().unwrap_or_else(|| client.default_namespace().to_string());
+    let routers = async {
+        let network = Api::<Network>::namespaced(client.clone(), &namespace).get_opt(&query.network).await?;
+        if !network.is_some_and(|network| c.manages(&network)) {
+            return Ok(None);
+        }
+        let lp = ListParams::default().labels(&format!("{NETWORK_LABEL_KEY}={}", query.network));
+        Api::<Router>::namespaced(client, &namespace).list(&lp).await.map(|routers| Some(routers.items))
+    };
+    let routers = match routers.await {
+        Ok(Some(routers)) => routers,
+        Ok(None) => return HttpResponse::NotFound().json(json!({ "error": format!("no managed Network {}/{}", namespace, query.network) })),
+        Err(e) => return HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    };
+    match MeshGraph::new(&routers).shortest_path(&query.from, &query.to) {
+        Ok(Some(path)) => HttpResponse::Ok().json(json!({ "hops": path.len() - 1, "path": path })),
+        Ok(None) => HttpResponse::Ok().json(json!({ "hops": null, "path": null, "error": "the routers are not connected" })),
+        Err(e) => HttpResponse::NotFound().json(json!({ "error": e.to_string() })),
+    }
+}
+
+/// Stop the controllers and release the leader Lease, so another replica takes over without
+/// waiting for the Lease to expire. The operator then exits, and restarts as a standby
+#[post("/step-down")]
+async fn step_down(c: Data<State>, _req: HttpRequest) -> impl Responder {
+    if !c.is_leading() {
+        return HttpResponse::Conflict().json(json!({ "error": "this replica is not the leader" }));
+    }
+    c.step_down();
+    HttpResponse::Accepted().json("stepping down")
+}
+
+#[cfg(feature = "metrics")]
+#[get("/metrics")]
+async fn metrics(c: Data<State>, _req: HttpRequest) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+        .body(c.metrics())
+}
+
+/// Serve the metrics only if they are compiled in
+fn metrics_service(_cfg: &mut ServiceConfig) {
+    #[cfg(feature = "metrics")]
+    _cfg.service(metrics);
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    telemetry::init().await;
+    telemetry::init().await?;
+    let config = Config::parse();
+    let serve_path = config.serve_path;
+    let leader_election = config.leader_election;
+    let client = Client::try_default().await?;
+    // Pods are named by their hostname
+    let leader_lease = leader_election.then(|| {
+        let identity = std::env::var("HOSTNAME").unwrap_or_else(|_| "ndn-operator".to_string());
+        let duration = Duration::from_secs(config.leader_election_lease_seconds);
+        LeaderLease::new(client.clone(), &config.leader_election_lease, &identity, duration)
+    });
+    // Missing RBAC fails the startup, rather than every reconcile later on
+    if let Err(e) = check_permissions(client.clone(), &config).await {
+        tracing::error!("{}", e);
+        return Err(e.into());
+    }
 
     // Initiatilize Kubernetes controller state
-    let state = State::default();
-    let nw_ctrl = run_nw(state.clone());
-    let rt_ctrl = run_router(state.clone());
-    let pod_sync = run_pod_sync(state.clone());
+    let state = State::new(config)?;
+    let controllers = {
+        let state = state.clone();
+        async move {
+            tokio::join!(run_nw(state.clone()), run_nw_status(state.clone()), run_router(state.clone()), run_pod_sync(state));
+        }
+    };
+    let server_state = state.clone();
     let server =  HttpServer::new(move || {
         App::new()
-            .app_data(Data::new(state.clone()))
-            .wrap(middleware::Logger::default().exclude("/health"))
+            .app_data(Data::new(server_state.clone()))
+            .app_data(Data::new(client.clone()))
+            .wrap(middleware::Logger::default().exclude("/health").exclude("/readyz").exclude("/metrics"))
             .service(index)
             .service(health)
+            .service(readyz)
+            .service(effective_config)
+            .configure(|cfg| {
+                if leader_election {
+                    cfg.service(step_down);
+                }
+            })
+            .configure(|cfg| {
+                if serve_path {
+                    cfg.service(path);
+                }
+            })
+            .configure(metrics_service)
     })
     .bind("0.0.0.0:8080")?
-    .shutdown_timeout(5);
+    .shutdown_timeout(5)
+    .run();
+    let server_handle = server.handle();
+    let server = tokio::spawn(server);
 
+    // Standbys wait for the Lease until they are stopped
+    let stop = state.clone();
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+        stop.step_down();
+    });
     // All runtimes implements graceful shutdown, so poll until all are done
-    let (_, _, _, server_result) = tokio::join!(nw_ctrl, rt_ctrl, pod_sync, server.run());
-    server_result?;
+    match leader_lease {
+        Some(leader_lease) => leader_lease.lead(&state, controllers).await,
+        None => controllers.await,
+    }
+    // A replica leads once, it exits and competes for the Lease again once its pod restarts
+    server_handle.stop(true).await;
+    server.await??;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,292 @@
+use chrono::Utc;
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use std::sync::Arc;
+
+#[cfg(feature = "mgmt")]
+use crate::mgmt::ForwarderCounters;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct KindLabels {
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ForwarderLabels {
+    pub network: String,
+    pub router: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RouterLabels {
+    pub namespace: String,
+    pub router: String,
+}
+
+/// Prefix of the operator metrics in the registry
+const PREFIX: &str = "operator";
+
+/// Names the operator metrics are registered under. See `Metrics::series_name` for the
+/// names the series are exposed under
+pub const START_TIME: &str = "start_time_seconds";
+pub const LAST_RECONCILE: &str = "last_reconcile_timestamp_seconds";
+pub const RECONCILIATIONS: &str = "reconciliations";
+pub const RECONCILE_ERRORS: &str = "reconcile_errors";
+pub const ROUTER_NEIGHBORS: &str = "router_neighbors";
+pub const ROUTER_EXPECTED_NEIGHBORS: &str = "router_expected_neighbors";
+pub const ROUTER_ISOLATED: &str = "router_isolated";
+
+/// Prometheus metrics of the operator
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+    /// Unix time the operator started at
+    pub start_time: Gauge,
+    /// Unix time of the last successful reconcile, by object kind
+    pub last_reconcile: Family<KindLabels, Gauge>,
+    /// Successful reconciles, by object kind
+    pub reconciliations: Family<KindLabels, Counter>,
+    /// Failed reconciles, by object kind
+    pub reconcile_errors: Family<KindLabels, Counter>,
+    /// Neighbors in the status of each online router
+    pub router_neighbors: Family<RouterLabels, Gauge>,
+    /// Neighbors each online router should have, the faces of the other online routers
+    pub router_expected_neighbors: Family<RouterLabels, Gauge>,
+    /// 1 if the router has no neighbors while other routers of its Network are online
+    pub router_isolated: Family<RouterLabels, Gauge>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::with_prefix(PREFIX);
+        let start_time = Gauge::default();
+        start_time.set(Utc::now().timestamp());
+        registry.register(
+            START_TIME,
+            "Unix time the operator started at",
+            start_time.clone(),
+        );
+        let last_reconcile = Family::<KindLabels, Gauge>::default();
+        registry.register(
+            LAST_RECONCILE,
+            "Unix time of the last successful reconcile",
+            last_reconcile.clone(),
+        );
+        let reconciliations = Family::<KindLabels, Counter>::default();
+        registry.register(
+            RECONCILIATIONS,
+            "Successful reconciles",
+            reconciliations.clone(),
+        );
+        let reconcile_errors = Family::<KindLabels, Counter>::default();
+        registry.register(
+            RECONCILE_ERRORS,
+            "Failed reconciles",
+            reconcile_errors.clone(),
+        );
+        let router_neighbors = Family::<RouterLabels, Gauge>::default();
+        registry.register(
+            ROUTER_NEIGHBORS,
+            "Neighbors in the status of the router",
+            router_neighbors.clone(),
+        );
+        let router_expected_neighbors = Family::<RouterLabels, Gauge>::default();
+        registry.register(
+            ROUTER_EXPECTED_NEIGHBORS,
+            "Neighbors the router should have, the faces of the other online routers",
+            router_expected_neighbors.clone(),
+        );
+        let router_isolated = Family::<RouterLabels, Gauge>::default();
+        registry.register(
+            ROUTER_ISOLATED,
+            "1 if the router has no neighbors while other routers of its Network are online",
+            router_isolated.clone(),
+        );
+        Self {
+            registry: Arc::new(registry),
+            start_time,
+            last_reconcile,
+            reconciliations,
+            reconcile_errors,
+            router_neighbors,
+            router_expected_neighbors,
+            router_isolated,
+        }
+    }
+}
+
+impl Metrics {
+    /// Record a successful reconcile of an object of the given kind
+    pub fn reconciled(&self, kind: &str) {
+        let labels = KindLabels { kind: kind.to_string() };
+        self.last_reconcile.get_or_create(&labels).set(Utc::now().timestamp());
+        self.reconciliations.get_or_create(&labels).inc();
+    }
+
+    /// Record a failed reconcile of an object of the given kind
+    pub fn reconcile_failed(&self, kind: &str) {
+        self.reconcile_errors
+            .get_or_create(&KindLabels { kind: kind.to_string() })
+            .inc();
+    }
+
+    /// Record the actual and expected neighbor counts of a router
+    pub fn router_neighbors(&self, namespace: &str, router: &str, neighbors: usize, expected: usize) {
+        let labels = RouterLabels {
+            namespace: namespace.to_string(),
+            router: router.to_string(),
+        };
+        self.router_neighbors.get_or_create(&labels).set(neighbors as i64);
+        self.router_expected_neighbors.get_or_create(&labels).set(expected as i64);
+    }
+
+    /// Record whether a router is isolated
+    pub fn router_isolated(&self, namespace: &str, router: &str, isolated: bool) {
+        let labels = RouterLabels {
+            namespace: namespace.to_string(),
+            router: router.to_string(),
+        };
+        self.router_isolated.get_or_create(&labels).set(isolated as i64);
+    }
+
+    /// Name of the series of a registered metric, as found in the registry: the name with
+    /// the registry prefix, and the `_total` suffix of counters
+    pub fn series_name(&self, name: &str) -> Option<String> {
+        let family = format!("{PREFIX}_{name}");
+        self.encode().lines().find_map(|line| {
+            let (family_name, type_) = line.strip_prefix("# TYPE ")?.split_once(' ')?;
+            (family_name == family).then(|| match type_ {
+                "counter" => format!("{family}_total"),
+                _ => family.clone(),
+            })
+        })
+    }
+
+    /// Stop exporting the metrics of a deleted router
+    pub fn router_deleted(&self, namespace: &str, router: &str) {
+        let labels = RouterLabels {
+            namespace: namespace.to_string(),
+            router: router.to_string(),
+        };
+        self.router_neighbors.remove(&labels);
+        self.router_expected_neighbors.remove(&labels);
+        self.router_isolated.remove(&labels);
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("Failed to encode metrics");
+        buffer
+    }
+}
+
+#[cfg(feature = "mgmt")]
+/// Forwarding metrics of a router, served by its sidecar from the general counters of ndnd
+#[derive(Clone)]
+pub struct RoutingMetrics {
+    registry: Arc<Registry>,
+    /// Interests received since ndnd started
+    pub in_interests: Family<ForwarderLabels, Gauge>,
+    /// Data received since ndnd started
+    pub in_data: Family<ForwarderLabels, Gauge>,
+    /// Interests forwarded since ndnd started
+    pub out_interests: Family<ForwarderLabels, Gauge>,
+    /// Data forwarded since ndnd started
+    pub out_data: Family<ForwarderLabels, Gauge>,
+}
+
+#[cfg(feature = "mgmt")]
+impl Default for RoutingMetrics {
+    fn default() -> Self {
+        let mut registry = Registry::with_prefix("ndnd");
+        let in_interests = Family::<ForwarderLabels, Gauge>::default();
+        registry.register("in_interests", "Interests received since ndnd started", in_interests.clone());
+        let in_data = Family::<ForwarderLabels, Gauge>::default();
+        registry.register("in_data", "Data received since ndnd started", in_data.clone());
+        let out_interests = Family::<ForwarderLabels, Gauge>::default();
+        registry.register("out_interests", "Interests forwarded since ndnd started", out_interests.clone());
+        let out_data = Family::<ForwarderLabels, Gauge>::default();
+        registry.register("out_data", "Data forwarded since ndnd started", out_data.clone());
+        Self {
+            registry: Arc::new(registry),
+            in_interests,
+            in_data,
+            out_interests,
+            out_data,
+        }
+    }
+}
+
+#[cfg(feature = "mgmt")]
+impl RoutingMetrics {
+    /// Record the counters ndnd reported for a router of a network
+    pub fn record(&self, network: &str, router: &str, counters: &ForwarderCounters) {
+        let labels = ForwarderLabels {
+            network: network.to_string(),
+            router: router.to_string(),
+        };
+        self.in_interests.get_or_create(&labels).set(counters.n_in_interests as i64);
+        self.in_data.get_or_create(&labels).set(counters.n_in_data as i64);
+        self.out_interests.get_or_create(&labels).set(counters.n_out_interests as i64);
+        self.out_data.get_or_create(&labels).set(counters.n_out_data as i64);
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("Failed to encode metrics");
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_names_are_read_from_the_registry() {
+        let metrics = Metrics::default();
+        metrics.reconciled("Network");
+        metrics.router_neighbors("ns", "router", 1, 2);
+        let encoded = metrics.encode();
+        for name in [START_TIME, LAST_RECONCILE, RECONCILIATIONS, ROUTER_NEIGHBORS, ROUTER_EXPECTED_NEIGHBORS] {
+            let series = metrics.series_name(name).unwrap();
+            assert!(encoded.lines().any(|line| line.starts_with(&format!("{series}{{")) || line.starts_with(&format!("{series} "))), "{series}");
+        }
+        assert_eq!(metrics.series_name(RECONCILIATIONS).as_deref(), Some("operator_reconciliations_total"));
+        assert_eq!(metrics.series_name(RECONCILE_ERRORS).as_deref(), Some("operator_reconcile_errors_total"));
+        assert_eq!(metrics.series_name(ROUTER_ISOLATED).as_deref(), Some("operator_router_isolated"));
+        assert_eq!(metrics.series_name("unregistered"), None);
+    }
+
+    #[test]
+    fn reconciles_update_the_timestamp_of_their_kind() {
+        let metrics = Metrics::default();
+        let last_reconcile = |kind: &str| metrics.last_reconcile.get_or_create(&KindLabels { kind: kind.to_string() }).get();
+        assert_eq!(last_reconcile("Router"), 0);
+        let before = Utc::now().timestamp();
+        metrics.reconciled("Router");
+        assert!(last_reconcile("Router") >= before);
+        assert!(metrics.start_time.get() <= last_reconcile("Router"));
+        // Failures and other kinds leave it
+        metrics.reconcile_failed("Network");
+        assert_eq!(last_reconcile("Network"), 0);
+    }
+
+    #[cfg(feature = "mgmt")]
+    #[test]
+    fn routing_metrics_encode_the_forwarder_counters() {
+        let metrics = RoutingMetrics::default();
+        let counters = ForwarderCounters { n_in_interests: 42, n_in_data: 40, n_out_interests: 41, n_out_data: 39 };
+        metrics.record("net", "router-a", &counters);
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"ndnd_in_interests{network="net",router="router-a"} 42"#));
+        assert!(encoded.contains(r#"ndnd_in_data{network="net",router="router-a"} 40"#));
+        assert!(encoded.contains(r#"ndnd_out_interests{network="net",router="router-a"} 41"#));
+        assert!(encoded.contains(r#"ndnd_out_data{network="net",router="router-a"} 39"#));
+    }
+}
@@ -2,11 +2,67 @@ pub mod dv;
 use dv::RouterConfig;
 pub mod fw;
 use fw::ForwarderConfig;
+#[cfg(feature = "mgmt")]
+pub mod mgmt;
+pub mod transport;
 
-use serde::Serialize;
+use crate::{Error, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
 pub struct NdndConfig {
     pub dv: RouterConfig,
     pub fw: ForwarderConfig,
-}
\ No newline at end of file
+}
+
+impl NdndConfig {
+    /// Serialize the config in the given format
+    pub fn render(&self, format: ConfigFormat) -> Result<String> {
+        match format {
+            ConfigFormat::Yaml => serde_yaml::to_string(self).map_err(Error::YamlSerializationError),
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(Error::SerializationError),
+        }
+    }
+}
+
+/// File format of the generated ndnd config
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yml",
+            ConfigFormat::Json => "json",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_rendered_in_the_given_format() {
+        let config = NdndConfig { dv: RouterConfig::default(), fw: ForwarderConfig::default() };
+        let json: serde_json::Value = serde_json::from_str(&config.render(ConfigFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["dv"]["network"], "ndn");
+        let yaml = config.render(ConfigFormat::Yaml).unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&yaml).is_err(), "{yaml}");
+        let yaml: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(yaml, json);
+    }
+}
@@ -6,7 +6,9 @@ use serde_with::skip_serializing_none;
 pub struct RouterConfig {
     pub network: String,
     pub router: String,
+    /// Milliseconds between the advertisements sent to the neighbors
     pub advertise_interval: Option<u64>,
+    /// Milliseconds without advertisements after which a neighbor is considered dead
     pub router_dead_interval: Option<u64>,
     pub keychain: String,
     pub trust_anchors: Option<Vec<String>>,
@@ -32,5 +34,7 @@ impl Default for RouterConfig {
 pub struct Neighbor {
     pub uri: String,
     pub mtu: Option<u64>,
+    /// Local endpoint the face is bound to
+    pub local: Option<String>,
 }
 
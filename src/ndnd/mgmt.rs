@@ -0,0 +1,175 @@
+use std::process::Stdio;
+
+use crate::{Error, Result};
+
+/// General forwarding counters of the forwarder since it started
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForwarderCounters {
+    pub n_in_interests: u64,
+    pub n_in_data: u64,
+    pub n_out_interests: u64,
+    pub n_out_data: u64,
+}
+
+/// Parse the counters of the general status dataset printed by `ndnd fw status`,
+/// one `key=value` (or `key: value`) line per field, e.g. `nInInterests=42`
+pub fn parse_forwarder_counters(status: &str) -> ForwarderCounters {
+    let mut counters = ForwarderCounters::default();
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(['=', ':']) else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<u64>() else {
+            continue;
+        };
+        match key.trim() {
+            "nInInterests" => counters.n_in_interests = value,
+            "nInData" => counters.n_in_data = value,
+            "nOutInterests" => counters.n_out_interests = value,
+            "nOutData" => counters.n_out_data = value,
+            _ => {}
+        }
+    }
+    counters
+}
+
+/// Fetch the general forwarding counters from the local forwarder through the management client
+pub async fn read_forwarder_counters() -> Result<ForwarderCounters> {
+    let output = tokio::process::Command::new("/ndnd")
+        .args(["fw", "status"])
+        .output()
+        .await
+        .map_err(Error::IoError)?;
+    if !output.status.success() {
+        return Err(Error::OtherError(format!(
+            "ndnd status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_forwarder_counters(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Version in the general status dataset printed by `ndnd fw status`, one `key=value` line
+/// per field with the keys padded to a column, e.g. `     version=v1.4.3`
+pub fn parse_forwarder_version(status: &str) -> Option<String> {
+    status.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        let value = value.trim();
+        (key.trim() == "version" && !value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// Fetch the version of the local forwarder through the management client
+pub async fn read_forwarder_version() -> Result<String> {
+    let output = tokio::process::Command::new("/ndnd")
+        .args(["fw", "status"])
+        .output()
+        .await
+        .map_err(Error::IoError)?;
+    if !output.status.success() {
+        return Err(Error::OtherError(format!(
+            "ndnd status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_forwarder_version(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::OtherError("ndnd status reports no version".to_string()))
+}
+
+/// ID of the face toward the remote URI in the face dataset printed by `ndnd fw face-list`,
+/// one `faceid=<id> remote=<uri> ...` line per face. The address family suffix of the
+/// scheme is ignored, a `udp://` link gets a `udp4://` face
+pub fn parse_face_id(faces: &str, remote: &str) -> Option<u64> {
+    let unqualified = |uri: &str| {
+        uri.split_once("://")
+            .map(|(scheme, address)| (scheme.trim_end_matches(['4', '6']).to_string(), address.trim_end_matches('/').to_string()))
+    };
+    let wanted = unqualified(remote)?;
+    faces.lines().find_map(|line| {
+        let mut fields = line.split_whitespace().filter_map(|field| field.split_once('='));
+        let id = fields.clone().find(|(key, _)| *key == "faceid")?.1.parse().ok()?;
+        let uri = fields.find(|(key, _)| *key == "remote")?.1;
+        (unqualified(uri)? == wanted).then_some(id)
+    })
+}
+
+/// Fetch the ID of the face toward the remote URI through the management client
+pub async fn read_face_id(remote: &str) -> Result<u64> {
+    let output = tokio::process::Command::new("/ndnd")
+        .args(["fw", "face-list"])
+        .output()
+        .await
+        .map_err(Error::IoError)?;
+    if !output.status.success() {
+        return Err(Error::OtherError(format!(
+            "ndnd face-list failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_face_id(&String::from_utf8_lossy(&output.stdout), remote)
+        .ok_or_else(|| Error::OtherError(format!("ndnd has no face toward {remote}")))
+}
+
+/// Answer the interests of the health probes of other routers under the name, until the
+/// returned server is dropped
+pub fn serve_probe(name: &str) -> Result<tokio::process::Child> {
+    tokio::process::Command::new("/ndnd")
+        .args(["pingserver", name])
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(Error::IoError)
+}
+
+/// Express one interest under the name through the local forwarder, and whether data came back
+pub async fn probe(name: &str) -> Result<bool> {
+    let output = tokio::process::Command::new("/ndnd")
+        .args(["ping", "-c", "1", name])
+        .output()
+        .await
+        .map_err(Error::IoError)?;
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarder_counters_are_read_from_the_general_status() {
+        let status = "General NFD status:\n  version=v1.4.3\n  nNameTreeEntries=12\n  nInInterests=42\n  nInData=40\n  nOutInterests=41\n  nOutData=39\n";
+        assert_eq!(
+            parse_forwarder_counters(status),
+            ForwarderCounters { n_in_interests: 42, n_in_data: 40, n_out_interests: 41, n_out_data: 39 }
+        );
+    }
+
+    #[test]
+    fn forwarder_counters_skip_missing_and_malformed_fields() {
+        let status = "nInInterests: 7\nnInData=lots\nuptime=10s\n";
+        assert_eq!(
+            parse_forwarder_counters(status),
+            ForwarderCounters { n_in_interests: 7, ..ForwarderCounters::default() }
+        );
+    }
+
+    #[test]
+    fn forwarder_version_is_read_from_the_general_status() {
+        let status = "General NDNd status:\n         version=v1.4.3\n       startTime=2026-10-15 07:22:13\n  nInInterests=42\n";
+        assert_eq!(parse_forwarder_version(status).as_deref(), Some("v1.4.3"));
+        assert_eq!(parse_forwarder_version("General NDNd status:\n  version=\n  nInData=1\n"), None);
+        // Only the version field, not fields or lines merely mentioning it
+        assert_eq!(parse_forwarder_version("  nfdVersion=1\nversion: v1.4.3\n"), None);
+    }
+
+    #[test]
+    fn face_id_is_found_by_remote_uri() {
+        let faces = "faceid=1 remote=internal:// local=internal://\n\
+                     faceid=267 remote=udp4://10.0.0.2:6363 local=udp4://10.0.0.1:6363 mtu=8800\n\
+                     faceid=268 remote=tcp4://10.0.0.3:6363 local=tcp4://10.0.0.1:40112\n";
+        assert_eq!(parse_face_id(faces, "udp://10.0.0.2:6363"), Some(267));
+        assert_eq!(parse_face_id(faces, "tcp4://10.0.0.3:6363"), Some(268));
+        assert_eq!(parse_face_id(faces, "udp://10.0.0.3:6363"), None);
+        assert_eq!(parse_face_id(faces, "not a uri"), None);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{Error, Result};
+use std::{
+    ffi::{CStr, CString},
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::net::{TcpStream, UnixStream};
+use tracing::*;
+
+/// Resolutions of the host of a TCP transport tried before giving up on a transient failure
+static DNS_ATTEMPTS: u32 = 4;
+/// Wait before the second resolution, doubled for each of the next ones
+static DNS_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Failure to resolve the host of a TCP transport
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The name doesn't exist or has no address, which retrying can't fix
+    Permanent(String),
+    /// The resolver failed or timed out, the next attempt may succeed
+    Transient(String),
+}
+
+impl ResolveError {
+    /// Classify a `getaddrinfo` return code. `EAI_NONAME` (NXDOMAIN), `EAI_NODATA` and
+    /// `EAI_FAIL` are permanent, others like `EAI_AGAIN` (SERVFAIL or a timeout) transient
+    pub fn from_gai_code(code: libc::c_int) -> Self {
+        // SAFETY: gai_strerror returns a static string for any code
+        let message = unsafe { CStr::from_ptr(libc::gai_strerror(code)) }.to_string_lossy().into_owned();
+        match code {
+            libc::EAI_NONAME | libc::EAI_NODATA | libc::EAI_FAIL => ResolveError::Permanent(message),
+            _ => ResolveError::Transient(message),
+        }
+    }
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::Permanent(message) | ResolveError::Transient(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Resolve the host with `getaddrinfo`, which unlike `lookup_host` keeps its return code
+pub fn getaddrinfo(host: &str, port: u16) -> std::result::Result<Vec<SocketAddr>, ResolveError> {
+    let node = CString::new(host).map_err(|_| ResolveError::Permanent(format!("invalid host `{host}`")))?;
+    let service = CString::new(port.to_string()).expect("a port has no NUL byte");
+    // SAFETY: an all-zero addrinfo is the unset hints getaddrinfo expects
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_socktype = libc::SOCK_STREAM;
+    let mut res: *mut libc::addrinfo = std::ptr::null_mut();
+    // SAFETY: the strings are NUL-terminated, and res is freed below once read
+    let code = unsafe { libc::getaddrinfo(node.as_ptr(), service.as_ptr(), &hints, &mut res) };
+    if code != 0 {
+        return Err(ResolveError::from_gai_code(code));
+    }
+    let mut addrs = Vec::new();
+    let mut next = res;
+    while !next.is_null() {
+        // SAFETY: getaddrinfo returned a list of entries, each address matching its family
+        let info = unsafe { &*next };
+        match info.ai_family {
+            libc::AF_INET => {
+                let addr = unsafe { &*(info.ai_addr as *const libc::sockaddr_in) };
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                addrs.push(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port))));
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(info.ai_addr as *const libc::sockaddr_in6) };
+                let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+                addrs.push(SocketAddr::V6(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), addr.sin6_flowinfo, addr.sin6_scope_id)));
+            }
+            _ => {}
+        }
+        next = info.ai_next;
+    }
+    // SAFETY: res came from a successful getaddrinfo and isn't used afterwards
+    unsafe { libc::freeaddrinfo(res) };
+    Ok(addrs)
+}
+
+/// Resolve the host with `resolve`, retrying transient failures `DNS_ATTEMPTS` times in all,
+/// waiting `backoff` before the second attempt and twice as long before each next one.
+/// Permanent failures are returned right away
+pub async fn resolve_with_retry<F, Fut>(host: &str, port: u16, backoff: Duration, resolve: F) -> std::result::Result<Vec<SocketAddr>, ResolveError>
+where
+    F: Fn(String, u16) -> Fut,
+    Fut: Future<Output = std::result::Result<Vec<SocketAddr>, ResolveError>>,
+{
+    let mut backoff = backoff;
+    let mut attempt = 1;
+    loop {
+        match resolve(host.to_string(), port).await {
+            Err(ResolveError::Transient(e)) if attempt < DNS_ATTEMPTS => {
+                debug!("Failed to resolve {host}, retrying in {backoff:?}: {e}");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// `getaddrinfo` on a blocking thread, as it may wait for the DNS servers
+async fn resolve_blocking(host: String, port: u16) -> std::result::Result<Vec<SocketAddr>, ResolveError> {
+    tokio::task::spawn_blocking(move || getaddrinfo(&host, port))
+        .await
+        .unwrap_or_else(|e| Err(ResolveError::Transient(e.to_string())))
+}
+
+/// Management transport of ndnd, as set in `NDN_CLIENT_TRANSPORT`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// `unix:///run/ndnd/ndnd.sock`
+    Unix(String),
+    /// `tcp://host:port`, `tcp4://` or `tcp6://`
+    Tcp { host: String, port: u16 },
+}
+
+impl FromStr for Transport {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::OtherError(format!("invalid transport `{s}`: {reason}"));
+        let (scheme, rest) = s.split_once("://").ok_or_else(|| invalid("missing scheme"))?;
+        match scheme {
+            "unix" => Ok(Transport::Unix(rest.to_string())),
+            "tcp" | "tcp4" | "tcp6" => {
+                let (host, port) = rest.rsplit_once(':').ok_or_else(|| invalid("missing port"))?;
+                let port = port.parse().map_err(|_| invalid("invalid port"))?;
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                if host.is_empty() {
+                    return Err(invalid("missing host"));
+                }
+                Ok(Transport::Tcp { host: host.to_string(), port })
+            }
+            _ => Err(invalid("only unix and tcp transports are supported")),
+        }
+    }
+}
+
+impl Transport {
+    /// Path of the socket of a unix transport
+    pub fn socket_path(&self) -> Option<&str> {
+        match self {
+            Transport::Unix(path) => Some(path),
+            Transport::Tcp { .. } => None,
+        }
+    }
+
+    /// Whether ndnd accepts connections on the transport. The host of a TCP transport is
+    /// resolved on each call, transient resolution failures are retried with backoff, and
+    /// a name that doesn't exist fails at once
+    pub async fn connectable(&self) -> Result<bool> {
+        match self {
+            Transport::Unix(path) => Ok(UnixStream::connect(path).await.is_ok()),
+            Transport::Tcp { host, port } => {
+                let addrs = resolve_with_retry(host, *port, DNS_BACKOFF, resolve_blocking)
+                    .await
+                    .map_err(|e| Error::OtherError(format!("Failed to resolve {host}: {e}")))?;
+                for addr in addrs {
+                    if TcpStream::connect(addr).await.is_ok() {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn transports_are_parsed() {
+        assert_eq!("unix:///run/ndnd/ndnd.sock".parse::<Transport>().unwrap(), Transport::Unix("/run/ndnd/ndnd.sock".to_string()));
+        assert_eq!(
+            "tcp://ndnd.local:6363".parse::<Transport>().unwrap(),
+            Transport::Tcp { host: "ndnd.local".to_string(), port: 6363 }
+        );
+        assert_eq!("tcp6://[::1]:6363".parse::<Transport>().unwrap(), Transport::Tcp { host: "::1".to_string(), port: 6363 });
+    }
+
+    #[test]
+    fn invalid_transports_are_rejected() {
+        for transport in ["/run/ndnd/ndnd.sock", "udp://10.0.0.1:6363", "tcp://10.0.0.1", "tcp://10.0.0.1:port", "tcp://:6363"] {
+            assert!(transport.parse::<Transport>().is_err(), "{transport}");
+        }
+    }
+
+    #[test]
+    fn gai_codes_are_classified() {
+        for code in [libc::EAI_NONAME, libc::EAI_NODATA, libc::EAI_FAIL] {
+            assert!(matches!(ResolveError::from_gai_code(code), ResolveError::Permanent(_)), "{code}");
+        }
+        for code in [libc::EAI_AGAIN, libc::EAI_MEMORY, libc::EAI_SYSTEM] {
+            assert!(matches!(ResolveError::from_gai_code(code), ResolveError::Transient(_)), "{code}");
+        }
+    }
+
+    #[test]
+    fn localhost_is_resolved() {
+        let addrs = getaddrinfo("127.0.0.1", 6363).unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:6363".parse().unwrap()]);
+        assert!(matches!(getaddrinfo("ndnd\0", 6363), Err(ResolveError::Permanent(_))));
+    }
+
+    /// Resolver failing with `error` the first `failures` times, then resolving to localhost
+    fn flaky_resolver(calls: Arc<AtomicU32>, failures: u32, error: ResolveError) -> impl Fn(String, u16) -> std::future::Ready<std::result::Result<Vec<SocketAddr>, ResolveError>> {
+        move |_, port| {
+            let result = match calls.fetch_add(1, Ordering::SeqCst) < failures {
+                true => Err(error.clone()),
+                false => Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))]),
+            };
+            std::future::ready(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let resolver = flaky_resolver(calls.clone(), 2, ResolveError::Transient("SERVFAIL".to_string()));
+        let addrs = resolve_with_retry("ndnd", 6363, Duration::from_millis(1), resolver).await.unwrap();
+        assert_eq!(addrs, vec![SocketAddr::from(([127, 0, 0, 1], 6363))]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_give_up() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let resolver = flaky_resolver(calls.clone(), u32::MAX, ResolveError::Transient("SERVFAIL".to_string()));
+        assert!(resolve_with_retry("ndnd", 6363, Duration::from_millis(1), resolver).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), DNS_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let resolver = flaky_resolver(calls.clone(), u32::MAX, ResolveError::Permanent("NXDOMAIN".to_string()));
+        let result = resolve_with_retry("ndnd", 6363, Duration::from_millis(1), resolver).await;
+        assert_eq!(result, Err(ResolveError::Permanent("NXDOMAIN".to_string())));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
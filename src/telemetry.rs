@@ -1,14 +1,91 @@
-use tracing_subscriber::{prelude::*, EnvFilter, Registry};
+use crate::{Error, Result};
+use std::{fs::OpenOptions, sync::Mutex};
+use tracing_subscriber::{filter::filter_fn, fmt::writer::BoxMakeWriter, prelude::*, EnvFilter, Registry};
 
-/// Initialize tracing
-pub async fn init() {
+/// Target of the audit records of the mutating API calls, see `controller::audit`
+pub const AUDIT_TARGET: &str = "audit";
+
+/// Tracer exporting spans to the collector at `OTEL_EXPORTER_OTLP_ENDPOINT`
+#[cfg(feature = "otlp")]
+fn init_tracer() -> opentelemetry_sdk::trace::SdkTracer {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .build()
+        .expect("Failed to build the OTLP span exporter");
+    let provider = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(env!("CARGO_PKG_NAME")).build())
+        .with_batch_exporter(exporter)
+        .build();
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    provider.tracer("ndn-operator")
+}
+
+/// Writer of the audit records to the destination set by `NDN_OPERATOR_AUDIT_LOG`: `stdout`,
+/// `stderr` or a file appended to. Unset, the records are part of the normal logs
+fn audit_writer(destination: Option<&str>) -> Result<Option<BoxMakeWriter>> {
+    let Some(destination) = destination.filter(|destination| !destination.is_empty()) else {
+        return Ok(None);
+    };
+    Ok(Some(match destination {
+        "stdout" => BoxMakeWriter::new(std::io::stdout),
+        "stderr" => BoxMakeWriter::new(std::io::stderr),
+        path => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| Error::OtherError(format!("Failed to open the audit log {path}: {e}")))?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+    }))
+}
+
+/// Initialize tracing. Fails if the audit log can't be opened
+pub async fn init() -> Result<()> {
 
-    let logger = tracing_subscriber::fmt::layer().compact();
     let env_filter = EnvFilter::try_from_default_env()
         .or(EnvFilter::try_new("info"))
         .unwrap();
+    // Audit records routed to their own writer are kept whatever the log level,
+    // as JSON lines, and left out of the normal logs
+    let audit_writer = audit_writer(std::env::var("NDN_OPERATOR_AUDIT_LOG").ok().as_deref())?;
+    let separate_audit = audit_writer.is_some();
+    let env_filter = match separate_audit {
+        true => env_filter.add_directive(format!("{AUDIT_TARGET}=info").parse().unwrap()),
+        false => env_filter,
+    };
+    let logger = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_filter(filter_fn(move |metadata| !separate_audit || metadata.target() != AUDIT_TARGET));
+    let audit = audit_writer.map(|writer| {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_writer(writer)
+            .with_filter(filter_fn(|metadata| metadata.target() == AUDIT_TARGET))
+    });
 
     // Decide on layers
     let reg = Registry::default();
-    reg.with(env_filter).with(logger).init();
+    #[cfg(feature = "otlp")]
+    let reg = reg.with(tracing_opentelemetry::layer().with_tracer(init_tracer()));
+    reg.with(env_filter).with(logger).with(audit).init();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit_log_that_cant_be_opened_is_an_error() {
+        assert!(audit_writer(None).unwrap().is_none());
+        assert!(audit_writer(Some("")).unwrap().is_none());
+        assert!(audit_writer(Some("stderr")).unwrap().is_some());
+        assert!(audit_writer(Some("/nonexistent/audit.log")).is_err());
+    }
 }